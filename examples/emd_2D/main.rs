@@ -23,9 +23,9 @@ fn main() {
     let target = ot::utils::sample_2D_gauss(n_samples, &mu_target, &cov_target).unwrap();
 
     // Uniform distribution on the source and target samples
-    let mut source_mass =
+    let source_mass =
         Array1::<f64>::from_vec(vec![1f64 / (n_samples as f64); n_samples as usize]);
-    let mut target_mass =
+    let target_mass =
         Array1::<f64>::from_vec(vec![1f64 / (n_samples as f64); n_samples as usize]);
 
     // Compute ground cost matrix - Squared Euclidean distance
@@ -36,11 +36,10 @@ fn main() {
     ground_cost = &ground_cost / *max_cost;
 
     // Compute optimal transport matrix as the Earth Mover's Distance
-    let ot_matrix =
-        match EarthMovers::new(&mut source_mass, &mut target_mass, &mut ground_cost).solve() {
-            Ok(result) => result,
-            Err(error) => panic!("{:?}", error),
-        };
+    let ot_matrix = match EarthMovers::new(&source_mass, &target_mass, &ground_cost).solve() {
+        Ok(result) => result.plan,
+        Err(error) => panic!("{:?}", error),
+    };
 
     // Plot using matplotlib
     match plot::plot_py(&source, &target, &ot_matrix) {
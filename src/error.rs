@@ -21,6 +21,16 @@ pub enum OTError {
         source: exact::FastTransportErrorCode,
     },
 
+    #[error(
+        "Solver did not converge after {iterations:?} iterations, \
+            error {error:?} is above the stop threshold {threshold:?}"
+    )]
+    ConvergenceError {
+        iterations: i32,
+        error: f64,
+        threshold: f64,
+    },
+
     #[error("Invalid argument: '{0}'")]
     ArgError(String),
 
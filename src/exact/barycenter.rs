@@ -0,0 +1,401 @@
+use ndarray::prelude::*;
+use ndarray::{Data, ScalarOperand};
+use num_traits::Float;
+
+use super::EarthMovers;
+use crate::error::OTError;
+use crate::metrics::{dist, MetricType};
+use crate::parallel::Element;
+use crate::{OTSolver, SolverLog};
+
+/// Computes the free-support Wasserstein barycenter of weighted point clouds and returns the
+/// locations of the barycenter support
+///
+/// The barycenter minimizes sum_k w_k W_2^2(mu_k, nu) over the locations of a fixed number of
+/// weighted points. Each iteration solves the Earth Movers Distance between the current
+/// support and every point cloud with a squared euclidean cost, then moves each support point
+/// to the weighted barycentric projection of its transport plans.
+///
+/// Fast Computation of Wasserstein Barycenters
+/// by Marco Cuturi, Arnaud Doucet
+///
+/// ```rust
+/// use rust_optimal_transport as ot;
+/// use ot::prelude::*;
+/// use ndarray::prelude::*;
+///
+/// // Two point clouds, the second one is the first one translated by (4, 4)
+/// let n = 20;
+/// let cov = array![[1., 0.], [0., 1.]];
+/// let source = ot::utils::sample_2D_gauss(n, &array![0., 0.], &cov).unwrap();
+/// let target = &source + &array![4., 4.];
+///
+/// let locations = vec![source.clone(), target];
+/// let weights = vec![Array1::<f64>::zeros(0), Array1::<f64>::zeros(0)];
+///
+/// // Initial support of the barycenter and uniform weights on the point clouds
+/// let init_support = source.clone();
+/// let barycenter_weights = Array1::<f64>::zeros(0);
+///
+/// let support = match FreeSupportBarycenter::new(
+///     &locations,
+///     &weights,
+///     &init_support,
+///     &barycenter_weights,
+/// )
+/// .solve()
+/// {
+///     Ok(result) => result,
+///     Err(error) => panic!("{:?}", error),
+/// };
+///
+/// // The barycenter is the point cloud translated halfway
+/// assert!(support.abs_diff_eq(&(&source + &array![2., 2.]), 1E-9));
+///
+/// ```
+///
+/// locations: Point clouds, each (n_k, dim) array holds the samples of one distribution
+/// weights: Sample weights of each point cloud (uniform weights if empty)
+/// init_support: (k, dim) initial locations of the barycenter support
+/// barycenter_weights: Weights of the point clouds in the barycenter (uniform if empty)
+///
+/// The mass of the support points is uniform unless set with `support_weights`.
+///
+pub struct FreeSupportBarycenter<'a, A> {
+    locations: Vec<ArrayView2<'a, A>>,
+    weights: Vec<ArrayView1<'a, A>>,
+    init_support: ArrayView2<'a, A>,
+    barycenter_weights: ArrayView1<'a, A>,
+    support_weights: Option<ArrayView1<'a, A>>,
+    iterations: i32,
+    emd_iterations: i32,
+    threshold: A,
+    strict: bool,
+    log: SolverLog,
+}
+
+impl<'a, A> FreeSupportBarycenter<'a, A>
+where
+    A: Float + ScalarOperand,
+{
+    pub fn new<S1, S2, S3, S4>(
+        locations: &'a [ArrayBase<S1, Ix2>],
+        weights: &'a [ArrayBase<S2, Ix1>],
+        init_support: &'a ArrayBase<S3, Ix2>,
+        barycenter_weights: &'a ArrayBase<S4, Ix1>,
+    ) -> Self
+    where
+        S1: Data<Elem = A>,
+        S2: Data<Elem = A>,
+        S3: Data<Elem = A>,
+        S4: Data<Elem = A>,
+    {
+        Self {
+            locations: locations.iter().map(|x| x.view()).collect(),
+            weights: weights.iter().map(|w| w.view()).collect(),
+            init_support: init_support.view(),
+            barycenter_weights: barycenter_weights.view(),
+            support_weights: None,
+            iterations: 100,
+            emd_iterations: 100000,
+            threshold: A::from(1E-7).unwrap(),
+            strict: false,
+            log: SolverLog::default(),
+        }
+    }
+
+    pub fn iterations<'b>(&'b mut self, iterations: i32) -> &'b mut Self {
+        self.iterations = iterations;
+        self
+    }
+
+    /// Max number of iterations of the network simplex solving each transport plan
+    pub fn emd_iterations<'b>(&'b mut self, emd_iterations: i32) -> &'b mut Self {
+        self.emd_iterations = emd_iterations;
+        self
+    }
+
+    /// Stop threshold on the squared displacement of the support between two iterations
+    pub fn threshold<'b>(&'b mut self, threshold: A) -> &'b mut Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Returns a ConvergenceError if the stop threshold is not reached within the max number
+    /// of iterations
+    pub fn strict<'b>(&'b mut self, strict: bool) -> &'b mut Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Mass of each point of the barycenter support, must be > 0
+    pub fn support_weights<'b, S>(
+        &'b mut self,
+        support_weights: &'a ArrayBase<S, Ix1>,
+    ) -> &'b mut Self
+    where
+        S: Data<Elem = A>,
+    {
+        self.support_weights = Some(support_weights.view());
+        self
+    }
+}
+
+impl<'a, A> OTSolver for FreeSupportBarycenter<'a, A>
+where
+    A: Float + ScalarOperand + Element,
+{
+    type Output = Array2<A>;
+
+    /// Ensures every point cloud has one weight per sample and lives in the dimension of the
+    /// barycenter support
+    fn check_shape(&self) -> Result<(), OTError> {
+        let n_measures = self.locations.len();
+        let (k, dim) = self.init_support.dim();
+
+        if self.weights.len() != n_measures {
+            return Err(OTError::ArgError(
+                "Number of sample weights does not match the number of point clouds".to_string(),
+            ));
+        }
+
+        for (x, w) in self.locations.iter().zip(&self.weights) {
+            if x.ncols() != dim {
+                return Err(OTError::ArgError(
+                    "Point cloud and barycenter support have different dimensions".to_string(),
+                ));
+            }
+
+            if !w.is_empty() && w.len() != x.nrows() {
+                return Err(OTError::ArgError(
+                    "Sample weights and samples have different lengths".to_string(),
+                ));
+            }
+        }
+
+        if !self.barycenter_weights.is_empty() && self.barycenter_weights.len() != n_measures {
+            return Err(OTError::ArgError(
+                "Number of barycenter weights does not match the number of point clouds"
+                    .to_string(),
+            ));
+        }
+
+        if let Some(b) = self.support_weights {
+            if b.len() != k {
+                return Err(OTError::ArgError(
+                    "Support weights and barycenter support have different lengths".to_string(),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn solve(&mut self) -> Result<Array2<A>, OTError> {
+        self.check_shape()?;
+
+        if self.iterations <= 0 {
+            return Err(OTError::ArgError(
+                "Iterations not a valid value. Must be > 0".to_string(),
+            ));
+        }
+
+        if self.locations.is_empty() || self.init_support.nrows() == 0 {
+            return Err(OTError::ArgError(
+                "No point clouds or empty barycenter support".to_string(),
+            ));
+        }
+
+        if self.locations.iter().any(|x| x.nrows() == 0) {
+            return Err(OTError::ArgError("Empty point cloud".to_string()));
+        }
+
+        let uniform = |n: usize| Array1::<A>::from_elem(n, A::one() / A::from(n).unwrap());
+
+        // Normalized barycenter weights, on a copy
+        let mut lambdas = if self.barycenter_weights.is_empty() {
+            uniform(self.locations.len())
+        } else {
+            self.barycenter_weights.to_owned()
+        };
+        let total = lambdas.sum();
+        lambdas.mapv_inplace(|w| w / total);
+
+        let support_weights = match self.support_weights {
+            Some(b) => b.to_owned(),
+            None => uniform(self.init_support.nrows()),
+        };
+
+        if support_weights.iter().any(|&w| w <= A::zero()) {
+            return Err(OTError::ArgError("Support weight <= 0".to_string()));
+        }
+
+        let weights: Vec<Array1<A>> = self
+            .locations
+            .iter()
+            .zip(&self.weights)
+            .map(|(x, w)| {
+                if w.is_empty() {
+                    uniform(x.nrows())
+                } else {
+                    w.to_owned()
+                }
+            })
+            .collect();
+
+        let (support, log) = free_support_barycenter(
+            &self.locations,
+            &weights,
+            self.init_support,
+            &lambdas,
+            &support_weights,
+            self.iterations,
+            self.emd_iterations,
+            self.threshold,
+        )?;
+
+        self.log = log;
+
+        if self.strict {
+            self.log.check_convergence(self.threshold)?;
+        }
+
+        Ok(support)
+    }
+
+    fn log(&self) -> &SolverLog {
+        &self.log
+    }
+}
+
+/// Alternates the Earth Movers plans between the support and each point cloud with the
+/// barycentric update of the support locations
+///
+/// locations: Point clouds, (n_k, dim)
+/// weights: Sample weights of each point cloud
+/// init_support: Initial barycenter support, (k, dim)
+/// lambdas: Normalized weights of the point clouds in the barycenter
+/// support_weights: Mass of each point of the support, > 0
+/// iterations: Max number of support updates
+/// emd_iterations: Max number of iterations of the network simplex
+/// threshold: Stop threshold on the squared displacement of the support
+#[allow(clippy::too_many_arguments)]
+fn free_support_barycenter<A>(
+    locations: &[ArrayView2<A>],
+    weights: &[Array1<A>],
+    init_support: ArrayView2<A>,
+    lambdas: &Array1<A>,
+    support_weights: &Array1<A>,
+    iterations: i32,
+    emd_iterations: i32,
+    threshold: A,
+) -> Result<(Array2<A>, SolverLog), OTError>
+where
+    A: Float + ScalarOperand + Element,
+{
+    let mut log = SolverLog::default();
+    let mut support = init_support.to_owned();
+
+    for count in 0..iterations {
+        let mut update = Array2::<A>::zeros(support.dim());
+
+        for ((x, w), &lambda) in locations.iter().zip(weights).zip(lambdas) {
+            let cost = dist(&support, x, MetricType::SqEuclidean);
+
+            // A truncated network simplex plan would bias the support update
+            let plan = EarthMovers::new(support_weights, w, &cost)
+                .iterations(emd_iterations)
+                .strict(true)
+                .solve()?
+                .plan;
+
+            // The target weights are rescaled to the mass of the support
+            update = update + plan.dot(x) * lambda;
+        }
+
+        // Barycentric projection of the plans
+        let support_prev = support;
+        support = update / support_weights.view().insert_axis(Axis(1));
+
+        let displacement = (&support - &support_prev).mapv(|d| d * d).sum();
+
+        log.iterations = count + 1;
+        log.push_change(displacement, displacement);
+
+        if displacement < threshold {
+            log.converged = true;
+            break;
+        }
+    }
+
+    Ok((support, log))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use ndarray::prelude::*;
+
+    use crate::OTSolver;
+
+    #[test]
+    fn test_free_support_barycenter() {
+        // Two segments at y = 0 and y = 2
+        let locations = vec![
+            array![[0., 0.], [1., 0.], [2., 0.]],
+            array![[0., 2.], [1., 2.], [2., 2.]],
+        ];
+        let weights = vec![Array1::<f64>::zeros(0), array![0.2, 0.3, 0.5]];
+
+        let init_support = array![[0.5, 0.5], [1.5, 1.5], [2.5, 0.5]];
+        let barycenter_weights = array![1., 1.];
+        let support_weights = array![0.2, 0.3, 0.5];
+
+        let mut solver = super::FreeSupportBarycenter::new(
+            &locations,
+            &weights,
+            &init_support,
+            &barycenter_weights,
+        );
+
+        let support = match solver.support_weights(&support_weights).solve() {
+            Ok(result) => result,
+            Err(error) => panic!("{:?}", error),
+        };
+
+        assert!(solver.log().converged);
+
+        // The support lies halfway between both segments and spans them
+        for y in support.column(1) {
+            assert!((y - 1.).abs() < 1E-9);
+        }
+
+        let mut x = support.column(0).to_vec();
+        x.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert!(x[0] >= 0. && x[2] <= 2.);
+
+        // Inputs are untouched
+        assert_eq!(init_support, array![[0.5, 0.5], [1.5, 1.5], [2.5, 0.5]]);
+
+        // Truncated network simplex plans are rejected
+        assert!(solver.emd_iterations(1).solve().is_err());
+    }
+
+    #[test]
+    fn test_free_support_barycenter_shape() {
+        let locations = vec![array![[0., 0.], [1., 0.]], array![[0., 2., 1.]]];
+        let weights = vec![Array1::<f64>::zeros(0), Array1::<f64>::zeros(0)];
+        let init_support = array![[0., 1.]];
+        let barycenter_weights = Array1::<f64>::zeros(0);
+
+        let result = super::FreeSupportBarycenter::new(
+            &locations,
+            &weights,
+            &init_support,
+            &barycenter_weights,
+        )
+        .solve();
+
+        assert!(result.is_err());
+    }
+}
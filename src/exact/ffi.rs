@@ -23,6 +23,9 @@ mod ffi {
 
 /// Wrapper of C++ FastTransport OT Network Simplex solver
 /// Returns 1 on success
+///
+/// The C++ solver reads the inputs as contiguous row-major buffers, they must be in standard
+/// layout
 #[allow(non_snake_case)]
 pub fn emd_c(
     a: &mut Array1<f64>,
@@ -49,6 +52,11 @@ pub fn emd_c(
         *b = Array1::from_vec(vec![1f64 / (n2 as f64); n2]);
     }
 
+    assert!(
+        a.is_standard_layout() && b.is_standard_layout() && M.is_standard_layout(),
+        "emd_c inputs must be contiguous and in standard layout"
+    );
+
     unsafe {
         let result_code = ffi::EMD_wrap(
             n1 as i32,
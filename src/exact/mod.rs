@@ -1,18 +1,23 @@
+pub mod barycenter;
 mod ffi;
+pub mod partial;
+pub mod solver_1d;
 mod utils;
 
 use ndarray::prelude::*;
+use ndarray::Data;
+use num_traits::Float;
 use std::error::Error;
 use std::fmt;
 
 use super::error::OTError;
-use super::OTSolver;
+use super::{OTSolver, SolverLog};
 use ffi::emd_c;
 use utils::*;
 
 /// Return codes from the FastTransport network simplex solver
 /// FastTransport returns 1 on success
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum FastTransportErrorCode {
     /// No feasible flow exists for the problem
     IsInfeasible,
@@ -52,6 +57,21 @@ impl From<i32> for FastTransportErrorCode {
     }
 }
 
+/// Solution of the unregularized Optimal Transport problem
+#[derive(Debug)]
+pub struct EMDResult<A> {
+    /// Optimal transport matrix
+    pub plan: Array2<A>,
+    /// Total transport cost <plan, M>
+    pub cost: A,
+    /// Source dual potential
+    pub alpha: Array1<A>,
+    /// Target dual potential
+    pub beta: Array1<A>,
+    /// Return code of the network simplex solver
+    pub status: FastTransportErrorCode,
+}
+
 /// Solves the unregularized Optimal Transport (Earth Movers Distance) between source and target distributions with a given cost matrix.
 ///
 /// ```rust
@@ -76,8 +96,8 @@ impl From<i32> for FastTransportErrorCode {
 /// let target = ot::utils::sample_2D_gauss(n, &mu_target, &cov_target).unwrap();
 ///
 /// // Uniform weights on the source and target distributions
-/// let mut source_weights = Array1::<f64>::from_elem(n, 1. / (n as f64));
-/// let mut target_weights = Array1::<f64>::from_elem(n, 1. / (n as f64));
+/// let source_weights = Array1::<f64>::from_elem(n, 1. / (n as f64));
+/// let target_weights = Array1::<f64>::from_elem(n, 1. / (n as f64));
 ///
 /// // Compute the cost between distributions
 /// let mut cost = dist(&source, &target, SqEuclidean);
@@ -88,11 +108,11 @@ impl From<i32> for FastTransportErrorCode {
 ///
 /// // Compute optimal transport matrix as the Earth Mover's Distance
 /// let ot_matrix = match EarthMovers::new(
-///     &mut source_weights,
-///     &mut target_weights,
-///     &mut cost
+///     &source_weights,
+///     &target_weights,
+///     &cost
 /// ).solve() {
-///     Ok(result) => result,
+///     Ok(result) => result.plan,
 ///     Err(error) => panic!("{:?}", error),
 /// };
 ///
@@ -101,24 +121,48 @@ impl From<i32> for FastTransportErrorCode {
 /// source_weights and target_weights represent histograms of the Source and Target distributions,
 /// respectively.
 ///
-pub struct EarthMovers<'a> {
-    source_weights: &'a mut Array1<f64>,
-    target_weights: &'a mut Array1<f64>,
-    cost: &'a mut Array2<f64>,
+/// Along with the OT matrix, the solver returns the total transport cost, the centered dual
+/// potentials and the status of the network simplex. See [`EMDResult`].
+///
+/// The network simplex works in double precision, f32 inputs are converted on the way in and
+/// the solution is converted back on the way out.
+///
+/// The inputs are never modified. The target weights are rescaled to the total mass of the
+/// source weights on an internal copy.
+///
+/// Reaching the max number of iterations of the network simplex is an error unless strict mode
+/// is disabled.
+///
+pub struct EarthMovers<'a, A> {
+    source_weights: ArrayView1<'a, A>,
+    target_weights: ArrayView1<'a, A>,
+    cost: ArrayView2<'a, A>,
     iterations: i32,
+    strict: bool,
+    log: SolverLog,
 }
 
-impl<'a> EarthMovers<'a> {
-    pub fn new(
-        source_weights: &'a mut Array1<f64>,
-        target_weights: &'a mut Array1<f64>,
-        cost: &'a mut Array2<f64>,
-    ) -> Self {
+impl<'a, A> EarthMovers<'a, A>
+where
+    A: Float,
+{
+    pub fn new<S1, S2, S3>(
+        source_weights: &'a ArrayBase<S1, Ix1>,
+        target_weights: &'a ArrayBase<S2, Ix1>,
+        cost: &'a ArrayBase<S3, Ix2>,
+    ) -> Self
+    where
+        S1: Data<Elem = A>,
+        S2: Data<Elem = A>,
+        S3: Data<Elem = A>,
+    {
         Self {
-            source_weights,
-            target_weights,
-            cost,
+            source_weights: source_weights.view(),
+            target_weights: target_weights.view(),
+            cost: cost.view(),
             iterations: 100000,
+            strict: true,
+            log: SolverLog::default(),
         }
     }
 
@@ -126,9 +170,21 @@ impl<'a> EarthMovers<'a> {
         self.iterations = iterations;
         self
     }
+
+    /// Returns an error if the network simplex reaches the max number of iterations, enabled by
+    /// default. When disabled, the truncated plan is returned with the IsMaxIterReached status
+    pub fn strict<'b>(&'b mut self, strict: bool) -> &'b mut Self {
+        self.strict = strict;
+        self
+    }
 }
 
-impl<'a> OTSolver for EarthMovers<'a> {
+impl<'a, A> OTSolver for EarthMovers<'a, A>
+where
+    A: Float,
+{
+    type Output = EMDResult<A>;
+
     fn check_shape(&self) -> Result<(), OTError> {
         let mshape = self.cost.shape();
         let m0 = mshape[0];
@@ -148,7 +204,7 @@ impl<'a> OTSolver for EarthMovers<'a> {
         Ok(())
     }
 
-    fn solve(&mut self) -> Result<Array2<f64>, OTError> {
+    fn solve(&mut self) -> Result<EMDResult<A>, OTError> {
         self.check_shape()?;
 
         if self.iterations <= 0 {
@@ -157,37 +213,96 @@ impl<'a> OTSolver for EarthMovers<'a> {
             ));
         }
 
-        *self.target_weights *= self.source_weights.sum() / self.target_weights.sum();
+        // The network simplex works in double precision, on contiguous row-major copies of the
+        // inputs. Iterating in logical order drops the strides of transposed or reversed views
+        let to_f64 = |&x: &A| x.to_f64().unwrap();
+        let mut a: Array1<f64> = self.source_weights.iter().map(to_f64).collect();
+        let mut b: Array1<f64> = self.target_weights.iter().map(to_f64).collect();
+        let mut m = Array2::from_shape_vec(self.cost.dim(), self.cost.iter().map(to_f64).collect())
+            .unwrap();
 
-        emd(
-            self.source_weights,
-            self.target_weights,
-            self.cost,
-            self.iterations,
-        )
+        b *= a.sum() / b.sum();
+
+        let result = emd(&mut a, &mut b, &mut m, self.iterations)?;
+
+        // The network simplex does not report its number of iterations
+        let err = (&result.plan.sum_axis(Axis(1)) - &a).mapv(f64::abs).sum()
+            + (&result.plan.sum_axis(Axis(0)) - &b).mapv(f64::abs).sum();
+
+        self.log = SolverLog {
+            marginal_violation: vec![err],
+            error: err,
+            converged: result.status == FastTransportErrorCode::IsOptimal,
+            ..SolverLog::default()
+        };
+
+        if self.strict {
+            check_result(result.status)?;
+        }
+
+        let from_f64 = |x: f64| A::from(x).unwrap();
+
+        Ok(EMDResult {
+            plan: result.plan.mapv(from_f64),
+            cost: from_f64(result.cost),
+            alpha: result.alpha.mapv(from_f64),
+            beta: result.beta.mapv(from_f64),
+            status: result.status,
+        })
+    }
+
+    fn log(&self) -> &SolverLog {
+        &self.log
     }
 }
 
+/// Solves the Earth Movers Distance with the FastTransport network simplex
+///
+/// The dual potentials are centered and, when some of the weights are zero, completed with
+/// feasible values for the discarded samples.
+/// Reaching the maximum number of iterations is not treated as an error, it is reported
+/// through the status of the result instead.
+///
+/// a: Source sample weights
+/// b: Target sample weights
+/// M: Loss matrix
+/// iterations: Max number of iterations of the network simplex
 #[allow(non_snake_case)]
 fn emd(
     a: &mut Array1<f64>,
     b: &mut Array1<f64>,
     M: &mut Array2<f64>,
     iterations: i32,
-) -> Result<Array2<f64>, OTError> {
+) -> Result<EMDResult<f64>, OTError> {
     // Call FastTransport via wrapper
-    let (G, _cost, mut _u, mut _v, result_code) = emd_c(a, b, M, iterations);
+    let (G, cost, alpha0, beta0, result_code) = emd_c(a, b, M, iterations);
+
+    let status = FastTransportErrorCode::from(result_code);
+    if status != FastTransportErrorCode::IsMaxIterReached {
+        check_result(status)?;
+    }
 
-    check_result(FastTransportErrorCode::from(result_code))?;
+    // The solver only returns feasible potentials for samples with non-zero weights
+    let (alpha, beta) = if a.iter().any(|&x| x <= 0.) || b.iter().any(|&x| x <= 0.) {
+        estimate_dual_null_weights(&alpha0, &beta0, a, b, M)
+    } else {
+        center_ot_dual(&alpha0, &beta0, Some(a), Some(b))
+    };
 
-    Ok(G)
+    Ok(EMDResult {
+        plan: G,
+        cost,
+        alpha,
+        beta,
+        status,
+    })
 }
 
 #[cfg(test)]
 mod tests {
 
     use crate::OTSolver;
-    use ndarray::array;
+    use ndarray::{array, s};
 
     #[allow(non_snake_case)]
     #[test]
@@ -197,7 +312,7 @@ mod tests {
         let mut M = array![[0.0, 1.0], [1.0, 0.0]];
 
         let gamma = match super::emd(&mut a, &mut b, &mut M, 100000) {
-            Ok(result) => result,
+            Ok(result) => result.plan,
             Err(error) => panic!("{:?}", error),
         };
 
@@ -210,12 +325,12 @@ mod tests {
 
     #[test]
     fn test_earthmovers_builder() {
-        let mut a = array![0.5, 0.5];
-        let mut b = array![0.5, 0.5];
-        let mut m = array![[0.0, 1.0], [1.0, 0.0]];
+        let a = array![0.5, 0.5];
+        let b = array![0.5, 0.5];
+        let m = array![[0.0, 1.0], [1.0, 0.0]];
 
-        let test = match super::EarthMovers::new(&mut a, &mut b, &mut m).solve() {
-            Ok(result) => result,
+        let test = match super::EarthMovers::new(&a, &b, &m).solve() {
+            Ok(result) => result.plan,
             Err(error) => panic!("{:?}", error),
         };
 
@@ -223,4 +338,146 @@ mod tests {
 
         assert_eq!(test, truth);
     }
+
+    #[allow(non_snake_case)]
+    #[test]
+    fn test_emd_cost_and_duals() {
+        let mut a = array![0.25, 0.75];
+        let mut b = array![0.5, 0.5];
+        let mut M = array![[0.0, 1.0], [2.0, 0.0]];
+        let M_cache = M.clone();
+
+        let result = match super::emd(&mut a, &mut b, &mut M, 100000) {
+            Ok(result) => result,
+            Err(error) => panic!("{:?}", error),
+        };
+
+        assert_eq!(result.status, super::FastTransportErrorCode::IsOptimal);
+        assert!((result.cost - (&result.plan * &M_cache).sum()).abs() < 1E-12);
+        assert!((result.cost - 0.5).abs() < 1E-12);
+
+        // Strong duality
+        let dual_objective = a.dot(&result.alpha) + b.dot(&result.beta);
+        assert!((dual_objective - result.cost).abs() < 1E-9);
+
+        // Centered potentials
+        assert!((a.dot(&result.alpha) - b.dot(&result.beta)).abs() < 1E-9);
+    }
+
+    #[allow(non_snake_case)]
+    #[test]
+    fn test_emd_duals_null_weights() {
+        let mut a = array![0.5, 0.5, 0.0];
+        let mut b = array![0.5, 0.5];
+        let mut M = array![[0.0, 1.0], [1.0, 0.0], [0.5, 0.5]];
+        let M_cache = M.clone();
+
+        let result = match super::emd(&mut a, &mut b, &mut M, 100000) {
+            Ok(result) => result,
+            Err(error) => panic!("{:?}", error),
+        };
+
+        // Dual potentials must be feasible, including on the zero weighted sample
+        for ((i, j), cost) in M_cache.indexed_iter() {
+            assert!(result.alpha[i] + result.beta[j] <= cost + 1E-9);
+        }
+
+        let dual_objective = a.dot(&result.alpha) + b.dot(&result.beta);
+        assert!((dual_objective - result.cost).abs() < 1E-9);
+    }
+
+    #[test]
+    fn test_earthmovers_log() {
+        let a = array![0.5, 0.5];
+        let b = array![0.5, 0.5];
+        let m = array![[0.0, 1.0], [1.0, 0.0]];
+
+        let mut solver = super::EarthMovers::new(&a, &b, &m);
+
+        if let Err(error) = solver.strict(true).solve() {
+            panic!("{:?}", error);
+        }
+
+        assert!(solver.log().converged);
+        assert!(solver.log().error < 1E-12);
+    }
+
+    #[test]
+    fn test_earthmovers_max_iterations() {
+        let n = 50;
+        let a = ndarray::Array1::from_shape_fn(n, |i| (1 + i % 7) as f64);
+        let b = ndarray::Array1::from_shape_fn(n, |j| (1 + j % 5) as f64);
+        let m = ndarray::Array2::from_shape_fn((n, n), |(i, j)| ((i * 37 + j * 91) % 101) as f64);
+
+        // Truncating the network simplex is an error by default
+        let mut solver = super::EarthMovers::new(&a, &b, &m);
+        assert!(solver.iterations(1).solve().is_err());
+
+        let result = match solver.strict(false).solve() {
+            Ok(result) => result,
+            Err(error) => panic!("{:?}", error),
+        };
+
+        assert_eq!(
+            result.status,
+            super::FastTransportErrorCode::IsMaxIterReached
+        );
+        assert!(!solver.log().converged);
+    }
+
+    #[test]
+    fn test_earthmovers_views() {
+        let a = array![0.25, 0.75];
+        let b = array![2.0, 2.0, 4.0];
+        let m = array![[0.0, 1.0, 2.0], [1.0, 0.0, 1.0], [2.0, 1.0, 0.0]];
+
+        // Slices of bigger arrays, the target weights are not normalized
+        let result =
+            match super::EarthMovers::new(&a.view(), &b.slice(s![..2]), &m.slice(s![..2, ..2]))
+                .solve()
+            {
+                Ok(result) => result,
+                Err(error) => panic!("{:?}", error),
+            };
+
+        let truth = array![[0.25, 0.0], [0.25, 0.5]];
+        assert_eq!(result.plan, truth);
+
+        // Inputs are left untouched
+        assert_eq!(b, array![2.0, 2.0, 4.0]);
+    }
+
+    #[test]
+    fn test_earthmovers_transposed_cost() {
+        let a = array![0.5, 0.5];
+        let b = array![0.5, 0.5];
+        let m = array![[1.0, 0.0], [2.0, 1.0]];
+
+        // The transposed cost [[1, 2], [0, 1]] sends everything along the anti-diagonal
+        let result = match super::EarthMovers::new(&a, &b, &m.t()).solve() {
+            Ok(result) => result,
+            Err(error) => panic!("{:?}", error),
+        };
+
+        let truth = array![[0.0, 0.5], [0.5, 0.0]];
+        assert_eq!(result.plan, truth);
+        assert_eq!(result.cost, 1.0);
+    }
+
+    #[test]
+    fn test_earthmovers_reversed_weights() {
+        let a = array![0.25, 0.75];
+        let b = array![0.75, 0.25];
+        let m = array![[0.0, 1.0], [1.0, 0.0]];
+
+        // Negative stride views are read in logical order, a_rev = [0.75, 0.25]
+        let result = match super::EarthMovers::new(&a.slice(s![..;-1]), &b, &m).solve() {
+            Ok(result) => result,
+            Err(error) => panic!("{:?}", error),
+        };
+
+        let truth = array![[0.75, 0.0], [0.0, 0.25]];
+        assert_eq!(result.plan, truth);
+        assert_eq!(result.cost, 0.0);
+    }
 }
@@ -0,0 +1,252 @@
+use ndarray::prelude::*;
+use ndarray::{concatenate, Data};
+use num_traits::Float;
+
+use super::EarthMovers;
+use crate::error::OTError;
+use crate::{OTSolver, SolverLog};
+
+/// Solves the exact partial Optimal Transport problem, moving only a total mass m between the
+/// source and target distributions, and returns the OT matrix
+///
+/// The problem is cast as a balanced Earth Movers Distance by adding a dummy point to each
+/// distribution: the dummy target absorbs the sum(a) - m source mass left in place and the
+/// dummy source provides the sum(b) - m target mass that is not reached. Transport between
+/// the dummy points is prohibitively expensive.
+///
+/// Partial Optimal Transport with Applications on Positive-Unlabeled Learning
+/// by Laetitia Chapel, Mokhtar Z. Alaya, Gilles Gasso
+///
+/// ```rust
+/// use rust_optimal_transport as ot;
+/// use ot::prelude::*;
+/// use ndarray::prelude::*;
+///
+/// // The last sample of each distribution is an outlier
+/// let source = array![[0., 0.], [1., 0.], [10., 10.]];
+/// let target = array![[0., 0.1], [1., 0.1], [-10., -10.]];
+///
+/// let source_weights = Array1::<f64>::from_elem(3, 1. / 3.);
+/// let target_weights = Array1::<f64>::from_elem(3, 1. / 3.);
+///
+/// let cost = dist(&source, &target, SqEuclidean);
+///
+/// // Only transport two thirds of the mass
+/// let ot_matrix = match PartialEarthMovers::new(
+///     &source_weights,
+///     &target_weights,
+///     &cost,
+///     2. / 3.,
+/// ).solve() {
+///     Ok(result) => result,
+///     Err(error) => panic!("{:?}", error),
+/// };
+///
+/// // The outliers are left untouched
+/// assert!(ot_matrix.row(2).sum() < 1E-12);
+/// assert!(ot_matrix.column(2).sum() < 1E-12);
+///
+/// ```
+///
+/// source_weights and target_weights represent histograms of the Source and Target distributions,
+/// respectively. Their total masses may differ, the transported mass m must be in
+/// [0, min(sum(a), sum(b))].
+///
+pub struct PartialEarthMovers<'a, A> {
+    source_weights: ArrayView1<'a, A>,
+    target_weights: ArrayView1<'a, A>,
+    cost: ArrayView2<'a, A>,
+    mass: A,
+    iterations: i32,
+    strict: bool,
+    log: SolverLog,
+}
+
+impl<'a, A> PartialEarthMovers<'a, A>
+where
+    A: Float,
+{
+    pub fn new<S1, S2, S3>(
+        source_weights: &'a ArrayBase<S1, Ix1>,
+        target_weights: &'a ArrayBase<S2, Ix1>,
+        cost: &'a ArrayBase<S3, Ix2>,
+        mass: A,
+    ) -> Self
+    where
+        S1: Data<Elem = A>,
+        S2: Data<Elem = A>,
+        S3: Data<Elem = A>,
+    {
+        Self {
+            source_weights: source_weights.view(),
+            target_weights: target_weights.view(),
+            cost: cost.view(),
+            mass,
+            iterations: 100000,
+            strict: true,
+            log: SolverLog::default(),
+        }
+    }
+
+    /// Total mass to transport
+    pub fn mass<'b>(&'b mut self, mass: A) -> &'b mut Self {
+        self.mass = mass;
+        self
+    }
+
+    pub fn iterations<'b>(&'b mut self, iterations: i32) -> &'b mut Self {
+        self.iterations = iterations;
+        self
+    }
+
+    /// Returns an error if the network simplex reaches the max number of iterations, enabled by
+    /// default
+    pub fn strict<'b>(&'b mut self, strict: bool) -> &'b mut Self {
+        self.strict = strict;
+        self
+    }
+}
+
+impl<'a, A> OTSolver for PartialEarthMovers<'a, A>
+where
+    A: Float,
+{
+    type Output = Array2<A>;
+
+    fn check_shape(&self) -> Result<(), OTError> {
+        let mshape = self.cost.shape();
+        let m0 = mshape[0];
+        let m1 = mshape[1];
+        let dim_a = self.source_weights.len();
+        let dim_b = self.target_weights.len();
+
+        if dim_a != m0 || dim_b != m1 {
+            return Err(OTError::WeightDimensionError {
+                dim_a,
+                dim_b,
+                dim_m_0: m0,
+                dim_m_1: m1,
+            });
+        }
+
+        Ok(())
+    }
+
+    fn solve(&mut self) -> Result<Array2<A>, OTError> {
+        self.check_shape()?;
+
+        let mass = check_mass(self.source_weights, self.target_weights, self.mass)?;
+
+        let (dim_a, dim_b) = (self.source_weights.len(), self.target_weights.len());
+
+        // Dummy points holding the mass that is not transported
+        let a = concatenate![
+            Axis(0),
+            self.source_weights,
+            array![self.target_weights.sum() - mass]
+        ];
+        let b = concatenate![
+            Axis(0),
+            self.target_weights,
+            array![self.source_weights.sum() - mass]
+        ];
+
+        let max_cost = self.cost.fold(A::zero(), |acc, &c| acc.max(c));
+        let mut cost = Array2::<A>::zeros((dim_a + 1, dim_b + 1));
+        cost.slice_mut(s![..dim_a, ..dim_b]).assign(&self.cost);
+        cost[(dim_a, dim_b)] = max_cost + max_cost + A::one();
+
+        let mut solver = EarthMovers::new(&a, &b, &cost);
+        let result = solver
+            .iterations(self.iterations)
+            .strict(self.strict)
+            .solve()?;
+
+        self.log = solver.log().clone();
+
+        Ok(result.plan.slice(s![..dim_a, ..dim_b]).to_owned())
+    }
+
+    fn log(&self) -> &SolverLog {
+        &self.log
+    }
+}
+
+/// Ensures the transported mass is feasible for both distributions and returns it
+///
+/// A mass that exceeds the smaller total mass of the weights by rounding errors only is
+/// clamped to it.
+pub(crate) fn check_mass<A: Float>(
+    a: ArrayView1<A>,
+    b: ArrayView1<A>,
+    mass: A,
+) -> Result<A, OTError> {
+    if a.iter().chain(b.iter()).any(|&w| w < A::zero()) {
+        return Err(OTError::ArgError("Negative sample weight".to_string()));
+    }
+
+    if mass < A::zero() {
+        return Err(OTError::ArgError("Transported mass < 0".to_string()));
+    }
+
+    let max_mass = a.sum().min(b.sum());
+    if mass > max_mass * (A::one() + A::epsilon().sqrt()) {
+        return Err(OTError::ArgError(
+            "Transported mass larger than the mass of the source or target weights".to_string(),
+        ));
+    }
+
+    Ok(mass.min(max_mass))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use ndarray::prelude::*;
+
+    use crate::metrics::{dist, MetricType};
+    use crate::OTSolver;
+
+    #[test]
+    fn test_partial_earthmovers() {
+        let source = array![[0., 0.], [1., 0.], [10., 10.]];
+        let target = array![[0., 0.1], [1., 0.1], [-10., -10.]];
+
+        let a = Array1::<f64>::from_elem(3, 1. / 3.);
+        let b = Array1::<f64>::from_elem(3, 1. / 3.);
+        let m = dist(&source, &target, MetricType::SqEuclidean);
+
+        let plan = match super::PartialEarthMovers::new(&a, &b, &m, 2. / 3.).solve() {
+            Ok(result) => result,
+            Err(error) => panic!("{:?}", error),
+        };
+
+        let truth = array![[1. / 3., 0., 0.], [0., 1. / 3., 0.], [0., 0., 0.]];
+        assert!(plan.abs_diff_eq(&truth, 1E-12));
+
+        // Full mass reduces to the Earth Movers Distance
+        let plan = match super::PartialEarthMovers::new(&a, &b, &m, 1.).solve() {
+            Ok(result) => result,
+            Err(error) => panic!("{:?}", error),
+        };
+
+        assert!(plan.sum_axis(Axis(1)).abs_diff_eq(&a, 1E-12));
+        assert!(plan.sum_axis(Axis(0)).abs_diff_eq(&b, 1E-12));
+
+        // The weights sum to 1 up to rounding errors
+        let weights = Array1::<f64>::from_elem(10, 0.1);
+        let cost = Array2::<f64>::zeros((10, 10));
+
+        let plan = match super::PartialEarthMovers::new(&weights, &weights, &cost, 1.).solve() {
+            Ok(result) => result,
+            Err(error) => panic!("{:?}", error),
+        };
+
+        assert!((plan.sum() - weights.sum()).abs() < 1E-12);
+
+        // Infeasible mass
+        assert!(super::PartialEarthMovers::new(&a, &b, &m, 1.5)
+            .solve()
+            .is_err());
+    }
+}
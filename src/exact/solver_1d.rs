@@ -0,0 +1,373 @@
+use ndarray::prelude::*;
+use ndarray::Data;
+use num_traits::Float;
+use std::cmp::Ordering;
+
+use crate::error::OTError;
+
+/// Sparse solution of the one dimensional Optimal Transport problem
+///
+/// The plan has at most n + m - 1 non-zero entries, stored in coordinate format.
+#[derive(Debug)]
+pub struct EMD1DResult<A> {
+    /// Source sample index of each non-zero entry of the plan
+    pub rows: Vec<usize>,
+    /// Target sample index of each non-zero entry of the plan
+    pub cols: Vec<usize>,
+    /// Transported mass of each non-zero entry of the plan
+    pub values: Vec<A>,
+    /// Total transport cost <plan, M> with M = |x_a - x_b|^p
+    pub cost: A,
+    /// Number of source samples
+    pub dim_a: usize,
+    /// Number of target samples
+    pub dim_b: usize,
+}
+
+impl<A: Float> EMD1DResult<A> {
+    /// Returns the OT matrix as a dense (dim_a, dim_b) array
+    pub fn to_dense(&self) -> Array2<A> {
+        let mut plan = Array2::<A>::zeros((self.dim_a, self.dim_b));
+        for ((&i, &j), &value) in self.rows.iter().zip(&self.cols).zip(&self.values) {
+            plan[(i, j)] = plan[(i, j)] + value;
+        }
+
+        plan
+    }
+}
+
+/// Solves the Earth Movers Distance between two weighted 1D distributions with the ground
+/// cost |x_a - x_b|^p and returns a sparse OT matrix and the transport cost
+///
+/// In one dimension the optimal plan is monotone, it is obtained in O(n log n) by sorting the
+/// samples and walking both quantile functions at once.
+///
+/// ```rust
+/// use rust_optimal_transport as ot;
+/// use ot::exact::solver_1d::emd_1d;
+/// use ndarray::prelude::*;
+///
+/// let x_a = array![0., 1., 2.];
+/// let x_b = array![2.5, 0.5];
+///
+/// let a = Array1::<f64>::from_elem(3, 1. / 3.);
+/// let b = Array1::<f64>::from_elem(2, 1. / 2.);
+///
+/// let result = match emd_1d(&x_a, &x_b, &a, &b, 1.) {
+///     Ok(result) => result,
+///     Err(error) => panic!("{:?}", error),
+/// };
+///
+/// let ot_matrix = result.to_dense();
+/// assert!((result.cost - 2. / 3.).abs() < 1E-12);
+///
+/// ```
+///
+/// x_a: Source samples
+/// x_b: Target samples
+/// a: Source sample weights (uniform weights if empty)
+/// b: Target sample weights (uniform weights if empty), rescaled to the total mass of a
+/// p: Power of the ground cost |x_a - x_b|^p, > 0
+pub fn emd_1d<A, S1, S2, S3, S4>(
+    x_a: &ArrayBase<S1, Ix1>,
+    x_b: &ArrayBase<S2, Ix1>,
+    a: &ArrayBase<S3, Ix1>,
+    b: &ArrayBase<S4, Ix1>,
+    p: A,
+) -> Result<EMD1DResult<A>, OTError>
+where
+    A: Float,
+    S1: Data<Elem = A>,
+    S2: Data<Elem = A>,
+    S3: Data<Elem = A>,
+    S4: Data<Elem = A>,
+{
+    let (a, b) = check_weights(x_a.len(), x_b.len(), a.view(), b.view(), p)?;
+    check_positions(x_a.iter().chain(x_b.iter()))?;
+
+    let perm_a = argsort(x_a.view());
+    let perm_b = argsort(x_b.view());
+    let a_sorted: Vec<A> = perm_a.iter().map(|&i| a[i]).collect();
+    let b_sorted: Vec<A> = perm_b.iter().map(|&j| b[j]).collect();
+
+    let mut result = EMD1DResult {
+        rows: Vec::with_capacity(a.len() + b.len() - 1),
+        cols: Vec::with_capacity(a.len() + b.len() - 1),
+        values: Vec::with_capacity(a.len() + b.len() - 1),
+        cost: A::zero(),
+        dim_a: a.len(),
+        dim_b: b.len(),
+    };
+
+    quantile_walk(&a_sorted, &b_sorted, |i, j, mass| {
+        let (i, j) = (perm_a[i], perm_b[j]);
+        result.rows.push(i);
+        result.cols.push(j);
+        result.values.push(mass);
+        result.cost = result.cost + mass * (x_a[i] - x_b[j]).abs().powf(p);
+    });
+
+    Ok(result)
+}
+
+/// Computes the 1D Optimal Transport loss, sum_ij G_ij |u_i - v_j|^p, between the columns of
+/// two sets of samples
+///
+/// Column k of u_values and column k of v_values are two 1D distributions, the loss is
+/// computed for each column independently. Taking the power 1/p of the result gives the
+/// p-Wasserstein distance.
+///
+/// ```rust
+/// use rust_optimal_transport as ot;
+/// use ot::exact::solver_1d::wasserstein_1d;
+/// use ndarray::prelude::*;
+///
+/// // Two features measured on a reference and a new batch of samples
+/// let reference = array![[0., 10.], [1., 11.], [2., 12.]];
+/// let batch = array![[0.5, 10.], [1.5, 11.], [2.5, 12.]];
+///
+/// let uniform = Array1::<f64>::zeros(0);
+///
+/// let losses = match wasserstein_1d(&reference, &batch, &uniform, &uniform, 1.) {
+///     Ok(result) => result,
+///     Err(error) => panic!("{:?}", error),
+/// };
+///
+/// assert!((losses[0] - 0.5).abs() < 1E-12);
+/// assert!(losses[1].abs() < 1E-12);
+///
+/// ```
+///
+/// u_values: Source samples, (n, k)
+/// v_values: Target samples, (m, k)
+/// u_weights: Source sample weights shared by all columns (uniform weights if empty)
+/// v_weights: Target sample weights shared by all columns (uniform weights if empty),
+/// rescaled to the total mass of u_weights
+/// p: Power of the ground cost |u - v|^p, > 0
+pub fn wasserstein_1d<A, S1, S2, S3, S4>(
+    u_values: &ArrayBase<S1, Ix2>,
+    v_values: &ArrayBase<S2, Ix2>,
+    u_weights: &ArrayBase<S3, Ix1>,
+    v_weights: &ArrayBase<S4, Ix1>,
+    p: A,
+) -> Result<Array1<A>, OTError>
+where
+    A: Float,
+    S1: Data<Elem = A>,
+    S2: Data<Elem = A>,
+    S3: Data<Elem = A>,
+    S4: Data<Elem = A>,
+{
+    if u_values.ncols() != v_values.ncols() {
+        return Err(OTError::ArgError(
+            "Source and target samples have a different number of columns".to_string(),
+        ));
+    }
+
+    let mut losses = Array1::<A>::zeros(u_values.ncols());
+
+    let (n, m) = (u_values.nrows(), v_values.nrows());
+    let (a, b) = check_weights(n, m, u_weights.view(), v_weights.view(), p)?;
+    check_positions(u_values.iter().chain(v_values.iter()))?;
+
+    for (k, loss) in losses.iter_mut().enumerate() {
+        *loss = emd_1d_loss(u_values.column(k), v_values.column(k), &a, &b, p);
+    }
+
+    Ok(losses)
+}
+
+/// 1D Optimal Transport loss between two weighted distributions with the same total mass
+pub(crate) fn emd_1d_loss<A: Float>(
+    x_a: ArrayView1<A>,
+    x_b: ArrayView1<A>,
+    a: &Array1<A>,
+    b: &Array1<A>,
+    p: A,
+) -> A {
+    let perm_a = argsort(x_a);
+    let perm_b = argsort(x_b);
+    let a_sorted: Vec<A> = perm_a.iter().map(|&i| a[i]).collect();
+    let b_sorted: Vec<A> = perm_b.iter().map(|&j| b[j]).collect();
+
+    let mut loss = A::zero();
+    quantile_walk(&a_sorted, &b_sorted, |i, j, mass| {
+        let diff = x_a[perm_a[i]] - x_b[perm_b[j]];
+        loss = loss + mass * diff.abs().powf(p);
+    });
+
+    loss
+}
+
+/// Checks the number of samples against their weights and returns the weights, uniform if
+/// empty, with the target weights rescaled to the total mass of the source weights
+pub(crate) fn check_weights<A: Float>(
+    n: usize,
+    m: usize,
+    a: ArrayView1<A>,
+    b: ArrayView1<A>,
+    p: A,
+) -> Result<(Array1<A>, Array1<A>), OTError> {
+    if n == 0 || m == 0 {
+        return Err(OTError::ArgError(
+            "Empty source or target samples".to_string(),
+        ));
+    }
+
+    if p <= A::zero() {
+        return Err(OTError::ArgError("Ground cost power p <= 0".to_string()));
+    }
+
+    let uniform = |n: usize| Array1::<A>::from_elem(n, A::one() / A::from(n).unwrap());
+
+    let a = if a.is_empty() {
+        uniform(n)
+    } else {
+        a.to_owned()
+    };
+
+    let mut b = if b.is_empty() {
+        uniform(m)
+    } else {
+        b.to_owned()
+    };
+
+    if a.len() != n || b.len() != m {
+        return Err(OTError::ArgError(
+            "Sample weights and samples have different lengths".to_string(),
+        ));
+    }
+
+    if a.iter().chain(b.iter()).any(|&w| w < A::zero()) {
+        return Err(OTError::ArgError("Negative sample weight".to_string()));
+    }
+
+    if a.sum() == A::zero() || b.sum() == A::zero() {
+        return Err(OTError::ArgError(
+            "Source or target weights sum to 0".to_string(),
+        ));
+    }
+
+    let scale = a.sum() / b.sum();
+    b.mapv_inplace(|w| w * scale);
+
+    Ok((a, b))
+}
+
+/// Ensures the sample positions are finite, NaN positions cannot be sorted
+pub(crate) fn check_positions<'a, A, I>(mut x: I) -> Result<(), OTError>
+where
+    A: Float + 'a,
+    I: Iterator<Item = &'a A>,
+{
+    if x.any(|x| !x.is_finite()) {
+        return Err(OTError::ArgError("Non-finite sample position".to_string()));
+    }
+
+    Ok(())
+}
+
+/// Indices sorting the samples in increasing order
+fn argsort<A: Float>(x: ArrayView1<A>) -> Vec<usize> {
+    let mut perm: Vec<usize> = (0..x.len()).collect();
+    perm.sort_by(|&i, &j| x[i].partial_cmp(&x[j]).unwrap_or(Ordering::Equal));
+
+    perm
+}
+
+/// Walks the quantile functions of two sorted distributions with the same total mass and
+/// calls f(i, j, mass) for each pair of sorted positions sharing some mass
+fn quantile_walk<A, F>(a: &[A], b: &[A], mut f: F)
+where
+    A: Float,
+    F: FnMut(usize, usize, A),
+{
+    let (n, m) = (a.len(), b.len());
+    let (mut i, mut j) = (0, 0);
+    let (mut w_i, mut w_j) = (a[0], b[0]);
+
+    loop {
+        if w_i < w_j || j == m - 1 {
+            if w_i > A::zero() {
+                f(i, j, w_i);
+            }
+            i += 1;
+            if i == n {
+                break;
+            }
+            w_j = w_j - w_i;
+            w_i = a[i];
+        } else {
+            if w_j > A::zero() {
+                f(i, j, w_j);
+            }
+            j += 1;
+            if j == m {
+                break;
+            }
+            w_i = w_i - w_j;
+            w_j = b[j];
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use ndarray::prelude::*;
+
+    #[test]
+    fn test_emd_1d() {
+        let x_a = array![2., 0., 1.];
+        let x_b = array![0.5, 2.5];
+        let a = array![0.25, 0.5, 0.25];
+        let b = array![0.5, 0.5];
+
+        let result = match super::emd_1d(&x_a, &x_b, &a, &b, 2.) {
+            Ok(result) => result,
+            Err(error) => panic!("{:?}", error),
+        };
+
+        let truth = array![[0.0, 0.25], [0.5, 0.0], [0.0, 0.25]];
+
+        assert_eq!(result.to_dense(), truth);
+        assert!((result.cost - 0.75f64).abs() < 1E-12);
+        assert!(result.values.len() < x_a.len() + x_b.len());
+
+        // NaN positions cannot be sorted
+        let x_a = array![2., f64::NAN, 1.];
+        assert!(super::emd_1d(&x_a, &x_b, &a, &b, 2.).is_err());
+
+        // No mass to rescale the target weights to
+        let x_a = array![2., 0., 1.];
+        assert!(super::emd_1d(&x_a, &x_b, &a, &array![0., 0.], 2.).is_err());
+    }
+
+    #[test]
+    fn test_wasserstein_1d() {
+        let u = array![[0., 3.], [1., 1.], [2., 2.]];
+        let v = array![[1., 2.], [2., 1.], [3., 3.]];
+        let uniform = Array1::<f64>::zeros(0);
+
+        let losses = match super::wasserstein_1d(&u, &v, &uniform, &uniform, 1.) {
+            Ok(result) => result,
+            Err(error) => panic!("{:?}", error),
+        };
+
+        // Each column matches the emd_1d cost
+        for k in 0..2 {
+            let cost = match super::emd_1d(&u.column(k), &v.column(k), &uniform, &uniform, 1.) {
+                Ok(result) => result.cost,
+                Err(error) => panic!("{:?}", error),
+            };
+
+            assert!((losses[k] - cost).abs() < 1E-12);
+        }
+
+        assert!((losses[0] - 1.).abs() < 1E-12);
+        assert!(losses[1].abs() < 1E-12);
+
+        let v = array![[1., 2.], [2., f64::INFINITY], [3., 3.]];
+        assert!(super::wasserstein_1d(&u, &v, &uniform, &uniform, 1.).is_err());
+    }
+}
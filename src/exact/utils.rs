@@ -57,8 +57,8 @@ pub fn estimate_dual_null_weights(
     M: &Array2<f64>,
 ) -> (Array1<f64>, Array1<f64>) {
     // binary indexing of non-zero weights
-    let asel = a.mapv(|a| (a > 0.) as i32);
-    let bsel = b.mapv(|b| (b > 0.) as i32);
+    let asel = a.mapv(|a| a > 0.);
+    let bsel = b.mapv(|b| b > 0.);
 
     // compute dual constraints violation
     // NOTE: alpha0 as a col vec added to each col of row vec beta0
@@ -88,13 +88,13 @@ pub fn estimate_dual_null_weights(
     let max_aviol = aviol.iter().fold(0f64, |a, &b| a.max(b));
     let mut alpha_up = Array1::<f64>::zeros(alpha0.len());
     for (i, selection) in asel.iter().enumerate() {
-        alpha_up[i] = -1f64 * (!selection as f64) * (max_aviol as f64);
+        alpha_up[i] = if *selection { 0. } else { -max_aviol };
     }
 
     let max_bviol = bviol.iter().fold(0f64, |a, &b| a.max(b));
     let mut beta_up = Array1::<f64>::zeros(beta0.len());
     for (i, selection) in bsel.iter().enumerate() {
-        beta_up[i] = -1f64 * (!selection as f64) * (max_bviol as f64);
+        beta_up[i] = if *selection { 0. } else { -max_bviol };
     }
 
     let alpha = alpha0 + alpha_up;
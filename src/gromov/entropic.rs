@@ -0,0 +1,359 @@
+use ndarray::prelude::*;
+use ndarray::{Data, ScalarOperand};
+use num_traits::Float;
+
+use super::gromov_wasserstein::{check_gw_shape, normalize_weights};
+use super::utils::GWTerms;
+use super::{GWLoss, GWResult};
+use crate::error::OTError;
+use crate::parallel::Element;
+use crate::regularized::sinkhorn::{SinkhornKnopp, SinkhornLog};
+use crate::{OTSolver, SolverLog};
+
+/// Solves the entropic regularized Gromov-Wasserstein problem and returns the coupling and the
+/// Gromov-Wasserstein loss of the coupling
+///
+/// Each iteration is a projected gradient step for the KL geometry: the Gromov-Wasserstein
+/// loss is linearized around the current coupling, giving the cost 2 (constC - hC1 T hC2^T),
+/// and the next coupling solves the entropic OT problem with this cost.
+///
+/// Gromov-Wasserstein Averaging of Kernel and Distance Matrices
+/// by Gabriel Peyré, Marco Cuturi, Justin Solomon
+///
+/// ```rust
+/// use rust_optimal_transport as ot;
+/// use ot::prelude::*;
+/// use ndarray::prelude::*;
+/// use ndarray_stats::QuantileExt;
+///
+/// let n = 30;
+///
+/// // Samples of a 2D gaussian and a 3D point cloud sharing its geometry
+/// let mu = array![0., 0.];
+/// let cov = array![[1., 0.], [0., 1.]];
+/// let source = ot::utils::sample_2D_gauss(n, &mu, &cov).unwrap();
+///
+/// let mut target = Array2::<f64>::zeros((n, 3));
+/// target.slice_mut(s![.., 1..]).assign(&source);
+///
+/// // Normalized intra-domain costs
+/// let mut c1 = dist(&source, &source, SqEuclidean);
+/// c1 = &c1 / *c1.max().unwrap();
+/// let mut c2 = dist(&target, &target, SqEuclidean);
+/// c2 = &c2 / *c2.max().unwrap();
+///
+/// let p = Array1::<f64>::from_elem(n, 1. / (n as f64));
+/// let q = Array1::<f64>::from_elem(n, 1. / (n as f64));
+///
+/// let result = match EntropicGromovWasserstein::new(&p, &q, &c1, &c2, 5E-3)
+///     .log_domain(true)
+///     .solve()
+/// {
+///     Ok(result) => result,
+///     Err(error) => panic!("{:?}", error),
+/// };
+///
+/// let coupling = result.plan;
+/// let gw_loss = result.distance;
+///
+/// ```
+///
+/// source_weights and target_weights represent histograms of the Source and Target distributions,
+/// respectively. source_cost (C1) and target_cost (C2) are the costs between the samples of each
+/// distribution.
+///
+pub struct EntropicGromovWasserstein<'a, A> {
+    source_weights: ArrayView1<'a, A>,
+    target_weights: ArrayView1<'a, A>,
+    source_cost: ArrayView2<'a, A>,
+    target_cost: ArrayView2<'a, A>,
+    reg: A,
+    loss: GWLoss,
+    iterations: i32,
+    sinkhorn_iterations: i32,
+    threshold: A,
+    log_domain: bool,
+    strict: bool,
+    log: SolverLog,
+}
+
+impl<'a, A> EntropicGromovWasserstein<'a, A>
+where
+    A: Float + ScalarOperand,
+{
+    pub fn new<S1, S2, S3, S4>(
+        source_weights: &'a ArrayBase<S1, Ix1>,
+        target_weights: &'a ArrayBase<S2, Ix1>,
+        source_cost: &'a ArrayBase<S3, Ix2>,
+        target_cost: &'a ArrayBase<S4, Ix2>,
+        reg: A,
+    ) -> Self
+    where
+        S1: Data<Elem = A>,
+        S2: Data<Elem = A>,
+        S3: Data<Elem = A>,
+        S4: Data<Elem = A>,
+    {
+        Self {
+            source_weights: source_weights.view(),
+            target_weights: target_weights.view(),
+            source_cost: source_cost.view(),
+            target_cost: target_cost.view(),
+            reg,
+            loss: GWLoss::Square,
+            iterations: 1000,
+            sinkhorn_iterations: 1000,
+            threshold: A::from(1E-9).unwrap(),
+            log_domain: false,
+            strict: false,
+            log: SolverLog::default(),
+        }
+    }
+
+    pub fn loss<'b>(&'b mut self, loss: GWLoss) -> &'b mut Self {
+        self.loss = loss;
+        self
+    }
+
+    pub fn iterations<'b>(&'b mut self, iterations: i32) -> &'b mut Self {
+        self.iterations = iterations;
+        self
+    }
+
+    /// Max number of iterations of the Sinkhorn solver at each projection
+    pub fn sinkhorn_iterations<'b>(&'b mut self, sinkhorn_iterations: i32) -> &'b mut Self {
+        self.sinkhorn_iterations = sinkhorn_iterations;
+        self
+    }
+
+    /// Stop threshold on the L1 change of the coupling between two iterations
+    pub fn threshold<'b>(&'b mut self, threshold: A) -> &'b mut Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Returns a ConvergenceError if the stop threshold is not reached within the max number
+    /// of iterations, or if a Sinkhorn projection does not converge
+    pub fn strict<'b>(&'b mut self, strict: bool) -> &'b mut Self {
+        self.strict = strict;
+        self
+    }
+
+    pub fn reg<'b>(&'b mut self, reg: A) -> &'b mut Self {
+        self.reg = reg;
+        self
+    }
+
+    /// Solves each projection with the log-domain Sinkhorn solver, stable for small
+    /// regularization terms
+    pub fn log_domain<'b>(&'b mut self, log_domain: bool) -> &'b mut Self {
+        self.log_domain = log_domain;
+        self
+    }
+}
+
+impl<'a, A> OTSolver for EntropicGromovWasserstein<'a, A>
+where
+    A: Float + ScalarOperand + Element,
+{
+    type Output = GWResult<A>;
+
+    fn check_shape(&self) -> Result<(), OTError> {
+        check_gw_shape(
+            self.source_weights,
+            self.target_weights,
+            self.source_cost,
+            self.target_cost,
+        )
+    }
+
+    fn solve(&mut self) -> Result<GWResult<A>, OTError> {
+        self.check_shape()?;
+
+        if self.reg <= A::zero() {
+            return Err(OTError::ArgError("Regularization term <= 0".to_string()));
+        }
+
+        if self.iterations <= 0 {
+            return Err(OTError::ArgError(
+                "Iterations not a valid value. Must be > 0".to_string(),
+            ));
+        }
+
+        let (p, q) = normalize_weights(self.source_weights, self.target_weights)?;
+        let terms = GWTerms::new(
+            self.source_cost,
+            self.target_cost,
+            p.view(),
+            q.view(),
+            self.loss,
+        );
+
+        let (result, log) = entropic_gromov(
+            p.view(),
+            q.view(),
+            &terms,
+            None,
+            A::one(),
+            self.reg,
+            self.iterations,
+            self.sinkhorn_iterations,
+            self.threshold,
+            self.log_domain,
+            self.strict,
+        )?;
+
+        self.log = log;
+
+        if self.strict {
+            self.log.check_convergence(self.threshold)?;
+        }
+
+        Ok(result)
+    }
+
+    fn log(&self) -> &SolverLog {
+        &self.log
+    }
+}
+
+/// Projected gradient iterations of the entropic (fused) Gromov-Wasserstein problem, starting
+/// from the independent coupling
+///
+/// Without feature cost the linearized cost is the gradient of the Gromov-Wasserstein loss,
+/// with a feature cost M it is (1 - alpha) M + alpha grad GW(T).
+///
+/// p: Source sample weights
+/// q: Target sample weights, with the mass of p
+/// terms: Decomposition of the Gromov-Wasserstein loss on the costs C1 and C2
+/// feature_cost: Optional cost between the source and target features, (n, m)
+/// alpha: Trade-off between the feature and structure terms, in [0, 1]
+/// reg: Entropy regularization term > 0
+/// iterations: Max number of projections
+/// sinkhorn_iterations: Max number of iterations of each Sinkhorn projection
+/// threshold: Stop threshold on the L1 change of the coupling
+/// log_domain: Solve the projections with the log-domain Sinkhorn solver
+/// strict: Returns a ConvergenceError if a Sinkhorn projection does not converge
+#[allow(non_snake_case, clippy::too_many_arguments)]
+pub(crate) fn entropic_gromov<A>(
+    p: ArrayView1<A>,
+    q: ArrayView1<A>,
+    terms: &GWTerms<A>,
+    feature_cost: Option<ArrayView2<A>>,
+    alpha: A,
+    reg: A,
+    iterations: i32,
+    sinkhorn_iterations: i32,
+    threshold: A,
+    log_domain: bool,
+    strict: bool,
+) -> Result<(GWResult<A>, SolverLog), OTError>
+where
+    A: Float + ScalarOperand + Element,
+{
+    let mut log = SolverLog::default();
+
+    let mut plan = &p.insert_axis(Axis(1)) * &q.insert_axis(Axis(0));
+
+    for count in 0..iterations {
+        // Entropic OT on the linearized Gromov-Wasserstein cost
+        let cost = match feature_cost {
+            Some(M) => terms.gradient(&plan) * alpha + &(&M * (A::one() - alpha)),
+            None => terms.gradient(&plan),
+        };
+
+        // Last marginal violation recorded by the Sinkhorn projection
+        let violation = |log: &SolverLog| A::from(*log.marginal_violation.last().unwrap()).unwrap();
+
+        let (plan_next, marginal_violation) = if log_domain {
+            let mut sinkhorn = SinkhornLog::new(&p, &q, &cost, reg);
+            let plan_next = sinkhorn
+                .iterations(sinkhorn_iterations)
+                .strict(strict)
+                .solve()?;
+            (plan_next, violation(sinkhorn.log()))
+        } else {
+            let mut sinkhorn = SinkhornKnopp::new(&p, &q, &cost, reg);
+            let plan_next = sinkhorn
+                .iterations(sinkhorn_iterations)
+                .strict(strict)
+                .solve()?;
+            (plan_next, violation(sinkhorn.log()))
+        };
+
+        let change = (&plan_next - &plan).mapv(A::abs).sum();
+        plan = plan_next;
+
+        log.iterations = count + 1;
+        log.push(change, marginal_violation, change);
+
+        if change < threshold {
+            log.converged = true;
+            break;
+        }
+    }
+
+    let distance = match feature_cost {
+        Some(M) => (&M * &plan).sum() * (A::one() - alpha) + terms.loss(&plan) * alpha,
+        None => terms.loss(&plan),
+    };
+
+    Ok((GWResult { plan, distance }, log))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use ndarray::prelude::*;
+
+    use crate::metrics::{dist, MetricType};
+    use crate::OTSolver;
+
+    #[test]
+    fn test_entropic_gromov_wasserstein() {
+        // Points on a line and their mirror image
+        let source = array![[0.], [1.], [3.], [7.]];
+        let target = array![[7.], [6.], [4.], [0.]];
+
+        let c1 = dist(&source, &source, MetricType::SqEuclidean) / 49.;
+        let c2 = dist(&target, &target, MetricType::SqEuclidean) / 49.;
+
+        let p = Array1::<f64>::from_elem(4, 0.25);
+        let q = Array1::<f64>::from_elem(4, 0.25);
+        let reg = 1E-2;
+
+        let mut solver = super::EntropicGromovWasserstein::new(&p, &q, &c1, &c2, reg);
+        let result = match solver.solve() {
+            Ok(result) => result,
+            Err(error) => panic!("{:?}", error),
+        };
+
+        assert!(solver.log().converged);
+        assert!(result.plan.sum_axis(Axis(1)).abs_diff_eq(&p, 1E-6));
+        assert!(result.plan.sum_axis(Axis(0)).abs_diff_eq(&q, 1E-6));
+
+        // Blurred isometry
+        for (i, row) in result.plan.axis_iter(Axis(0)).enumerate() {
+            assert!(row[i] > 0.2);
+        }
+
+        // The log-domain projections reach the same coupling
+        let result_log = match super::EntropicGromovWasserstein::new(&p, &q, &c1, &c2, reg)
+            .log_domain(true)
+            .solve()
+        {
+            Ok(result) => result,
+            Err(error) => panic!("{:?}", error),
+        };
+
+        assert!(result_log.plan.abs_diff_eq(&result.plan, 1E-6));
+        assert!((result_log.distance - result.distance).abs() < 1E-6);
+
+        // Marginal violation of the last Sinkhorn projection
+        assert!(*solver.log().marginal_violation.last().unwrap() < 1E-6);
+
+        // Truncated Sinkhorn projections are rejected in strict mode
+        assert!(solver.sinkhorn_iterations(1).solve().is_ok());
+        assert!(solver.strict(true).solve().is_err());
+    }
+}
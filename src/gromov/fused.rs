@@ -0,0 +1,934 @@
+use ndarray::prelude::*;
+use ndarray::{Data, ScalarOperand};
+use num_traits::Float;
+
+use super::entropic::entropic_gromov;
+use super::gromov_wasserstein::{check_gw_shape, gromov_cg, normalize_weights};
+use super::utils::GWTerms;
+use super::{GWLoss, GWResult};
+use crate::error::OTError;
+use crate::metrics::{dist, MetricType};
+use crate::parallel::Element;
+use crate::{OTSolver, SolverLog};
+
+/// Solves the Fused Gromov-Wasserstein problem between two structured distributions and
+/// returns the coupling and the FGW distance
+///
+/// The coupling T minimizes (1 - alpha) <M, T> + alpha sum_ijkl L(C1_ik, C2_jl) T_ij T_kl,
+/// M compares the features of the samples and C1, C2 the structures of both distributions.
+/// It is solved with conditional gradient over the Earth Movers Distance.
+///
+/// Optimal Transport for structured data with application on graphs
+/// by Titouan Vayer, Laetitia Chapel, Rémi Flamary, Romain Tavenard, Nicolas Courty
+///
+/// ```rust
+/// use rust_optimal_transport as ot;
+/// use ot::prelude::*;
+/// use ndarray::prelude::*;
+///
+/// // Nodes on a line with their position as feature, and the same graph relabeled
+/// let source = array![[0.], [1.], [3.], [7.]];
+/// let target = array![[7.], [0.], [3.], [1.]];
+///
+/// // Feature cost and intra-domain structures
+/// let features_cost = dist(&source, &target, SqEuclidean);
+/// let c1 = dist(&source, &source, SqEuclidean);
+/// let c2 = dist(&target, &target, SqEuclidean);
+///
+/// let p = Array1::<f64>::from_elem(4, 0.25);
+/// let q = Array1::<f64>::from_elem(4, 0.25);
+///
+/// let alpha = 0.5;
+///
+/// let result = match FusedGromovWasserstein::new(&p, &q, &features_cost, &c1, &c2, alpha)
+///     .solve()
+/// {
+///     Ok(result) => result,
+///     Err(error) => panic!("{:?}", error),
+/// };
+///
+/// assert!(result.distance.abs() < 1E-9);
+///
+/// ```
+///
+/// source_weights and target_weights represent histograms of the Source and Target distributions,
+/// respectively. features_cost (M) is the cost between the source and target features,
+/// source_cost (C1) and target_cost (C2) are the costs between the samples of each distribution.
+///
+pub struct FusedGromovWasserstein<'a, A> {
+    source_weights: ArrayView1<'a, A>,
+    target_weights: ArrayView1<'a, A>,
+    features_cost: ArrayView2<'a, A>,
+    source_cost: ArrayView2<'a, A>,
+    target_cost: ArrayView2<'a, A>,
+    alpha: A,
+    loss: GWLoss,
+    iterations: i32,
+    emd_iterations: i32,
+    threshold: A,
+    strict: bool,
+    log: SolverLog,
+}
+
+impl<'a, A> FusedGromovWasserstein<'a, A>
+where
+    A: Float + ScalarOperand,
+{
+    pub fn new<S1, S2, S3, S4, S5>(
+        source_weights: &'a ArrayBase<S1, Ix1>,
+        target_weights: &'a ArrayBase<S2, Ix1>,
+        features_cost: &'a ArrayBase<S3, Ix2>,
+        source_cost: &'a ArrayBase<S4, Ix2>,
+        target_cost: &'a ArrayBase<S5, Ix2>,
+        alpha: A,
+    ) -> Self
+    where
+        S1: Data<Elem = A>,
+        S2: Data<Elem = A>,
+        S3: Data<Elem = A>,
+        S4: Data<Elem = A>,
+        S5: Data<Elem = A>,
+    {
+        Self {
+            source_weights: source_weights.view(),
+            target_weights: target_weights.view(),
+            features_cost: features_cost.view(),
+            source_cost: source_cost.view(),
+            target_cost: target_cost.view(),
+            alpha,
+            loss: GWLoss::Square,
+            iterations: 10000,
+            emd_iterations: 100000,
+            threshold: A::from(1E-9).unwrap(),
+            strict: false,
+            log: SolverLog::default(),
+        }
+    }
+
+    /// Trade-off between the feature term (0) and the structure term (1)
+    pub fn alpha<'b>(&'b mut self, alpha: A) -> &'b mut Self {
+        self.alpha = alpha;
+        self
+    }
+
+    pub fn loss<'b>(&'b mut self, loss: GWLoss) -> &'b mut Self {
+        self.loss = loss;
+        self
+    }
+
+    pub fn iterations<'b>(&'b mut self, iterations: i32) -> &'b mut Self {
+        self.iterations = iterations;
+        self
+    }
+
+    /// Max number of iterations of the network simplex solving each linear minimization step
+    pub fn emd_iterations<'b>(&'b mut self, emd_iterations: i32) -> &'b mut Self {
+        self.emd_iterations = emd_iterations;
+        self
+    }
+
+    /// Stop threshold on the absolute or relative change of the loss between two iterations
+    pub fn threshold<'b>(&'b mut self, threshold: A) -> &'b mut Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Returns a ConvergenceError if the stop threshold is not reached within the max number
+    /// of iterations
+    pub fn strict<'b>(&'b mut self, strict: bool) -> &'b mut Self {
+        self.strict = strict;
+        self
+    }
+}
+
+impl<'a, A> OTSolver for FusedGromovWasserstein<'a, A>
+where
+    A: Float + ScalarOperand,
+{
+    type Output = GWResult<A>;
+
+    fn check_shape(&self) -> Result<(), OTError> {
+        check_fgw_shape(
+            self.source_weights,
+            self.target_weights,
+            self.features_cost,
+            self.source_cost,
+            self.target_cost,
+        )
+    }
+
+    fn solve(&mut self) -> Result<GWResult<A>, OTError> {
+        self.check_shape()?;
+
+        if self.iterations <= 0 {
+            return Err(OTError::ArgError(
+                "Iterations not a valid value. Must be > 0".to_string(),
+            ));
+        }
+
+        check_alpha(self.alpha)?;
+
+        let (p, q) = normalize_weights(self.source_weights, self.target_weights)?;
+
+        let terms = GWTerms::new(
+            self.source_cost,
+            self.target_cost,
+            p.view(),
+            q.view(),
+            self.loss,
+        );
+
+        let (result, log) = gromov_cg(
+            p.view(),
+            q.view(),
+            &terms,
+            Some(self.features_cost),
+            self.alpha,
+            self.iterations,
+            self.emd_iterations,
+            self.threshold,
+        )?;
+
+        self.log = log;
+
+        if self.strict {
+            self.log.check_convergence(self.threshold)?;
+        }
+
+        Ok(result)
+    }
+
+    fn log(&self) -> &SolverLog {
+        &self.log
+    }
+}
+
+/// Solves the entropic regularized Fused Gromov-Wasserstein problem and returns the coupling
+/// and the FGW loss of the coupling
+///
+/// Each iteration solves the entropic OT problem on the linearized objective
+/// (1 - alpha) M + alpha grad GW(T) with the Sinkhorn solvers.
+///
+/// ```rust
+/// use rust_optimal_transport as ot;
+/// use ot::prelude::*;
+/// use ndarray::prelude::*;
+///
+/// // Nodes on a line with their position as feature, and the same graph relabeled
+/// let source = array![[0.], [1.], [3.], [7.]];
+/// let target = array![[7.], [0.], [3.], [1.]];
+///
+/// // Normalized feature cost and intra-domain structures
+/// let features_cost = dist(&source, &target, SqEuclidean) / 49.;
+/// let c1 = dist(&source, &source, SqEuclidean) / 49.;
+/// let c2 = dist(&target, &target, SqEuclidean) / 49.;
+///
+/// let p = Array1::<f64>::from_elem(4, 0.25);
+/// let q = Array1::<f64>::from_elem(4, 0.25);
+///
+/// let result = match EntropicFusedGromovWasserstein::new(
+///     &p,
+///     &q,
+///     &features_cost,
+///     &c1,
+///     &c2,
+///     0.5,
+///     1E-2,
+/// )
+/// .solve()
+/// {
+///     Ok(result) => result,
+///     Err(error) => panic!("{:?}", error),
+/// };
+///
+/// let coupling = result.plan;
+/// let fgw_loss = result.distance;
+///
+/// ```
+///
+/// source_weights and target_weights represent histograms of the Source and Target distributions,
+/// respectively. features_cost (M) is the cost between the source and target features,
+/// source_cost (C1) and target_cost (C2) are the costs between the samples of each distribution.
+///
+pub struct EntropicFusedGromovWasserstein<'a, A> {
+    source_weights: ArrayView1<'a, A>,
+    target_weights: ArrayView1<'a, A>,
+    features_cost: ArrayView2<'a, A>,
+    source_cost: ArrayView2<'a, A>,
+    target_cost: ArrayView2<'a, A>,
+    alpha: A,
+    reg: A,
+    loss: GWLoss,
+    iterations: i32,
+    sinkhorn_iterations: i32,
+    threshold: A,
+    log_domain: bool,
+    strict: bool,
+    log: SolverLog,
+}
+
+impl<'a, A> EntropicFusedGromovWasserstein<'a, A>
+where
+    A: Float + ScalarOperand,
+{
+    pub fn new<S1, S2, S3, S4, S5>(
+        source_weights: &'a ArrayBase<S1, Ix1>,
+        target_weights: &'a ArrayBase<S2, Ix1>,
+        features_cost: &'a ArrayBase<S3, Ix2>,
+        source_cost: &'a ArrayBase<S4, Ix2>,
+        target_cost: &'a ArrayBase<S5, Ix2>,
+        alpha: A,
+        reg: A,
+    ) -> Self
+    where
+        S1: Data<Elem = A>,
+        S2: Data<Elem = A>,
+        S3: Data<Elem = A>,
+        S4: Data<Elem = A>,
+        S5: Data<Elem = A>,
+    {
+        Self {
+            source_weights: source_weights.view(),
+            target_weights: target_weights.view(),
+            features_cost: features_cost.view(),
+            source_cost: source_cost.view(),
+            target_cost: target_cost.view(),
+            alpha,
+            reg,
+            loss: GWLoss::Square,
+            iterations: 1000,
+            sinkhorn_iterations: 1000,
+            threshold: A::from(1E-9).unwrap(),
+            log_domain: false,
+            strict: false,
+            log: SolverLog::default(),
+        }
+    }
+
+    /// Trade-off between the feature term (0) and the structure term (1)
+    pub fn alpha<'b>(&'b mut self, alpha: A) -> &'b mut Self {
+        self.alpha = alpha;
+        self
+    }
+
+    pub fn loss<'b>(&'b mut self, loss: GWLoss) -> &'b mut Self {
+        self.loss = loss;
+        self
+    }
+
+    pub fn iterations<'b>(&'b mut self, iterations: i32) -> &'b mut Self {
+        self.iterations = iterations;
+        self
+    }
+
+    /// Max number of iterations of the Sinkhorn solver at each projection
+    pub fn sinkhorn_iterations<'b>(&'b mut self, sinkhorn_iterations: i32) -> &'b mut Self {
+        self.sinkhorn_iterations = sinkhorn_iterations;
+        self
+    }
+
+    /// Stop threshold on the L1 change of the coupling between two iterations
+    pub fn threshold<'b>(&'b mut self, threshold: A) -> &'b mut Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Returns a ConvergenceError if the stop threshold is not reached within the max number
+    /// of iterations, or if a Sinkhorn projection does not converge
+    pub fn strict<'b>(&'b mut self, strict: bool) -> &'b mut Self {
+        self.strict = strict;
+        self
+    }
+
+    pub fn reg<'b>(&'b mut self, reg: A) -> &'b mut Self {
+        self.reg = reg;
+        self
+    }
+
+    /// Solves each projection with the log-domain Sinkhorn solver, stable for small
+    /// regularization terms
+    pub fn log_domain<'b>(&'b mut self, log_domain: bool) -> &'b mut Self {
+        self.log_domain = log_domain;
+        self
+    }
+}
+
+impl<'a, A> OTSolver for EntropicFusedGromovWasserstein<'a, A>
+where
+    A: Float + ScalarOperand + Element,
+{
+    type Output = GWResult<A>;
+
+    fn check_shape(&self) -> Result<(), OTError> {
+        check_fgw_shape(
+            self.source_weights,
+            self.target_weights,
+            self.features_cost,
+            self.source_cost,
+            self.target_cost,
+        )
+    }
+
+    fn solve(&mut self) -> Result<GWResult<A>, OTError> {
+        self.check_shape()?;
+
+        if self.reg <= A::zero() {
+            return Err(OTError::ArgError("Regularization term <= 0".to_string()));
+        }
+
+        if self.iterations <= 0 {
+            return Err(OTError::ArgError(
+                "Iterations not a valid value. Must be > 0".to_string(),
+            ));
+        }
+
+        check_alpha(self.alpha)?;
+
+        let (p, q) = normalize_weights(self.source_weights, self.target_weights)?;
+
+        let terms = GWTerms::new(
+            self.source_cost,
+            self.target_cost,
+            p.view(),
+            q.view(),
+            self.loss,
+        );
+
+        let (result, log) = entropic_gromov(
+            p.view(),
+            q.view(),
+            &terms,
+            Some(self.features_cost),
+            self.alpha,
+            self.reg,
+            self.iterations,
+            self.sinkhorn_iterations,
+            self.threshold,
+            self.log_domain,
+            self.strict,
+        )?;
+
+        self.log = log;
+
+        if self.strict {
+            self.log.check_convergence(self.threshold)?;
+        }
+
+        Ok(result)
+    }
+
+    fn log(&self) -> &SolverLog {
+        &self.log
+    }
+}
+
+/// Fused Gromov-Wasserstein barycenter of structured distributions
+#[derive(Debug)]
+pub struct FGWBarycenterResult<A> {
+    /// Features of the barycenter samples, (k, d)
+    pub features: Array2<A>,
+    /// Structure matrix of the barycenter, (k, k)
+    pub structure: Array2<A>,
+}
+
+/// Computes the Fused Gromov-Wasserstein barycenter of structured distributions and returns
+/// its feature and structure matrices
+///
+/// The barycenter minimizes sum_s lambda_s FGW(barycenter, distribution_s) with a fixed number
+/// of samples. Each iteration solves the exact FGW problems between the current barycenter and
+/// every distribution, then updates the features with the barycentric projection of the
+/// couplings and the structure with the closed form of the loss.
+///
+/// Optimal Transport for structured data with application on graphs
+/// by Titouan Vayer, Laetitia Chapel, Rémi Flamary, Romain Tavenard, Nicolas Courty
+///
+/// ```rust
+/// use rust_optimal_transport as ot;
+/// use ot::prelude::*;
+/// use ndarray::prelude::*;
+///
+/// // Two relabelings of the same graph, node positions are used as features
+/// let features = vec![array![[0.], [1.], [3.]], array![[3.], [0.], [1.]]];
+/// let structures: Vec<Array2<f64>> = features
+///     .iter()
+///     .map(|x| dist(x, x, Euclidean))
+///     .collect();
+/// let weights = vec![Array1::<f64>::from_elem(3, 1. / 3.); 2];
+///
+/// // Initial barycenter and uniform weights on the distributions
+/// let init_features = array![[0.5], [1.5], [2.5]];
+/// let init_structure = dist(&init_features, &init_features, Euclidean);
+/// let barycenter_weights = Array1::<f64>::zeros(0);
+///
+/// let barycenter = match FGWBarycenter::new(
+///     &features,
+///     &structures,
+///     &weights,
+///     &barycenter_weights,
+///     &init_features,
+///     &init_structure,
+///     0.5,
+/// )
+/// .solve()
+/// {
+///     Ok(result) => result,
+///     Err(error) => panic!("{:?}", error),
+/// };
+///
+/// let structure = barycenter.structure;
+/// let features = barycenter.features;
+///
+/// ```
+///
+/// features: Features of each distribution, (n_s, d) arrays
+/// structures: Intra-domain cost of each distribution, (n_s, n_s) arrays
+/// weights: Sample weights of each distribution
+/// barycenter_weights: Weights of the distributions in the barycenter (uniform if empty)
+/// init_features: (k, d) initial features of the barycenter
+/// init_structure: (k, k) initial structure of the barycenter
+/// alpha: Trade-off between the feature term (0) and the structure term (1)
+///
+/// The feature cost is the squared euclidean distance between features. The mass of the
+/// barycenter samples is uniform unless set with `support_weights`.
+///
+pub struct FGWBarycenter<'a, A> {
+    features: Vec<ArrayView2<'a, A>>,
+    structures: Vec<ArrayView2<'a, A>>,
+    weights: Vec<ArrayView1<'a, A>>,
+    barycenter_weights: ArrayView1<'a, A>,
+    init_features: ArrayView2<'a, A>,
+    init_structure: ArrayView2<'a, A>,
+    support_weights: Option<ArrayView1<'a, A>>,
+    alpha: A,
+    loss: GWLoss,
+    iterations: i32,
+    cg_iterations: i32,
+    emd_iterations: i32,
+    threshold: A,
+    strict: bool,
+    log: SolverLog,
+}
+
+impl<'a, A> FGWBarycenter<'a, A>
+where
+    A: Float + ScalarOperand,
+{
+    #[allow(clippy::too_many_arguments)]
+    pub fn new<S1, S2, S3, S4, S5, S6>(
+        features: &'a [ArrayBase<S1, Ix2>],
+        structures: &'a [ArrayBase<S2, Ix2>],
+        weights: &'a [ArrayBase<S3, Ix1>],
+        barycenter_weights: &'a ArrayBase<S4, Ix1>,
+        init_features: &'a ArrayBase<S5, Ix2>,
+        init_structure: &'a ArrayBase<S6, Ix2>,
+        alpha: A,
+    ) -> Self
+    where
+        S1: Data<Elem = A>,
+        S2: Data<Elem = A>,
+        S3: Data<Elem = A>,
+        S4: Data<Elem = A>,
+        S5: Data<Elem = A>,
+        S6: Data<Elem = A>,
+    {
+        Self {
+            features: features.iter().map(|x| x.view()).collect(),
+            structures: structures.iter().map(|c| c.view()).collect(),
+            weights: weights.iter().map(|w| w.view()).collect(),
+            barycenter_weights: barycenter_weights.view(),
+            init_features: init_features.view(),
+            init_structure: init_structure.view(),
+            support_weights: None,
+            alpha,
+            loss: GWLoss::Square,
+            iterations: 100,
+            cg_iterations: 10000,
+            emd_iterations: 100000,
+            threshold: A::from(1E-9).unwrap(),
+            strict: false,
+            log: SolverLog::default(),
+        }
+    }
+
+    /// Trade-off between the feature term (0) and the structure term (1)
+    pub fn alpha<'b>(&'b mut self, alpha: A) -> &'b mut Self {
+        self.alpha = alpha;
+        self
+    }
+
+    pub fn loss<'b>(&'b mut self, loss: GWLoss) -> &'b mut Self {
+        self.loss = loss;
+        self
+    }
+
+    pub fn iterations<'b>(&'b mut self, iterations: i32) -> &'b mut Self {
+        self.iterations = iterations;
+        self
+    }
+
+    /// Max number of conditional gradient iterations of each Fused Gromov-Wasserstein coupling
+    pub fn cg_iterations<'b>(&'b mut self, cg_iterations: i32) -> &'b mut Self {
+        self.cg_iterations = cg_iterations;
+        self
+    }
+
+    /// Max number of iterations of the network simplex solving each linear minimization step
+    pub fn emd_iterations<'b>(&'b mut self, emd_iterations: i32) -> &'b mut Self {
+        self.emd_iterations = emd_iterations;
+        self
+    }
+
+    /// Stop threshold on the L1 change of the features and the structure between two
+    /// iterations
+    pub fn threshold<'b>(&'b mut self, threshold: A) -> &'b mut Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Returns a ConvergenceError if the stop threshold is not reached within the max number
+    /// of iterations
+    pub fn strict<'b>(&'b mut self, strict: bool) -> &'b mut Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Mass of each sample of the barycenter, must be > 0
+    pub fn support_weights<'b, S>(
+        &'b mut self,
+        support_weights: &'a ArrayBase<S, Ix1>,
+    ) -> &'b mut Self
+    where
+        S: Data<Elem = A>,
+    {
+        self.support_weights = Some(support_weights.view());
+        self
+    }
+}
+
+impl<'a, A> OTSolver for FGWBarycenter<'a, A>
+where
+    A: Float + ScalarOperand + Element,
+{
+    type Output = FGWBarycenterResult<A>;
+
+    /// Ensures every distribution has consistent features, structure and weights, with
+    /// features in the dimension of the barycenter
+    fn check_shape(&self) -> Result<(), OTError> {
+        let n_measures = self.features.len();
+        let (k, dim) = self.init_features.dim();
+
+        if self.structures.len() != n_measures || self.weights.len() != n_measures {
+            return Err(OTError::ArgError(
+                "Features, structures and weights have different lengths".to_string(),
+            ));
+        }
+
+        for ((x, c), w) in self
+            .features
+            .iter()
+            .zip(&self.structures)
+            .zip(&self.weights)
+        {
+            if x.ncols() != dim {
+                return Err(OTError::ArgError(
+                    "Features and barycenter features have different dimensions".to_string(),
+                ));
+            }
+
+            if w.len() != x.nrows() {
+                return Err(OTError::ArgError(
+                    "Sample weights and features have different lengths".to_string(),
+                ));
+            }
+
+            check_gw_shape(*w, *w, *c, *c)?;
+        }
+
+        if self.init_structure.shape() != [k, k] {
+            return Err(OTError::ArgError(
+                "Barycenter structure does not match the barycenter features".to_string(),
+            ));
+        }
+
+        if !self.barycenter_weights.is_empty() && self.barycenter_weights.len() != n_measures {
+            return Err(OTError::ArgError(
+                "Number of barycenter weights does not match the number of distributions"
+                    .to_string(),
+            ));
+        }
+
+        if let Some(b) = self.support_weights {
+            if b.len() != k {
+                return Err(OTError::ArgError(
+                    "Support weights and barycenter features have different lengths".to_string(),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn solve(&mut self) -> Result<FGWBarycenterResult<A>, OTError> {
+        self.check_shape()?;
+
+        if self.iterations <= 0 || self.cg_iterations <= 0 || self.emd_iterations <= 0 {
+            return Err(OTError::ArgError(
+                "Iterations not a valid value. Must be > 0".to_string(),
+            ));
+        }
+
+        check_alpha(self.alpha)?;
+
+        if self.features.is_empty() || self.init_features.nrows() == 0 {
+            return Err(OTError::ArgError(
+                "No distributions or empty barycenter".to_string(),
+            ));
+        }
+
+        let k = self.init_features.nrows();
+
+        let p = match self.support_weights {
+            Some(b) => b.to_owned(),
+            None => Array1::<A>::from_elem(k, A::one() / A::from(k).unwrap()),
+        };
+
+        if p.iter().any(|&w| w <= A::zero()) {
+            return Err(OTError::ArgError("Support weight <= 0".to_string()));
+        }
+
+        // Normalized barycenter weights, on a copy
+        let mut lambdas = if self.barycenter_weights.is_empty() {
+            Array1::<A>::ones(self.features.len())
+        } else {
+            self.barycenter_weights.to_owned()
+        };
+        let total = lambdas.sum();
+        lambdas.mapv_inplace(|w| w / total);
+
+        let mut weights = Vec::with_capacity(self.weights.len());
+        for w in &self.weights {
+            weights.push(normalize_weights(p.view(), *w)?.1);
+        }
+
+        self.log = SolverLog::default();
+
+        let mut features = self.init_features.to_owned();
+        let mut structure = self.init_structure.to_owned();
+
+        // Outer product of the barycenter weights, denominator of the structure update
+        let pp = &p.view().insert_axis(Axis(1)) * &p.view().insert_axis(Axis(0));
+        let p_col = p.view().insert_axis(Axis(1));
+
+        for count in 0..self.iterations {
+            let mut features_update = Array2::<A>::zeros(features.dim());
+            let mut structure_update = Array2::<A>::zeros(structure.dim());
+
+            for (((x, c), q), &lambda) in self
+                .features
+                .iter()
+                .zip(&self.structures)
+                .zip(&weights)
+                .zip(&lambdas)
+            {
+                // Exact FGW coupling between the barycenter and the distribution
+                let features_cost = dist(&features, x, MetricType::SqEuclidean);
+                let terms = GWTerms::new(structure.view(), *c, p.view(), q.view(), self.loss);
+
+                let (result, _) = gromov_cg(
+                    p.view(),
+                    q.view(),
+                    &terms,
+                    Some(features_cost.view()),
+                    self.alpha,
+                    self.cg_iterations,
+                    self.emd_iterations,
+                    self.threshold,
+                )?;
+
+                let plan = result.plan;
+
+                features_update = features_update + plan.dot(x) * lambda;
+
+                let c_s = match self.loss {
+                    GWLoss::Square => c.to_owned(),
+                    GWLoss::KL => c.mapv(|c| (c + A::from(1E-15).unwrap()).ln()),
+                };
+                structure_update = structure_update + plan.dot(&c_s).dot(&plan.t()) * lambda;
+            }
+
+            let features_prev = features;
+            let structure_prev = structure;
+
+            features = features_update / p_col;
+            structure = structure_update / &pp;
+
+            if self.loss == GWLoss::KL {
+                structure.mapv_inplace(A::exp);
+            }
+
+            let features_change = (&features - &features_prev).mapv(A::abs).sum();
+            let structure_change = (&structure - &structure_prev).mapv(A::abs).sum();
+            let change = features_change.max(structure_change);
+
+            self.log.iterations = count + 1;
+            self.log.push_change(change, change);
+
+            if change < self.threshold {
+                self.log.converged = true;
+                break;
+            }
+        }
+
+        if self.strict {
+            self.log.check_convergence(self.threshold)?;
+        }
+
+        Ok(FGWBarycenterResult {
+            features,
+            structure,
+        })
+    }
+
+    fn log(&self) -> &SolverLog {
+        &self.log
+    }
+}
+
+/// Ensures the feature cost matches the sample weights of both domains, along with the shapes
+/// of the intra-domain costs
+#[allow(non_snake_case)]
+fn check_fgw_shape<A>(
+    p: ArrayView1<A>,
+    q: ArrayView1<A>,
+    M: ArrayView2<A>,
+    C1: ArrayView2<A>,
+    C2: ArrayView2<A>,
+) -> Result<(), OTError> {
+    let mshape = M.shape();
+    let m0 = mshape[0];
+    let m1 = mshape[1];
+    let dim_a = p.len();
+    let dim_b = q.len();
+
+    if dim_a != m0 || dim_b != m1 {
+        return Err(OTError::WeightDimensionError {
+            dim_a,
+            dim_b,
+            dim_m_0: m0,
+            dim_m_1: m1,
+        });
+    }
+
+    check_gw_shape(p, q, C1, C2)
+}
+
+fn check_alpha<A: Float>(alpha: A) -> Result<(), OTError> {
+    if alpha < A::zero() || alpha > A::one() {
+        return Err(OTError::ArgError("alpha not in [0, 1]".to_string()));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+
+    use ndarray::prelude::*;
+
+    use crate::metrics::{dist, MetricType};
+    use crate::OTSolver;
+
+    #[test]
+    fn test_fused_gromov_wasserstein() {
+        // Nodes on a line with their position as feature, and the same graph relabeled
+        let source = array![[0.], [1.], [3.], [7.]];
+        let target = array![[7.], [0.], [3.], [1.]];
+
+        let m = dist(&source, &target, MetricType::SqEuclidean) / 49.;
+        let c1 = dist(&source, &source, MetricType::SqEuclidean) / 49.;
+        let c2 = dist(&target, &target, MetricType::SqEuclidean) / 49.;
+
+        let p = Array1::<f64>::from_elem(4, 0.25);
+        let q = Array1::<f64>::from_elem(4, 0.25);
+
+        let truth = array![
+            [0., 0.25, 0., 0.],
+            [0., 0., 0., 0.25],
+            [0., 0., 0.25, 0.],
+            [0.25, 0., 0., 0.]
+        ];
+
+        let mut solver = super::FusedGromovWasserstein::new(&p, &q, &m, &c1, &c2, 0.5);
+        let result = match solver.solve() {
+            Ok(result) => result,
+            Err(error) => panic!("{:?}", error),
+        };
+
+        assert!(solver.log().converged);
+        assert!(result.plan.abs_diff_eq(&truth, 1E-9));
+        assert!(result.distance.abs() < 1E-9);
+
+        // Entropic coupling, concentrated on the relabeling
+        let result =
+            match super::EntropicFusedGromovWasserstein::new(&p, &q, &m, &c1, &c2, 0.5, 1E-2)
+                .log_domain(true)
+                .solve()
+            {
+                Ok(result) => result,
+                Err(error) => panic!("{:?}", error),
+            };
+
+        assert!(result.plan.sum_axis(Axis(1)).abs_diff_eq(&p, 1E-6));
+        for (row, truth_row) in result.plan.axis_iter(Axis(0)).zip(truth.axis_iter(Axis(0))) {
+            assert!((&row - &truth_row).mapv(f64::abs).sum() < 0.05);
+        }
+    }
+
+    #[test]
+    fn test_fgw_barycenter() {
+        // Two relabelings of the same graph
+        let features = vec![array![[0.], [1.], [3.]], array![[3.], [0.], [1.]]];
+        let structures: Vec<Array2<f64>> = features
+            .iter()
+            .map(|x| dist(x, x, MetricType::Euclidean))
+            .collect();
+        let weights = vec![Array1::<f64>::from_elem(3, 1. / 3.); 2];
+
+        let init_features = array![[0.5], [1.5], [2.5]];
+        let init_structure = dist(&init_features, &init_features, MetricType::Euclidean);
+        let barycenter_weights = Array1::<f64>::zeros(0);
+
+        let mut solver = super::FGWBarycenter::new(
+            &features,
+            &structures,
+            &weights,
+            &barycenter_weights,
+            &init_features,
+            &init_structure,
+            0.5,
+        );
+
+        let barycenter = match solver.solve() {
+            Ok(result) => result,
+            Err(error) => panic!("{:?}", error),
+        };
+
+        assert!(solver.log().converged);
+
+        // The barycenter is the shared graph
+        let mut x = barycenter.features.column(0).to_vec();
+        x.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert!(Array1::from(x).abs_diff_eq(&array![0., 1., 3.], 1E-9));
+
+        let structure = dist(
+            &barycenter.features,
+            &barycenter.features,
+            MetricType::Euclidean,
+        );
+        assert!(barycenter.structure.abs_diff_eq(&structure, 1E-9));
+
+        // The inner conditional gradient and network simplex limits are passed through
+        assert!(solver.cg_iterations(0).solve().is_err());
+        assert!(solver.cg_iterations(10).emd_iterations(1).solve().is_err());
+    }
+}
@@ -0,0 +1,393 @@
+use ndarray::prelude::*;
+use ndarray::{Data, ScalarOperand};
+use num_traits::Float;
+
+use super::utils::{solve_1d_quadratic, GWTerms};
+use super::{GWLoss, GWResult};
+use crate::error::OTError;
+use crate::exact::EarthMovers;
+use crate::{OTSolver, SolverLog};
+
+/// Solves the Gromov-Wasserstein problem between two metric measure spaces and returns the
+/// coupling and the Gromov-Wasserstein distance
+///
+/// The samples of both distributions are only compared through their intra-domain costs,
+/// the coupling T minimizes sum_ijkl L(C1_ik, C2_jl) T_ij T_kl under the marginal constraints.
+/// The problem is non-convex, it is solved with conditional gradient where each linear
+/// minimization step is an Earth Movers Distance, followed by an exact line search.
+///
+/// Gromov-Wasserstein Averaging of Kernel and Distance Matrices
+/// by Gabriel Peyré, Marco Cuturi, Justin Solomon
+///
+/// ```rust
+/// use rust_optimal_transport as ot;
+/// use ot::prelude::*;
+/// use ndarray::prelude::*;
+///
+/// let n = 30;
+///
+/// // Samples of a 2D gaussian and a 3D point cloud sharing its geometry
+/// let mu = array![0., 0.];
+/// let cov = array![[1., 0.], [0., 1.]];
+/// let source = ot::utils::sample_2D_gauss(n, &mu, &cov).unwrap();
+///
+/// let mut target = Array2::<f64>::zeros((n, 3));
+/// target.slice_mut(s![.., 1..]).assign(&source);
+///
+/// // Intra-domain costs
+/// let c1 = dist(&source, &source, SqEuclidean);
+/// let c2 = dist(&target, &target, SqEuclidean);
+///
+/// let p = Array1::<f64>::from_elem(n, 1. / (n as f64));
+/// let q = Array1::<f64>::from_elem(n, 1. / (n as f64));
+///
+/// let result = match GromovWasserstein::new(&p, &q, &c1, &c2)
+///     .loss(GWLoss::Square)
+///     .solve()
+/// {
+///     Ok(result) => result,
+///     Err(error) => panic!("{:?}", error),
+/// };
+///
+/// let coupling = result.plan;
+/// let gw_distance = result.distance;
+///
+/// ```
+///
+/// source_weights and target_weights represent histograms of the Source and Target distributions,
+/// respectively. source_cost (C1) and target_cost (C2) are the costs between the samples of each
+/// distribution.
+///
+/// The target weights are rescaled to the total mass of the source weights on an internal copy.
+///
+pub struct GromovWasserstein<'a, A> {
+    source_weights: ArrayView1<'a, A>,
+    target_weights: ArrayView1<'a, A>,
+    source_cost: ArrayView2<'a, A>,
+    target_cost: ArrayView2<'a, A>,
+    loss: GWLoss,
+    iterations: i32,
+    emd_iterations: i32,
+    threshold: A,
+    strict: bool,
+    log: SolverLog,
+}
+
+impl<'a, A> GromovWasserstein<'a, A>
+where
+    A: Float + ScalarOperand,
+{
+    pub fn new<S1, S2, S3, S4>(
+        source_weights: &'a ArrayBase<S1, Ix1>,
+        target_weights: &'a ArrayBase<S2, Ix1>,
+        source_cost: &'a ArrayBase<S3, Ix2>,
+        target_cost: &'a ArrayBase<S4, Ix2>,
+    ) -> Self
+    where
+        S1: Data<Elem = A>,
+        S2: Data<Elem = A>,
+        S3: Data<Elem = A>,
+        S4: Data<Elem = A>,
+    {
+        Self {
+            source_weights: source_weights.view(),
+            target_weights: target_weights.view(),
+            source_cost: source_cost.view(),
+            target_cost: target_cost.view(),
+            loss: GWLoss::Square,
+            iterations: 10000,
+            emd_iterations: 100000,
+            threshold: A::from(1E-9).unwrap(),
+            strict: false,
+            log: SolverLog::default(),
+        }
+    }
+
+    pub fn loss<'b>(&'b mut self, loss: GWLoss) -> &'b mut Self {
+        self.loss = loss;
+        self
+    }
+
+    pub fn iterations<'b>(&'b mut self, iterations: i32) -> &'b mut Self {
+        self.iterations = iterations;
+        self
+    }
+
+    /// Max number of iterations of the network simplex solving each linear minimization step
+    pub fn emd_iterations<'b>(&'b mut self, emd_iterations: i32) -> &'b mut Self {
+        self.emd_iterations = emd_iterations;
+        self
+    }
+
+    /// Stop threshold on the absolute or relative change of the loss between two iterations
+    pub fn threshold<'b>(&'b mut self, threshold: A) -> &'b mut Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Returns a ConvergenceError if the stop threshold is not reached within the max number
+    /// of iterations
+    pub fn strict<'b>(&'b mut self, strict: bool) -> &'b mut Self {
+        self.strict = strict;
+        self
+    }
+}
+
+impl<'a, A> OTSolver for GromovWasserstein<'a, A>
+where
+    A: Float + ScalarOperand,
+{
+    type Output = GWResult<A>;
+
+    fn check_shape(&self) -> Result<(), OTError> {
+        check_gw_shape(
+            self.source_weights,
+            self.target_weights,
+            self.source_cost,
+            self.target_cost,
+        )
+    }
+
+    fn solve(&mut self) -> Result<GWResult<A>, OTError> {
+        self.check_shape()?;
+
+        if self.iterations <= 0 {
+            return Err(OTError::ArgError(
+                "Iterations not a valid value. Must be > 0".to_string(),
+            ));
+        }
+
+        let (p, q) = normalize_weights(self.source_weights, self.target_weights)?;
+
+        let terms = GWTerms::new(
+            self.source_cost,
+            self.target_cost,
+            p.view(),
+            q.view(),
+            self.loss,
+        );
+
+        let (result, log) = gromov_cg(
+            p.view(),
+            q.view(),
+            &terms,
+            None,
+            A::one(),
+            self.iterations,
+            self.emd_iterations,
+            self.threshold,
+        )?;
+
+        self.log = log;
+
+        if self.strict {
+            self.log.check_convergence(self.threshold)?;
+        }
+
+        Ok(result)
+    }
+
+    fn log(&self) -> &SolverLog {
+        &self.log
+    }
+}
+
+/// Ensures the intra-domain costs are square and match the number of weights of their domain
+#[allow(non_snake_case)]
+pub(crate) fn check_gw_shape<A>(
+    p: ArrayView1<A>,
+    q: ArrayView1<A>,
+    C1: ArrayView2<A>,
+    C2: ArrayView2<A>,
+) -> Result<(), OTError> {
+    let (n, m) = (p.len(), q.len());
+
+    if C1.shape() != [n, n] || C2.shape() != [m, m] {
+        return Err(OTError::ArgError(format!(
+            "Intra-domain costs of shapes {:?} and {:?} do not match sample weights of \
+                lengths {} and {}",
+            C1.shape(),
+            C2.shape(),
+            n,
+            m
+        )));
+    }
+
+    Ok(())
+}
+
+/// Returns copies of the sample weights with the target weights rescaled to the total mass of
+/// the source weights
+pub(crate) fn normalize_weights<A: Float>(
+    p: ArrayView1<A>,
+    q: ArrayView1<A>,
+) -> Result<(Array1<A>, Array1<A>), OTError> {
+    if p.is_empty() || q.is_empty() {
+        return Err(OTError::ArgError(
+            "Empty source or target weights".to_string(),
+        ));
+    }
+
+    if p.iter().chain(q.iter()).any(|&w| w < A::zero()) {
+        return Err(OTError::ArgError("Negative sample weight".to_string()));
+    }
+
+    let scale = p.sum() / q.sum();
+
+    Ok((p.to_owned(), q.mapv(|w| w * scale)))
+}
+
+/// Conditional gradient on the (fused) Gromov-Wasserstein loss, starting from the independent
+/// coupling
+///
+/// Without feature cost the objective is the Gromov-Wasserstein loss, with a feature cost M
+/// it is (1 - alpha) <M, T> + alpha GW(T).
+///
+/// p: Source sample weights
+/// q: Target sample weights, with the mass of p
+/// terms: Decomposition of the Gromov-Wasserstein loss on the costs C1 and C2
+/// feature_cost: Optional cost between the source and target features, (n, m)
+/// alpha: Trade-off between the feature and structure terms, in [0, 1]
+/// iterations: Max number of conditional gradient iterations
+/// emd_iterations: Max number of iterations of the network simplex
+/// threshold: Stop threshold on the absolute or relative change of the loss
+#[allow(non_snake_case, clippy::too_many_arguments)]
+pub(crate) fn gromov_cg<A>(
+    p: ArrayView1<A>,
+    q: ArrayView1<A>,
+    terms: &GWTerms<A>,
+    feature_cost: Option<ArrayView2<A>>,
+    alpha: A,
+    iterations: i32,
+    emd_iterations: i32,
+    threshold: A,
+) -> Result<(GWResult<A>, SolverLog), OTError>
+where
+    A: Float + ScalarOperand,
+{
+    let mut log = SolverLog::default();
+
+    let objective = |plan: &Array2<A>| match feature_cost {
+        Some(M) => (&M * plan).sum() * (A::one() - alpha) + terms.loss(plan) * alpha,
+        None => terms.loss(plan),
+    };
+
+    let mut plan = &p.insert_axis(Axis(1)) * &q.insert_axis(Axis(0));
+    let mut distance = objective(&plan);
+
+    for count in 0..iterations {
+        // Linear minimization step on the gradient of the loss
+        let gradient = match feature_cost {
+            Some(M) => terms.gradient(&plan) * alpha + &(&M * (A::one() - alpha)),
+            None => terms.gradient(&plan),
+        };
+
+        // The linearized problem must be solved exactly for a descent direction
+        let direction = EarthMovers::new(&p, &q, &gradient)
+            .iterations(emd_iterations)
+            .strict(true)
+            .solve()?
+            .plan
+            - &plan;
+
+        // Exact line search, the feature term is linear in the step
+        let (a, b) = terms.quadratic_coefficients(&plan, &direction);
+        let step = match feature_cost {
+            Some(M) => solve_1d_quadratic(
+                a * alpha,
+                b * alpha + (&M * &direction).sum() * (A::one() - alpha),
+            ),
+            None => solve_1d_quadratic(a, b),
+        };
+
+        plan = plan + &direction * step;
+
+        let distance_prev = distance;
+        distance = objective(&plan);
+
+        let abs_change = (distance - distance_prev).abs();
+        let rel_change = abs_change / distance.abs();
+
+        let violation = (&plan.sum_axis(Axis(1)) - &p).mapv(A::abs).sum()
+            + (&plan.sum_axis(Axis(0)) - &q).mapv(A::abs).sum();
+
+        log.iterations = count + 1;
+        log.push(
+            direction.mapv(A::abs).sum() * step,
+            violation,
+            abs_change.min(rel_change),
+        );
+
+        if abs_change < threshold || rel_change < threshold {
+            log.converged = true;
+            break;
+        }
+    }
+
+    Ok((GWResult { plan, distance }, log))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use ndarray::prelude::*;
+
+    use crate::gromov::GWLoss;
+    use crate::metrics::{dist, MetricType};
+    use crate::OTSolver;
+
+    #[test]
+    fn test_gromov_wasserstein() {
+        // Points on a line and their mirror image
+        let source = array![[0.], [1.], [3.], [7.]];
+        let target = array![[7.], [6.], [4.], [0.]];
+
+        let c1 = dist(&source, &source, MetricType::SqEuclidean);
+        let c2 = dist(&target, &target, MetricType::SqEuclidean);
+
+        let p = Array1::<f64>::from_elem(4, 0.25);
+        let q = Array1::<f64>::from_elem(4, 0.25);
+
+        let mut solver = super::GromovWasserstein::new(&p, &q, &c1, &c2);
+        let result = match solver.solve() {
+            Ok(result) => result,
+            Err(error) => panic!("{:?}", error),
+        };
+
+        assert!(solver.log().converged);
+        assert!(*solver.log().marginal_violation.last().unwrap() < 1E-9);
+
+        // Isometric spaces, the coupling is the isometry
+        let truth = Array2::<f64>::eye(4) * 0.25;
+        assert!(result.plan.abs_diff_eq(&truth, 1E-9));
+        assert!(result.distance.abs() < 1E-9);
+
+        // Truncated network simplex plans are rejected
+        assert!(solver.emd_iterations(1).solve().is_err());
+    }
+
+    #[test]
+    fn test_gromov_wasserstein_kl() {
+        let source = array![[0., 0.], [1., 0.], [0., 2.]];
+        let target = array![[0., 0.], [2., 0.], [2., 1.], [0., 1.]];
+
+        let c1 = dist(&source, &source, MetricType::Euclidean);
+        let c2 = dist(&target, &target, MetricType::Euclidean);
+
+        let p = Array1::<f64>::from_elem(3, 1. / 3.);
+        let q = Array1::<f64>::from_elem(4, 0.25);
+
+        let result = match super::GromovWasserstein::new(&p, &q, &c1, &c2)
+            .loss(GWLoss::KL)
+            .solve()
+        {
+            Ok(result) => result,
+            Err(error) => panic!("{:?}", error),
+        };
+
+        // Coupling of the sample weights
+        assert!(result.plan.sum_axis(Axis(1)).abs_diff_eq(&p, 1E-9));
+        assert!(result.plan.sum_axis(Axis(0)).abs_diff_eq(&q, 1E-9));
+        assert!(result.distance.is_finite());
+    }
+}
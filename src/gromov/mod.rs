@@ -0,0 +1,24 @@
+pub mod entropic;
+pub mod fused;
+pub mod gromov_wasserstein;
+mod utils;
+
+use ndarray::prelude::*;
+
+/// Loss comparing the pairwise costs of both domains in the Gromov-Wasserstein problem
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GWLoss {
+    /// L(a, b) = (a - b)^2
+    Square,
+    /// L(a, b) = a log(a / b) - a + b
+    KL,
+}
+
+/// Solution of a Gromov-Wasserstein problem
+#[derive(Debug)]
+pub struct GWResult<A> {
+    /// Optimal coupling between the source and target samples
+    pub plan: Array2<A>,
+    /// Gromov-Wasserstein loss of the coupling, sum_ijkl L(C1_ik, C2_jl) T_ij T_kl
+    pub distance: A,
+}
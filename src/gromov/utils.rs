@@ -0,0 +1,144 @@
+use ndarray::prelude::*;
+use ndarray::ScalarOperand;
+use num_traits::Float;
+
+use super::GWLoss;
+
+/// Decomposition of the Gromov-Wasserstein loss tensor for losses of the form
+/// L(a, b) = f1(a) + f2(b) - h1(a) h2(b)
+///
+/// For a coupling T with marginals p and q, the tensor product L x T is
+/// constC - hC1 T hC2^T with constC = f1(C1) p 1^T + 1 q^T f2(C2)^T, so the loss never builds
+/// the (n, n, m, m) tensor.
+///
+/// Computational Optimal Transport: Gromov-Wasserstein Averaging of Kernel and Distance Matrices
+/// by Gabriel Peyré, Marco Cuturi, Justin Solomon
+pub(crate) struct GWTerms<A> {
+    const_c: Array2<A>,
+    h_c1: Array2<A>,
+    h_c2: Array2<A>,
+}
+
+impl<A> GWTerms<A>
+where
+    A: Float + ScalarOperand,
+{
+    /// C1: Source intra-domain cost, (n, n)
+    /// C2: Target intra-domain cost, (m, m)
+    /// p: Source sample weights
+    /// q: Target sample weights
+    /// loss: Loss comparing the costs of both domains
+    #[allow(non_snake_case)]
+    pub(crate) fn new(
+        C1: ArrayView2<A>,
+        C2: ArrayView2<A>,
+        p: ArrayView1<A>,
+        q: ArrayView1<A>,
+        loss: GWLoss,
+    ) -> Self {
+        let (f_c1, f_c2, h_c1, h_c2) = match loss {
+            GWLoss::Square => (
+                C1.mapv(|a| a * a),
+                C2.mapv(|b| b * b),
+                C1.to_owned(),
+                C2.mapv(|b| b + b),
+            ),
+            GWLoss::KL => {
+                // 0 log 0 = 0, and the target costs are shifted away from 0 before the log
+                let eps = A::from(1E-15).unwrap();
+                (
+                    C1.mapv(|a| {
+                        if a > A::zero() {
+                            a * a.ln() - a
+                        } else {
+                            A::zero()
+                        }
+                    }),
+                    C2.to_owned(),
+                    C1.to_owned(),
+                    C2.mapv(|b| (b + eps).ln()),
+                )
+            }
+        };
+
+        let const_c1 = f_c1.dot(&p).insert_axis(Axis(1));
+        let const_c2 = f_c2.dot(&q).insert_axis(Axis(0));
+
+        Self {
+            const_c: &const_c1 + &const_c2,
+            h_c1,
+            h_c2,
+        }
+    }
+
+    /// L x T, (n, m)
+    pub(crate) fn tensor_product(&self, plan: &Array2<A>) -> Array2<A> {
+        &self.const_c - &self.h_c1.dot(plan).dot(&self.h_c2.t())
+    }
+
+    /// Gromov-Wasserstein loss <L x T, T>
+    pub(crate) fn loss(&self, plan: &Array2<A>) -> A {
+        (&self.tensor_product(plan) * plan).sum()
+    }
+
+    /// Gradient of the Gromov-Wasserstein loss with respect to the coupling
+    pub(crate) fn gradient(&self, plan: &Array2<A>) -> Array2<A> {
+        let tensor = self.tensor_product(plan);
+        &tensor + &tensor
+    }
+
+    /// Coefficients (a, b) of the loss along plan + t direction, a t^2 + b t + c
+    ///
+    /// Both plan and plan + direction are couplings of p and q, constC is then unchanged and
+    /// the loss is a second order polynomial of the step.
+    pub(crate) fn quadratic_coefficients(&self, plan: &Array2<A>, direction: &Array2<A>) -> (A, A) {
+        let h_d = self.h_c1.dot(direction).dot(&self.h_c2.t());
+        let h_g = self.h_c1.dot(plan).dot(&self.h_c2.t());
+
+        let a = -(&h_d * direction).sum();
+        let b = (&self.const_c * direction).sum() - (&h_g * direction).sum() - (&h_d * plan).sum();
+
+        (a, b)
+    }
+}
+
+/// Minimizer of a t^2 + b t over [0, 1], exact line search of the conditional gradient
+pub(crate) fn solve_1d_quadratic<A: Float>(a: A, b: A) -> A {
+    if a > A::zero() {
+        (-b / (a + a)).max(A::zero()).min(A::one())
+    } else if a + b < A::zero() {
+        A::one()
+    } else {
+        A::zero()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use ndarray::prelude::*;
+
+    use super::super::GWLoss;
+
+    #[allow(non_snake_case)]
+    #[test]
+    fn test_gw_terms_loss() {
+        let C1: Array2<f64> = array![[0., 1., 2.], [1., 0., 1.], [2., 1., 0.]];
+        let C2 = array![[0., 2.], [2., 0.]];
+        let p = array![0.5, 0.25, 0.25];
+        let q = array![0.5, 0.5];
+        let T = array![[0.25, 0.25], [0.25, 0.], [0., 0.25]];
+
+        // Naive sum_ijkl (C1_ik - C2_jl)^2 T_ij T_kl
+        let mut truth = 0f64;
+        for ((i, j), &t_ij) in T.indexed_iter() {
+            for ((k, l), &t_kl) in T.indexed_iter() {
+                truth += (C1[(i, k)] - C2[(j, l)]).powi(2) * t_ij * t_kl;
+            }
+        }
+
+        let terms = super::GWTerms::new(C1.view(), C2.view(), p.view(), q.view(), GWLoss::Square);
+
+        assert!((terms.loss(&T) - truth).abs() < 1E-12);
+    }
+}
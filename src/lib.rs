@@ -1,16 +1,78 @@
 #[cfg(feature = "blas")]
 extern crate blas_src;
 
+use num_traits::Float;
+
 mod error;
 pub mod exact;
+pub mod gromov;
 pub mod metrics;
 pub mod ndarray_logical;
+pub mod optim;
+pub mod parallel;
 pub mod prelude;
 pub mod regularized;
+pub mod sliced;
+pub mod smooth;
 pub mod unbalanced;
 pub mod utils;
 
 pub trait OTSolver {
+    type Output;
+
     fn check_shape(&self) -> Result<(), error::OTError>;
-    fn solve(&mut self) -> Result<ndarray::Array2<f64>, error::OTError>;
+    fn solve(&mut self) -> Result<Self::Output, error::OTError>;
+
+    /// Convergence log of the last call to solve
+    fn log(&self) -> &SolverLog;
+}
+
+/// Convergence diagnostics recorded by the solvers
+///
+/// The error is evaluated periodically during the iterations, each evaluation appends an
+/// entry to the error histories.
+#[derive(Debug, Clone, Default)]
+pub struct SolverLog {
+    /// Number of iterations performed
+    pub iterations: i32,
+    /// History of the L1 change of the target scaling v (or its log-domain potential)
+    pub v_change: Vec<f64>,
+    /// History of the L1 violation of the marginal constraints, empty for solvers that do not
+    /// measure it. The unbalanced solvers penalize the marginals instead of constraining them
+    /// and record the L1 deviation of the plan from the target weights
+    pub marginal_violation: Vec<f64>,
+    /// Last error compared against the stop threshold
+    pub error: f64,
+    /// True if the error went below the stop threshold before the max number of iterations
+    pub converged: bool,
+}
+
+impl SolverLog {
+    /// Appends an error evaluation to the histories and updates the last error
+    pub(crate) fn push<A: Float>(&mut self, v_change: A, marginal_violation: A, error: A) {
+        self.v_change.push(v_change.to_f64().unwrap());
+        self.marginal_violation
+            .push(marginal_violation.to_f64().unwrap());
+        self.error = error.to_f64().unwrap();
+    }
+
+    /// Appends an error evaluation to the change history and updates the last error, for
+    /// solvers that do not measure a marginal violation
+    pub(crate) fn push_change<A: Float>(&mut self, v_change: A, error: A) {
+        self.v_change.push(v_change.to_f64().unwrap());
+        self.error = error.to_f64().unwrap();
+    }
+
+    /// Returns a ConvergenceError if the solver did not converge
+    pub(crate) fn check_convergence<A: Float>(&self, threshold: A) -> Result<(), error::OTError> {
+        if self.converged {
+            return Ok(());
+        }
+
+        Err(error::OTError::ConvergenceError {
+            iterations: self.iterations,
+            error: self.error,
+            threshold: threshold.to_f64().unwrap(),
+        })
+    }
 }
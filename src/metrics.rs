@@ -1,6 +1,11 @@
 use ndarray::prelude::*;
+use ndarray::{Data, ScalarOperand};
 use ndarray_einsum_beta::*;
+use num_traits::Float;
 
+use crate::parallel::{self, Element};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum MetricType {
     SqEuclidean,
     Euclidean,
@@ -10,7 +15,16 @@ pub enum MetricType {
 /// x1: matrix with n1 samples of size d
 /// x2: matrix with n2 samples of size d
 /// metric: choice of distance metric
-pub fn dist(x1: &Array2<f64>, x2: &Array2<f64>, metric: MetricType) -> Array2<f64> {
+pub fn dist<A, S1, S2>(
+    x1: &ArrayBase<S1, Ix2>,
+    x2: &ArrayBase<S2, Ix2>,
+    metric: MetricType,
+) -> Array2<A>
+where
+    A: Float + ScalarOperand + Element,
+    S1: Data<Elem = A>,
+    S2: Data<Elem = A>,
+{
     match metric {
         MetricType::SqEuclidean => euclidean_distances(x1, x2, true),
         MetricType::Euclidean => euclidean_distances(x1, x2, false),
@@ -22,51 +36,59 @@ pub fn dist(x1: &Array2<f64>, x2: &Array2<f64>, metric: MetricType) -> Array2<f6
 /// X: matrix of nsamples x nfeatures
 /// Y: matrix of nsamples x nfeatures
 /// squared: Return squared Euclidean distances
-fn euclidean_distances(x: &Array2<f64>, y: &Array2<f64>, squared: bool) -> Array2<f64> {
+fn euclidean_distances<A, S1, S2>(
+    x: &ArrayBase<S1, Ix2>,
+    y: &ArrayBase<S2, Ix2>,
+    squared: bool,
+) -> Array2<A>
+where
+    A: Float + ScalarOperand + Element,
+    S1: Data<Elem = A>,
+    S2: Data<Elem = A>,
+{
     // einsum('ij,ij->i', X, X)
     // repeated i and j for both x and y inout matrices : multiply those components
     // - element-wise multiplication
     // ommitted j in output : sum along j axis
     // - summation in j
     let a2 = einsum("ij,ij->i", &[x, x]).unwrap();
+    let a2 = a2.into_dimensionality::<Ix1>().unwrap();
     // einsum('ij,ij->i', Y, Y)
     let b2 = einsum("ij,ij->i", &[y, y]).unwrap();
 
-    let mut c = (x.dot(&y.t())) * -2f64;
+    let (x, y) = (x.view(), y.view());
+    let mut c = Array2::<A>::zeros((x.nrows(), y.nrows()));
 
-    // c += a2[:, None]
-    for (mut row, a2val) in c.axis_iter_mut(Axis(0)).zip(&a2.t()) {
-        for ele in row.iter_mut() {
-            *ele += a2val;
-        }
-    }
+    // The distances are computed by blocks of rows, in parallel with the rayon feature
+    parallel::for_each_row_block(&mut c, |start, mut block| {
+        let rows = start..start + block.nrows();
 
-    // c += b2[None, :]
-    for (mut col, b2val) in c.axis_iter_mut(Axis(1)).zip(&b2) {
-        for ele in col.iter_mut() {
-            *ele += b2val;
-        }
-    }
+        // c = -2 x y^T
+        block.assign(&(x.slice(s![rows.clone(), ..]).dot(&y.t()) * A::from(-2.).unwrap()));
 
-    // c = nx.maximum(c, 0)
-    for val in c.iter_mut() {
-        if *val <= 0f64 {
-            *val = 0f64;
-        }
-    }
+        // c += a2[:, None] + b2[None, :]
+        for (mut row, a2val) in block.rows_mut().into_iter().zip(a2.slice(s![rows])) {
+            for (ele, b2val) in row.iter_mut().zip(&b2) {
+                *ele = *ele + *a2val + *b2val;
 
-    if !squared {
-        // np.sqrt(c)
-        for val in c.iter_mut() {
-            *val = val.powf(0.5);
+                // c = nx.maximum(c, 0)
+                if *ele <= A::zero() {
+                    *ele = A::zero();
+                }
+
+                if !squared {
+                    // np.sqrt(c)
+                    *ele = ele.sqrt();
+                }
+            }
         }
-    }
+    });
 
     if x == y {
         // ones matrix with diagonals set to zero
-        let mut anti_diag = Array2::<f64>::ones((a2.len(), b2.len()));
+        let mut anti_diag = Array2::<A>::ones((a2.len(), b2.len()));
         for ele in anti_diag.diag_mut().iter_mut() {
-            *ele = 0f64;
+            *ele = A::zero();
         }
 
         c = c * anti_diag;
@@ -125,4 +147,14 @@ mod tests {
 
         assert_eq!(M, truth);
     }
+
+    #[test]
+    fn test_dist_f32() {
+        let x = Array2::<f32>::zeros((3, 5));
+        let y = Array2::<f32>::from_elem((3, 5), 5.0);
+
+        let distance = super::dist(&x, &y, super::MetricType::SqEuclidean);
+
+        assert_eq!(distance, Array2::<f32>::from_elem((3, 3), 125.0));
+    }
 }
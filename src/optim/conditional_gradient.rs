@@ -0,0 +1,641 @@
+use ndarray::prelude::*;
+use ndarray::{Data, ScalarOperand};
+use num_traits::Float;
+
+use super::{LineSearch, Regularizer};
+use crate::error::OTError;
+use crate::exact::EarthMovers;
+use crate::parallel::Element;
+use crate::regularized::sinkhorn::SinkhornKnopp;
+use crate::{OTSolver, SolverLog};
+
+/// Solves the regularized Optimal Transport problem min <G, M> + reg f(G) with conditional
+/// gradient and returns the OT matrix
+///
+/// Each iteration linearizes the regularization term around the current plan and solves the
+/// resulting Earth Movers Distance, the plan then moves towards its solution with an Armijo or
+/// exact line search.
+///
+/// Regularized discrete optimal transport
+/// by Sira Ferradans, Nicolas Papadakis, Gabriel Peyré, Jean-François Aujol
+///
+/// ```rust
+/// use rust_optimal_transport as ot;
+/// use ot::prelude::*;
+/// use ot::optim::{regularizers::SquaredL2, LineSearch};
+/// use ndarray::prelude::*;
+/// use ndarray_stats::QuantileExt;
+///
+/// // Generate data
+/// let n = 50;
+///
+/// let cov = array![[1., 0.], [0., 1.]];
+/// let source = ot::utils::sample_2D_gauss(n, &array![0., 0.], &cov).unwrap();
+/// let target = ot::utils::sample_2D_gauss(n, &array![4., 4.], &cov).unwrap();
+///
+/// let source_weights = Array1::<f64>::from_elem(n, 1. / (n as f64));
+/// let target_weights = Array1::<f64>::from_elem(n, 1. / (n as f64));
+///
+/// // Normalize cost matrix for numerical stability
+/// let mut cost = dist(&source, &target, SqEuclidean);
+/// cost = &cost / *cost.max().unwrap();
+///
+/// let ot_matrix = match ConditionalGradient::new(
+///     &source_weights,
+///     &target_weights,
+///     &cost,
+///     1E-1,
+///     &SquaredL2,
+/// )
+/// .line_search(LineSearch::Exact)
+/// .solve()
+/// {
+///     Ok(result) => result,
+///     Err(error) => panic!("{:?}", error),
+/// };
+///
+/// ```
+///
+/// source_weights and target_weights represent histograms of the Source and Target distributions,
+/// respectively.
+///
+/// The target weights are rescaled to the total mass of the source weights on an internal copy.
+///
+pub struct ConditionalGradient<'a, A, R: ?Sized> {
+    source_weights: ArrayView1<'a, A>,
+    target_weights: ArrayView1<'a, A>,
+    cost: ArrayView2<'a, A>,
+    reg: A,
+    regularizer: &'a R,
+    line_search: LineSearch,
+    iterations: i32,
+    emd_iterations: i32,
+    threshold: A,
+    strict: bool,
+    log: SolverLog,
+}
+
+impl<'a, A, R> ConditionalGradient<'a, A, R>
+where
+    A: Float + ScalarOperand,
+    R: Regularizer<A> + ?Sized,
+{
+    pub fn new<S1, S2, S3>(
+        source_weights: &'a ArrayBase<S1, Ix1>,
+        target_weights: &'a ArrayBase<S2, Ix1>,
+        cost: &'a ArrayBase<S3, Ix2>,
+        reg: A,
+        regularizer: &'a R,
+    ) -> Self
+    where
+        S1: Data<Elem = A>,
+        S2: Data<Elem = A>,
+        S3: Data<Elem = A>,
+    {
+        Self {
+            source_weights: source_weights.view(),
+            target_weights: target_weights.view(),
+            cost: cost.view(),
+            reg,
+            regularizer,
+            line_search: LineSearch::Armijo,
+            iterations: 200,
+            emd_iterations: 100000,
+            threshold: A::from(1E-9).unwrap(),
+            strict: false,
+            log: SolverLog::default(),
+        }
+    }
+
+    /// Armijo by default, the exact line search requires a quadratic regularization term
+    pub fn line_search<'b>(&'b mut self, line_search: LineSearch) -> &'b mut Self {
+        self.line_search = line_search;
+        self
+    }
+
+    pub fn iterations<'b>(&'b mut self, iterations: i32) -> &'b mut Self {
+        self.iterations = iterations;
+        self
+    }
+
+    /// Max number of iterations of the network simplex solving each linear minimization step
+    pub fn emd_iterations<'b>(&'b mut self, emd_iterations: i32) -> &'b mut Self {
+        self.emd_iterations = emd_iterations;
+        self
+    }
+
+    /// Stop threshold on the absolute or relative change of the objective between two
+    /// iterations
+    pub fn threshold<'b>(&'b mut self, threshold: A) -> &'b mut Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Returns a ConvergenceError if the stop threshold is not reached within the max number
+    /// of iterations
+    pub fn strict<'b>(&'b mut self, strict: bool) -> &'b mut Self {
+        self.strict = strict;
+        self
+    }
+
+    pub fn reg<'b>(&'b mut self, reg: A) -> &'b mut Self {
+        self.reg = reg;
+        self
+    }
+}
+
+impl<'a, A, R> OTSolver for ConditionalGradient<'a, A, R>
+where
+    A: Float + ScalarOperand,
+    R: Regularizer<A> + ?Sized,
+{
+    type Output = Array2<A>;
+
+    fn check_shape(&self) -> Result<(), OTError> {
+        check_shape(self.source_weights, self.target_weights, self.cost)?;
+        self.regularizer.check_shape(self.cost.dim())
+    }
+
+    #[allow(non_snake_case)]
+    fn solve(&mut self) -> Result<Array2<A>, OTError> {
+        self.check_shape()?;
+
+        if self.iterations <= 0 {
+            return Err(OTError::ArgError(
+                "Iterations not a valid value. Must be > 0".to_string(),
+            ));
+        }
+
+        let (a, b) = normalize_weights(self.source_weights, self.target_weights);
+        let (M, reg, regularizer) = (self.cost, self.reg, self.regularizer);
+
+        let objective = |plan: &Array2<A>| (&M * plan).sum() + reg * regularizer.value(plan);
+
+        let mut log = SolverLog::default();
+        let mut plan = &a.view().insert_axis(Axis(1)) * &b.view().insert_axis(Axis(0));
+        let mut value = objective(&plan);
+
+        for count in 0..self.iterations {
+            // Linear minimization step on the gradient of the objective
+            let gradient = &M + &(regularizer.gradient(&plan) * reg);
+            // A truncated network simplex plan is not a minimizer of the linearized problem
+            let direction = EarthMovers::new(&a, &b, &gradient)
+                .iterations(self.emd_iterations)
+                .strict(true)
+                .solve()?
+                .plan
+                - &plan;
+
+            let slope = (&gradient * &direction).sum();
+
+            let step = match self.line_search {
+                LineSearch::Armijo => armijo(&objective, &plan, &direction, value, slope),
+                LineSearch::Exact => {
+                    let c = match regularizer.quadratic_coefficient(&direction) {
+                        Some(c) => c * reg,
+                        None => {
+                            return Err(OTError::ArgError(
+                                "Exact line search requires a quadratic regularizer".to_string(),
+                            ))
+                        }
+                    };
+
+                    // Minimizer of value + slope t + c t^2 over [0, 1]
+                    if c > A::zero() {
+                        (-slope / (c + c)).max(A::zero()).min(A::one())
+                    } else if slope + c < A::zero() {
+                        A::one()
+                    } else {
+                        A::zero()
+                    }
+                }
+            };
+
+            plan = plan + &direction * step;
+
+            let value_prev = value;
+            value = objective(&plan);
+
+            let abs_change = (value - value_prev).abs();
+            let rel_change = abs_change / value.abs();
+
+            let violation = (&plan.sum_axis(Axis(1)) - &a).mapv(A::abs).sum()
+                + (&plan.sum_axis(Axis(0)) - &b).mapv(A::abs).sum();
+
+            log.iterations = count + 1;
+            log.push(
+                direction.mapv(A::abs).sum() * step,
+                violation,
+                abs_change.min(rel_change),
+            );
+
+            if abs_change < self.threshold || rel_change < self.threshold {
+                log.converged = true;
+                break;
+            }
+        }
+
+        self.log = log;
+
+        if self.strict {
+            self.log.check_convergence(self.threshold)?;
+        }
+
+        Ok(plan)
+    }
+
+    fn log(&self) -> &SolverLog {
+        &self.log
+    }
+}
+
+/// Solves the doubly regularized Optimal Transport problem
+/// min <G, M> + reg_entropy sum(G log G) + reg f(G) with generalized conditional gradient and
+/// returns the OT matrix
+///
+/// The entropic term is kept as is while f is linearized, each linear minimization step is
+/// then an entropic OT problem solved with the Sinkhorn kernel, followed by an Armijo line
+/// search.
+///
+/// Generalized conditional gradient: analysis of convergence and applications
+/// by Alain Rakotomamonjy, Rémi Flamary, Nicolas Courty
+///
+/// ```rust
+/// use rust_optimal_transport as ot;
+/// use ot::prelude::*;
+/// use ot::optim::regularizers::GroupLasso;
+/// use ndarray::prelude::*;
+///
+/// // Two classes of source samples
+/// let source = array![[0., 0.], [0., 1.], [5., 0.], [5., 1.]];
+/// let target = array![[0.5, 0.5], [4.5, 0.5]];
+/// let labels = [0, 0, 1, 1];
+///
+/// let source_weights = Array1::<f64>::from_elem(4, 0.25);
+/// let target_weights = Array1::<f64>::from_elem(2, 0.5);
+///
+/// let cost = dist(&source, &target, SqEuclidean) / 50.;
+///
+/// let ot_matrix = match GeneralizedConditionalGradient::new(
+///     &source_weights,
+///     &target_weights,
+///     &cost,
+///     1E-1,
+///     1E-1,
+///     &GroupLasso::new(&labels),
+/// )
+/// .solve()
+/// {
+///     Ok(result) => result,
+///     Err(error) => panic!("{:?}", error),
+/// };
+///
+/// ```
+///
+/// source_weights and target_weights represent histograms of the Source and Target distributions,
+/// respectively.
+///
+/// The target weights are rescaled to the total mass of the source weights on an internal copy.
+///
+pub struct GeneralizedConditionalGradient<'a, A, R: ?Sized> {
+    source_weights: ArrayView1<'a, A>,
+    target_weights: ArrayView1<'a, A>,
+    cost: ArrayView2<'a, A>,
+    reg_entropy: A,
+    reg: A,
+    regularizer: &'a R,
+    iterations: i32,
+    sinkhorn_iterations: i32,
+    threshold: A,
+    strict: bool,
+    log: SolverLog,
+}
+
+impl<'a, A, R> GeneralizedConditionalGradient<'a, A, R>
+where
+    A: Float + ScalarOperand,
+    R: Regularizer<A> + ?Sized,
+{
+    pub fn new<S1, S2, S3>(
+        source_weights: &'a ArrayBase<S1, Ix1>,
+        target_weights: &'a ArrayBase<S2, Ix1>,
+        cost: &'a ArrayBase<S3, Ix2>,
+        reg_entropy: A,
+        reg: A,
+        regularizer: &'a R,
+    ) -> Self
+    where
+        S1: Data<Elem = A>,
+        S2: Data<Elem = A>,
+        S3: Data<Elem = A>,
+    {
+        Self {
+            source_weights: source_weights.view(),
+            target_weights: target_weights.view(),
+            cost: cost.view(),
+            reg_entropy,
+            reg,
+            regularizer,
+            iterations: 10,
+            sinkhorn_iterations: 200,
+            threshold: A::from(1E-9).unwrap(),
+            strict: false,
+            log: SolverLog::default(),
+        }
+    }
+
+    pub fn iterations<'b>(&'b mut self, iterations: i32) -> &'b mut Self {
+        self.iterations = iterations;
+        self
+    }
+
+    /// Max number of iterations of the Sinkhorn solver at each linear minimization step
+    pub fn sinkhorn_iterations<'b>(&'b mut self, sinkhorn_iterations: i32) -> &'b mut Self {
+        self.sinkhorn_iterations = sinkhorn_iterations;
+        self
+    }
+
+    /// Stop threshold on the absolute or relative change of the objective between two
+    /// iterations
+    pub fn threshold<'b>(&'b mut self, threshold: A) -> &'b mut Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Returns a ConvergenceError if the stop threshold is not reached within the max number
+    /// of iterations, or if a Sinkhorn step does not converge
+    pub fn strict<'b>(&'b mut self, strict: bool) -> &'b mut Self {
+        self.strict = strict;
+        self
+    }
+
+    pub fn reg_entropy<'b>(&'b mut self, reg_entropy: A) -> &'b mut Self {
+        self.reg_entropy = reg_entropy;
+        self
+    }
+
+    pub fn reg<'b>(&'b mut self, reg: A) -> &'b mut Self {
+        self.reg = reg;
+        self
+    }
+}
+
+impl<'a, A, R> OTSolver for GeneralizedConditionalGradient<'a, A, R>
+where
+    A: Float + ScalarOperand + Element,
+    R: Regularizer<A> + ?Sized,
+{
+    type Output = Array2<A>;
+
+    fn check_shape(&self) -> Result<(), OTError> {
+        check_shape(self.source_weights, self.target_weights, self.cost)?;
+        self.regularizer.check_shape(self.cost.dim())
+    }
+
+    #[allow(non_snake_case)]
+    fn solve(&mut self) -> Result<Array2<A>, OTError> {
+        self.check_shape()?;
+
+        if self.reg_entropy <= A::zero() {
+            return Err(OTError::ArgError("Regularization term <= 0".to_string()));
+        }
+
+        if self.iterations <= 0 {
+            return Err(OTError::ArgError(
+                "Iterations not a valid value. Must be > 0".to_string(),
+            ));
+        }
+
+        let (a, b) = normalize_weights(self.source_weights, self.target_weights);
+        let (M, reg, reg_entropy, regularizer) =
+            (self.cost, self.reg, self.reg_entropy, self.regularizer);
+
+        // 0 log 0 = 0
+        let xlogx = |x: A| if x > A::zero() { x * x.ln() } else { A::zero() };
+        let objective = |plan: &Array2<A>| {
+            (&M * plan).sum() + reg_entropy * plan.mapv(xlogx).sum() + reg * regularizer.value(plan)
+        };
+
+        let mut log = SolverLog::default();
+        let mut plan = &a.view().insert_axis(Axis(1)) * &b.view().insert_axis(Axis(0));
+        let mut value = objective(&plan);
+
+        for count in 0..self.iterations {
+            // Entropic OT on the linearized regularization term
+            let cost = &M + &(regularizer.gradient(&plan) * reg);
+            let direction = SinkhornKnopp::new(&a, &b, &cost, reg_entropy)
+                .iterations(self.sinkhorn_iterations)
+                .strict(self.strict)
+                .solve()?
+                - &plan;
+
+            // Slope of the objective, the entropic gradient is reg_entropy (1 + log G)
+            let gradient = &cost + &plan.mapv(|g| reg_entropy * (A::one() + g.ln()));
+            let slope = (&gradient * &direction).sum();
+
+            let step = armijo(&objective, &plan, &direction, value, slope);
+            plan = plan + &direction * step;
+
+            let value_prev = value;
+            value = objective(&plan);
+
+            let abs_change = (value - value_prev).abs();
+            let rel_change = abs_change / value.abs();
+
+            let violation = (&plan.sum_axis(Axis(1)) - &a).mapv(A::abs).sum()
+                + (&plan.sum_axis(Axis(0)) - &b).mapv(A::abs).sum();
+
+            log.iterations = count + 1;
+            log.push(
+                direction.mapv(A::abs).sum() * step,
+                violation,
+                abs_change.min(rel_change),
+            );
+
+            if abs_change < self.threshold || rel_change < self.threshold {
+                log.converged = true;
+                break;
+            }
+        }
+
+        self.log = log;
+
+        if self.strict {
+            self.log.check_convergence(self.threshold)?;
+        }
+
+        Ok(plan)
+    }
+
+    fn log(&self) -> &SolverLog {
+        &self.log
+    }
+}
+
+/// Ensures dimensions of the source and target measures are consistent with the cost matrix
+/// dimensions
+#[allow(non_snake_case)]
+fn check_shape<A>(a: ArrayView1<A>, b: ArrayView1<A>, M: ArrayView2<A>) -> Result<(), OTError> {
+    let mshape = M.shape();
+    let m0 = mshape[0];
+    let m1 = mshape[1];
+    let dim_a = a.len();
+    let dim_b = b.len();
+
+    if dim_a != m0 || dim_b != m1 {
+        return Err(OTError::WeightDimensionError {
+            dim_a,
+            dim_b,
+            dim_m_0: m0,
+            dim_m_1: m1,
+        });
+    }
+
+    Ok(())
+}
+
+/// Copies of the sample weights with the target weights rescaled to the total mass of the
+/// source weights
+fn normalize_weights<A: Float>(a: ArrayView1<A>, b: ArrayView1<A>) -> (Array1<A>, Array1<A>) {
+    let scale = a.sum() / b.sum();
+
+    (a.to_owned(), b.mapv(|w| w * scale))
+}
+
+/// Backtracking line search on the step t in [0, 1], halves the step until the Armijo
+/// condition f(G + t D) <= f(G) + c1 t slope holds
+///
+/// f: Objective
+/// plan: Current plan G
+/// direction: Descent direction D
+/// value: f(G)
+/// slope: Directional derivative <grad f(G), D>
+fn armijo<A, F>(f: &F, plan: &Array2<A>, direction: &Array2<A>, value: A, slope: A) -> A
+where
+    A: Float + ScalarOperand,
+    F: Fn(&Array2<A>) -> A,
+{
+    let c1 = A::from(1E-4).unwrap();
+    let min_step = A::from(1E-10).unwrap();
+    let half = A::from(0.5).unwrap();
+
+    if slope >= A::zero() {
+        return A::zero();
+    }
+
+    let mut step = A::one();
+    while step > min_step {
+        let candidate = plan + &(direction * step);
+        if f(&candidate) <= value + c1 * step * slope {
+            return step;
+        }
+        step = step * half;
+    }
+
+    A::zero()
+}
+
+#[cfg(test)]
+mod tests {
+
+    use ndarray::prelude::*;
+
+    use crate::metrics::{dist, MetricType};
+    use crate::optim::regularizers::{GroupLasso, SquaredL2};
+    use crate::optim::{LineSearch, Regularizer};
+    use crate::OTSolver;
+
+    #[test]
+    fn test_conditional_gradient() {
+        let source = array![[0., 0.], [1., 0.], [2., 0.]];
+        let target = array![[0., 1.], [1., 1.], [2., 1.]];
+
+        let a = Array1::<f64>::from_elem(3, 1. / 3.);
+        let b = Array1::<f64>::from_elem(3, 1. / 3.);
+        let m = dist(&source, &target, MetricType::SqEuclidean) / 5.;
+
+        let mut solver = super::ConditionalGradient::new(&a, &b, &m, 1., &SquaredL2);
+        let plan = match solver.line_search(LineSearch::Exact).solve() {
+            Ok(result) => result,
+            Err(error) => panic!("{:?}", error),
+        };
+
+        assert!(solver.log().converged);
+        assert!(*solver.log().marginal_violation.last().unwrap() < 1E-9);
+        assert!(plan.sum_axis(Axis(1)).abs_diff_eq(&a, 1E-9));
+        assert!(plan.sum_axis(Axis(0)).abs_diff_eq(&b, 1E-9));
+
+        // The quadratic regularization spreads the mass of the EMD plan around the diagonal
+        let emd_plan = Array2::<f64>::eye(3) / 3.;
+        let objective = |plan: &Array2<f64>| (&m * plan).sum() + SquaredL2.value(plan);
+
+        assert!(plan[(0, 1)] > 0.);
+        assert!(objective(&plan) < objective(&emd_plan));
+
+        // Both line searches agree
+        let plan_armijo = match super::ConditionalGradient::new(&a, &b, &m, 1., &SquaredL2)
+            .iterations(1000)
+            .solve()
+        {
+            Ok(result) => result,
+            Err(error) => panic!("{:?}", error),
+        };
+
+        assert!(plan_armijo.abs_diff_eq(&plan, 1E-3));
+
+        // Truncated network simplex plans are rejected
+        assert!(solver.emd_iterations(1).solve().is_err());
+
+        // Non quadratic regularizers only support the Armijo line search
+        let group_lasso = GroupLasso::new(&[0, 0, 1]);
+        assert!(
+            super::ConditionalGradient::new(&a, &b, &m, 1E-1, &group_lasso)
+                .line_search(LineSearch::Exact)
+                .solve()
+                .is_err()
+        );
+
+        // Every source sample needs a label
+        let group_lasso = GroupLasso::new(&[0, 1]);
+        assert!(
+            super::ConditionalGradient::new(&a, &b, &m, 1E-1, &group_lasso)
+                .solve()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_generalized_conditional_gradient() {
+        // Two classes of source samples, each target sample lies between both classes
+        let source = array![[0., 0.], [0., 1.], [2., 0.], [2., 1.]];
+        let target = array![[0.5, 0.], [1.5, 1.]];
+        let labels = [0, 0, 1, 1];
+
+        let a = Array1::<f64>::from_elem(4, 0.25);
+        let b = Array1::<f64>::from_elem(2, 0.5);
+        let m = dist(&source, &target, MetricType::SqEuclidean) / 2.;
+
+        let group_lasso = GroupLasso::new(&labels);
+
+        let mut solver =
+            super::GeneralizedConditionalGradient::new(&a, &b, &m, 1E-1, 1., &group_lasso);
+        let plan = match solver.iterations(100).solve() {
+            Ok(result) => result,
+            Err(error) => panic!("{:?}", error),
+        };
+
+        assert!(plan.sum_axis(Axis(0)).abs_diff_eq(&b, 1E-6));
+
+        // The group lasso lowers the number of classes sent to each target sample
+        let plan_entropic =
+            match crate::regularized::sinkhorn::SinkhornKnopp::new(&a, &b, &m, 1E-1).solve() {
+                Ok(result) => result,
+                Err(error) => panic!("{:?}", error),
+            };
+
+        assert!(group_lasso.value(&plan) < group_lasso.value(&plan_entropic));
+
+        // Truncated Sinkhorn steps are rejected in strict mode
+        assert!(solver.sinkhorn_iterations(1).strict(true).solve().is_err());
+    }
+}
@@ -0,0 +1,258 @@
+use ndarray::prelude::*;
+use ndarray::{ScalarOperand, Zip};
+use num_traits::Float;
+use std::collections::VecDeque;
+
+/// Outcome of a L-BFGS minimization
+pub(crate) struct LBFGSResult<A> {
+    /// Last iterate
+    pub x: Array1<A>,
+    /// Objective value at x
+    pub value: A,
+    /// Largest absolute entry of the projected gradient at x
+    pub grad_norm: A,
+    /// Number of iterations performed
+    pub iterations: i32,
+    /// True if the projected gradient went below the stop threshold
+    pub converged: bool,
+}
+
+/// Minimizes a smooth function with the limited memory BFGS quasi-Newton method
+///
+/// f: Objective, returns the value and the gradient at x
+/// x0: Starting point
+/// iterations: Max number of iterations
+/// threshold: Stop threshold on the largest absolute entry of the gradient
+/// memory: Number of correction pairs kept
+pub(crate) fn lbfgs<A, F>(
+    f: F,
+    x0: Array1<A>,
+    iterations: i32,
+    threshold: A,
+    memory: usize,
+) -> LBFGSResult<A>
+where
+    A: Float + ScalarOperand,
+    F: FnMut(&Array1<A>) -> (A, Array1<A>),
+{
+    let lower = Array1::from_elem(x0.len(), A::neg_infinity());
+    let upper = Array1::from_elem(x0.len(), A::infinity());
+
+    lbfgsb(f, x0, &lower, &upper, iterations, threshold, memory)
+}
+
+/// Minimizes a smooth function under the box constraints lower <= x <= upper with a
+/// projected limited memory BFGS quasi-Newton method
+///
+/// The variables held at a bound by the gradient form the active set and are left out of the
+/// step. The search directions of the free variables come from the two-loop recursion on the
+/// last `memory` pairs of steps and gradient changes, the step length from a backtracking line
+/// search along the projection on the box satisfying the Armijo condition.
+///
+/// f: Objective, returns the value and the gradient at x
+/// x0: Starting point, projected on the box
+/// lower: Lower bounds, -infinity for unbounded variables
+/// upper: Upper bounds, infinity for unbounded variables
+/// iterations: Max number of iterations
+/// threshold: Stop threshold on the largest absolute entry of the projected gradient
+/// memory: Number of correction pairs kept
+pub(crate) fn lbfgsb<A, F>(
+    mut f: F,
+    x0: Array1<A>,
+    lower: &Array1<A>,
+    upper: &Array1<A>,
+    iterations: i32,
+    threshold: A,
+    memory: usize,
+) -> LBFGSResult<A>
+where
+    A: Float + ScalarOperand,
+    F: FnMut(&Array1<A>) -> (A, Array1<A>),
+{
+    let c1 = A::from(1E-4).unwrap();
+    let half = A::from(0.5).unwrap();
+    let min_step = A::from(1E-20).unwrap();
+
+    let inf_norm = |g: &Array1<A>| g.fold(A::zero(), |acc, &x| acc.max(x.abs()));
+    let project = |x: Array1<A>| {
+        Zip::from(&x)
+            .and(lower)
+            .and(upper)
+            .map_collect(|&x, &l, &u| x.max(l).min(u))
+    };
+
+    // Gradient with the entries of the variables held at a bound set to zero
+    let projected_gradient = |x: &Array1<A>, g: &Array1<A>| {
+        Zip::from(x)
+            .and(g)
+            .and(lower)
+            .and(upper)
+            .map_collect(|&x, &g, &l, &u| {
+                if (x <= l && g > A::zero()) || (x >= u && g < A::zero()) {
+                    A::zero()
+                } else {
+                    g
+                }
+            })
+    };
+
+    let mut x = project(x0);
+    let (mut value, mut grad) = f(&x);
+    let mut free_grad = projected_gradient(&x, &grad);
+    let mut pairs: VecDeque<(Array1<A>, Array1<A>, A)> = VecDeque::with_capacity(memory);
+
+    let mut result = LBFGSResult {
+        x: Array1::zeros(0),
+        value,
+        grad_norm: inf_norm(&free_grad),
+        iterations: 0,
+        converged: false,
+    };
+
+    for count in 0..iterations {
+        if result.grad_norm < threshold {
+            result.converged = true;
+            break;
+        }
+
+        // Two-loop recursion, direction = -H grad on the free variables
+        let mut q = free_grad.clone();
+        let mut alphas = Vec::with_capacity(pairs.len());
+        for (s, y, rho) in pairs.iter().rev() {
+            let alpha = *rho * s.dot(&q);
+            q = q - y * alpha;
+            alphas.push(alpha);
+        }
+
+        // Initial Hessian approximation scaled with the last pair
+        if let Some((s, y, _)) = pairs.back() {
+            q = q * (s.dot(y) / y.dot(y));
+        }
+
+        for ((s, y, rho), alpha) in pairs.iter().zip(alphas.into_iter().rev()) {
+            let beta = *rho * y.dot(&q);
+            q = q + s * (alpha - beta);
+        }
+
+        // Steps of the variables held at a bound are dropped
+        let mut direction = Zip::from(&q)
+            .and(&free_grad)
+            .and(&grad)
+            .map_collect(|&d, &pg, &g| {
+                if pg == A::zero() && g != A::zero() {
+                    A::zero()
+                } else {
+                    -d
+                }
+            });
+
+        // Not a descent direction, restart from the steepest descent
+        if free_grad.dot(&direction) >= A::zero() {
+            pairs.clear();
+            direction = free_grad.mapv(|g| -g);
+        }
+
+        // Backtracking line search along the projection path
+        let mut step = if pairs.is_empty() {
+            A::one().min(A::one() / result.grad_norm)
+        } else {
+            A::one()
+        };
+
+        // Near the minimum the objective changes fall below its precision, steps keeping the
+        // objective level while decreasing the gradient are accepted as well
+        let tolerance = A::epsilon() * value.abs().max(A::one());
+
+        let (x_next, value_next, grad_next) = loop {
+            let candidate = project(&x + &(&direction * step));
+            let (value_candidate, grad_candidate) = f(&candidate);
+            let decrease = grad.dot(&(&candidate - &x));
+
+            if value_candidate <= value + c1 * decrease
+                || (value_candidate <= value + tolerance
+                    && inf_norm(&projected_gradient(&candidate, &grad_candidate))
+                        < result.grad_norm)
+            {
+                break (candidate, value_candidate, grad_candidate);
+            }
+
+            step = step * half;
+
+            if step < min_step {
+                break (x.clone(), value, grad.clone());
+            }
+        };
+
+        // The line search cannot make progress anymore
+        if step < min_step {
+            break;
+        }
+
+        let s = &x_next - &x;
+        let y = &grad_next - &grad;
+        let sy = s.dot(&y);
+
+        // Keeps the Hessian approximation positive definite
+        if sy > A::epsilon() * y.dot(&y) {
+            if pairs.len() == memory {
+                pairs.pop_front();
+            }
+            pairs.push_back((s, y, A::one() / sy));
+        }
+
+        x = x_next;
+        value = value_next;
+        grad = grad_next;
+        free_grad = projected_gradient(&x, &grad);
+
+        result.iterations = count + 1;
+        result.grad_norm = inf_norm(&free_grad);
+
+        if result.grad_norm < threshold {
+            result.converged = true;
+            break;
+        }
+    }
+
+    result.x = x;
+    result.value = value;
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+
+    use ndarray::prelude::*;
+
+    #[test]
+    fn test_lbfgs_rosenbrock() {
+        let rosenbrock = |x: &Array1<f64>| {
+            let (a, b) = (x[0], x[1]);
+            let value = (1. - a).powi(2) + 100. * (b - a * a).powi(2);
+            let grad = array![-2. * (1. - a) - 400. * a * (b - a * a), 200. * (b - a * a)];
+            (value, grad)
+        };
+
+        let result = super::lbfgs(rosenbrock, array![-1.2, 1.], 1000, 1E-10, 10);
+
+        assert!(result.converged);
+        assert!(result.x.abs_diff_eq(&array![1., 1.], 1E-5));
+
+        // The box excludes the unconstrained minimum, a = 0.5 and b = a^2 is optimal
+        let lower = array![-2., -2.];
+        let upper = array![0.5, 2.];
+        let result = super::lbfgsb(
+            rosenbrock,
+            array![-1.2, 1.],
+            &lower,
+            &upper,
+            1000,
+            1E-10,
+            10,
+        );
+
+        assert!(result.converged);
+        assert!(result.x.abs_diff_eq(&array![0.5, 0.25], 1E-5));
+    }
+}
@@ -0,0 +1,60 @@
+pub mod conditional_gradient;
+pub(crate) mod lbfgs;
+pub mod regularizers;
+
+use ndarray::prelude::*;
+
+use crate::error::OTError;
+
+/// Differentiable regularization term f(G) of the OT plan, used by the conditional gradient
+/// solvers to solve min <G, M> + reg f(G)
+///
+/// ```rust
+/// use rust_optimal_transport as ot;
+/// use ot::optim::Regularizer;
+/// use ndarray::prelude::*;
+///
+/// // f(G) = sum(G^3) / 3
+/// struct Cubic;
+///
+/// impl Regularizer<f64> for Cubic {
+///     fn value(&self, plan: &Array2<f64>) -> f64 {
+///         plan.mapv(|g| g.powi(3)).sum() / 3.
+///     }
+///
+///     fn gradient(&self, plan: &Array2<f64>) -> Array2<f64> {
+///         plan.mapv(|g| g * g)
+///     }
+/// }
+///
+/// ```
+pub trait Regularizer<A> {
+    /// Value of the regularization term
+    fn value(&self, plan: &Array2<A>) -> A;
+
+    /// Gradient of the regularization term with respect to the plan
+    fn gradient(&self, plan: &Array2<A>) -> Array2<A>;
+
+    /// Second order coefficient c of f(G + t D) = f(G) + t <grad f(G), D> + c t^2 when the
+    /// regularization term is quadratic, None otherwise
+    ///
+    /// Required by the exact line search.
+    fn quadratic_coefficient(&self, _direction: &Array2<A>) -> Option<A> {
+        None
+    }
+
+    /// Ensures the regularization term is defined on plans of the given (n_source, n_target)
+    /// dimensions
+    fn check_shape(&self, _dim: (usize, usize)) -> Result<(), OTError> {
+        Ok(())
+    }
+}
+
+/// Step size selection of the conditional gradient iterations
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LineSearch {
+    /// Backtracking line search satisfying the Armijo condition
+    Armijo,
+    /// Closed form step, for quadratic regularization terms
+    Exact,
+}
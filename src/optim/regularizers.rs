@@ -0,0 +1,120 @@
+use ndarray::prelude::*;
+use ndarray::ScalarOperand;
+use num_traits::Float;
+
+use super::Regularizer;
+use crate::error::OTError;
+
+/// Squared Frobenius norm regularization, f(G) = ||G||^2 / 2
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SquaredL2;
+
+impl<A> Regularizer<A> for SquaredL2
+where
+    A: Float + ScalarOperand,
+{
+    fn value(&self, plan: &Array2<A>) -> A {
+        plan.fold(A::zero(), |acc, &g| acc + g * g) / A::from(2.).unwrap()
+    }
+
+    fn gradient(&self, plan: &Array2<A>) -> Array2<A> {
+        plan.clone()
+    }
+
+    fn quadratic_coefficient(&self, direction: &Array2<A>) -> Option<A> {
+        Some(self.value(direction))
+    }
+}
+
+/// Group lasso regularization on the source samples, f(G) = sum_j sum_c ||G[I_c, j]||_2
+///
+/// I_c holds the source samples of class c. Each target sample is encouraged to receive mass
+/// from a single class, as in domain adaptation with labeled source samples.
+///
+/// Optimal Transport for Domain Adaptation
+/// by Nicolas Courty, Rémi Flamary, Devis Tuia, Alain Rakotomamonjy
+#[derive(Debug, Clone)]
+pub struct GroupLasso {
+    labels: Vec<usize>,
+    n_classes: usize,
+}
+
+impl GroupLasso {
+    /// labels: Class of each source sample
+    pub fn new(labels: &[usize]) -> Self {
+        Self {
+            labels: labels.to_vec(),
+            n_classes: labels.iter().map(|&c| c + 1).max().unwrap_or(0),
+        }
+    }
+
+    /// L2 norm of each group of each column, (n_classes, n_target)
+    fn group_norms<A: Float>(&self, plan: &Array2<A>) -> Array2<A> {
+        assert_eq!(
+            self.labels.len(),
+            plan.nrows(),
+            "GroupLasso needs one label per source sample"
+        );
+
+        let mut norms = Array2::<A>::zeros((self.n_classes, plan.ncols()));
+        for (row, &c) in plan.axis_iter(Axis(0)).zip(&self.labels) {
+            let mut norm = norms.row_mut(c);
+            azip!((n in &mut norm, &g in &row) *n = *n + g * g);
+        }
+
+        norms.mapv_into(A::sqrt)
+    }
+}
+
+impl<A> Regularizer<A> for GroupLasso
+where
+    A: Float + ScalarOperand,
+{
+    fn value(&self, plan: &Array2<A>) -> A {
+        self.group_norms(plan).sum()
+    }
+
+    fn gradient(&self, plan: &Array2<A>) -> Array2<A> {
+        let norms = self.group_norms(plan);
+
+        // G[I_c, j] / ||G[I_c, j]||, 0 on empty groups
+        let mut gradient = plan.clone();
+        for (mut row, &c) in gradient.axis_iter_mut(Axis(0)).zip(&self.labels) {
+            azip!((g in &mut row, &n in &norms.row(c)) *g = if n > A::zero() { *g / n } else { A::zero() });
+        }
+
+        gradient
+    }
+
+    fn check_shape(&self, dim: (usize, usize)) -> Result<(), OTError> {
+        if self.labels.len() != dim.0 {
+            return Err(OTError::ArgError(format!(
+                "GroupLasso has {} labels for {} source samples",
+                self.labels.len(),
+                dim.0
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use ndarray::prelude::*;
+
+    use crate::optim::Regularizer;
+
+    #[test]
+    fn test_group_lasso() {
+        let plan: Array2<f64> = array![[3., 0.], [4., 1.], [0., 2.]];
+        let regularizer = super::GroupLasso::new(&[0, 0, 1]);
+
+        // ||(3, 4)|| + ||(0, 1)|| + ||0|| + ||2||
+        assert!((regularizer.value(&plan) - 8.).abs() < 1E-12);
+
+        let truth = array![[0.6, 0.], [0.8, 1.], [0., 1.]];
+        assert!(regularizer.gradient(&plan).abs_diff_eq(&truth, 1E-12));
+    }
+}
@@ -0,0 +1,144 @@
+//! Row-block parallelism of the dense kernels behind the `rayon` feature
+//!
+//! With the feature enabled, the matrix-vector products and elementwise maps of the Sinkhorn
+//! iterations and the distance matrices of `metrics::dist` are split over row blocks on the
+//! rayon thread pool. Without BLAS, every entry is computed by the same sequence of floating
+//! point operations as the serial code, so results do not depend on the feature or the number
+//! of threads. With BLAS, the serial matrix-vector products use gemv and may differ in the
+//! last bits.
+
+use ndarray::prelude::*;
+use ndarray::{Data, LinalgScalar, Zip};
+
+#[cfg(feature = "rayon")]
+use ndarray::parallel::prelude::*;
+
+/// Number of rows per block of the blocked kernels
+pub(crate) const ROW_BLOCK: usize = 64;
+
+/// Element types shareable between the worker threads of the `rayon` feature
+///
+/// Without the feature, every type is an Element.
+#[cfg(feature = "rayon")]
+pub trait Element: Send + Sync {}
+
+#[cfg(feature = "rayon")]
+impl<T: Send + Sync> Element for T {}
+
+/// Element types shareable between the worker threads of the `rayon` feature
+///
+/// Without the feature, every type is an Element.
+#[cfg(not(feature = "rayon"))]
+pub trait Element {}
+
+#[cfg(not(feature = "rayon"))]
+impl<T> Element for T {}
+
+/// Matrix-vector product m.dot(x)
+///
+/// In parallel, each entry is the dot product of a row of m with x, as computed by ndarray
+/// without BLAS.
+#[cfg(feature = "rayon")]
+pub(crate) fn mat_vec<A, S1, S2>(m: &ArrayBase<S1, Ix2>, x: &ArrayBase<S2, Ix1>) -> Array1<A>
+where
+    A: LinalgScalar + Element,
+    S1: Data<Elem = A>,
+    S2: Data<Elem = A>,
+{
+    let mut out = Array1::<A>::zeros(m.nrows());
+    let x = x.view();
+
+    Zip::from(&mut out)
+        .and(m.rows())
+        .par_for_each(|out, row| *out = row.dot(&x));
+
+    out
+}
+
+/// Matrix-vector product m.dot(x), with BLAS gemv when enabled
+#[cfg(not(feature = "rayon"))]
+pub(crate) fn mat_vec<A, S1, S2>(m: &ArrayBase<S1, Ix2>, x: &ArrayBase<S2, Ix1>) -> Array1<A>
+where
+    A: LinalgScalar + Element,
+    S1: Data<Elem = A>,
+    S2: Data<Elem = A>,
+{
+    m.dot(x)
+}
+
+/// Elementwise map of a matrix, m.mapv(f)
+pub(crate) fn map<A, S, F>(m: &ArrayBase<S, Ix2>, f: F) -> Array2<A>
+where
+    A: Copy + Element,
+    S: Data<Elem = A>,
+    F: Fn(A) -> A + Element,
+{
+    #[cfg(feature = "rayon")]
+    return Zip::from(m).par_map_collect(|&m| f(m));
+
+    #[cfg(not(feature = "rayon"))]
+    return Zip::from(m).map_collect(|&m| f(m));
+}
+
+/// Fills the blocks of ROW_BLOCK rows of out with f(index of the first row, block)
+pub(crate) fn for_each_row_block<A, F>(out: &mut Array2<A>, f: F)
+where
+    A: Element,
+    F: Fn(usize, ArrayViewMut2<A>) + Element,
+{
+    #[cfg(feature = "rayon")]
+    out.axis_chunks_iter_mut(Axis(0), ROW_BLOCK)
+        .into_par_iter()
+        .enumerate()
+        .for_each(|(i, block)| f(i * ROW_BLOCK, block));
+
+    #[cfg(not(feature = "rayon"))]
+    out.axis_chunks_iter_mut(Axis(0), ROW_BLOCK)
+        .enumerate()
+        .for_each(|(i, block)| f(i * ROW_BLOCK, block));
+}
+
+#[cfg(test)]
+mod tests {
+
+    use ndarray::prelude::*;
+
+    // BLAS gemv may round the serial products differently
+    #[cfg(not(any(feature = "openblas", feature = "openblas-system")))]
+    #[test]
+    fn test_mat_vec() {
+        let m = Array::range(0., 300., 1.)
+            .mapv(|x: f64| (x * 0.37).sin())
+            .into_shape((150, 2))
+            .unwrap();
+        let x = array![0.3, -1.7];
+        let y = Array::range(0., 150., 1.).mapv(|y: f64| (y * 0.11).cos());
+
+        assert_eq!(super::mat_vec(&m, &x), m.dot(&x));
+        assert_eq!(super::mat_vec(&m.t(), &y), m.t().dot(&y));
+    }
+
+    #[test]
+    fn test_map() {
+        let m = Array::range(0., 300., 1.).into_shape((100, 3)).unwrap();
+        let f = |x: f64| (-x / 7.).exp();
+
+        assert_eq!(super::map(&m, f), m.mapv(f));
+        assert_eq!(super::map(&m.t(), f), m.t().mapv(f));
+    }
+
+    #[test]
+    fn test_for_each_row_block() {
+        let mut out = Array2::<usize>::zeros((150, 3));
+
+        super::for_each_row_block(&mut out, |start, mut block| {
+            for (i, mut row) in block.rows_mut().into_iter().enumerate() {
+                row.fill(start + i);
+            }
+        });
+
+        for (i, row) in out.rows().into_iter().enumerate() {
+            assert!(row.iter().all(|&x| x == i));
+        }
+    }
+}
@@ -7,14 +7,49 @@
 //!
 //! ```
 
-pub use crate::OTSolver;
+pub use crate::{OTSolver, SolverLog};
 
 pub use crate::error::OTError;
 
-pub use crate::exact::EarthMovers;
+pub use crate::exact::{
+    barycenter::FreeSupportBarycenter,
+    partial::PartialEarthMovers,
+    solver_1d::{emd_1d, wasserstein_1d},
+    EMDResult, EarthMovers,
+};
 
-pub use crate::regularized::{greenkhorn::Greenkhorn, sinkhorn::SinkhornKnopp};
+pub use crate::gromov::{
+    entropic::EntropicGromovWasserstein,
+    fused::{
+        EntropicFusedGromovWasserstein, FGWBarycenter, FGWBarycenterResult, FusedGromovWasserstein,
+    },
+    gromov_wasserstein::GromovWasserstein,
+    GWLoss, GWResult,
+};
 
-pub use crate::unbalanced::SinkhornKnoppUnbalanced;
+pub use crate::optim::{
+    conditional_gradient::{ConditionalGradient, GeneralizedConditionalGradient},
+    LineSearch, Regularizer,
+};
+
+pub use crate::regularized::{
+    barycenter::SinkhornBarycenter,
+    empirical::{EmpiricalSinkhorn, EmpiricalSinkhornResult},
+    greenkhorn::Greenkhorn,
+    loss::SinkhornDivergence,
+    partial::SinkhornPartial,
+    screenkhorn::{Screenkhorn, ScreenkhornResult},
+    sinkhorn::{SinkhornKnopp, SinkhornLog},
+    sinkhorn_stabilized::{SinkhornEpsilonScaling, SinkhornStabilized},
+};
+
+pub use crate::sliced::{MaxSlicedWasserstein, SlicedWasserstein};
+
+pub use crate::smooth::{SmoothOT, SmoothOTResult, SmoothPlan};
+
+pub use crate::unbalanced::{
+    barycenter::SinkhornUnbalancedBarycenter, lbfgsb::LBFGSBUnbalanced, mm::MMUnbalanced,
+    MarginalDivergence, SinkhornKnoppUnbalanced, SinkhornLogUnbalanced, UnbalancedResult,
+};
 
 pub use crate::metrics::{dist, MetricType::Euclidean, MetricType::SqEuclidean};
@@ -0,0 +1,449 @@
+use ndarray::prelude::*;
+use ndarray::{Data, ScalarOperand};
+use num_traits::Float;
+
+use super::sinkhorn::logsumexp;
+use crate::error::OTError;
+use crate::{OTSolver, SolverLog};
+
+/// Computes the entropic regularized Wasserstein barycenter of histograms sharing a support
+/// and returns the barycenter histogram
+///
+/// The barycenter minimizes sum_k w_k OT_reg(h_k, b), it is obtained with iterative Bregman
+/// projections on the Sinkhorn kernel K = exp(-M/reg).
+///
+/// Iterative Bregman Projections for Regularized Transportation Problems
+/// by Jean-David Benamou, Guillaume Carlier, Marco Cuturi, Luca Nenna, Gabriel Peyré
+///
+/// ```rust
+/// use rust_optimal_transport as ot;
+/// use ot::prelude::*;
+/// use ndarray::prelude::*;
+/// use ndarray_stats::QuantileExt;
+///
+/// let n = 100;
+///
+/// // Two 1D gaussian histograms, stacked as columns
+/// let h1 = ot::utils::get_1D_gauss_histogram(n, 20., 5.).unwrap();
+/// let h2 = ot::utils::get_1D_gauss_histogram(n, 60., 8.).unwrap();
+/// let histograms = ndarray::stack![Axis(1), h1, h2];
+///
+/// // Cost between the bins of the support
+/// let x = Array::range(0., n as f64, 1.).into_shape((n, 1)).unwrap();
+/// let mut cost = dist(&x, &x, SqEuclidean);
+/// cost = &cost / *cost.max().unwrap();
+///
+/// // Uniform weights on the histograms
+/// let weights = Array1::<f64>::zeros(0);
+///
+/// let barycenter = match SinkhornBarycenter::new(&histograms, &weights, &cost, 1E-3)
+///     .log_domain(true)
+///     .solve()
+/// {
+///     Ok(result) => result,
+///     Err(error) => panic!("{:?}", error),
+/// };
+///
+/// ```
+///
+/// histograms: (dim, n_hists) array, each column is a histogram on the shared support
+/// weights: Weights of the histograms in the barycenter (uniform if empty)
+/// cost: (dim, dim) loss matrix between the bins of the support
+///
+pub struct SinkhornBarycenter<'a, A> {
+    histograms: ArrayView2<'a, A>,
+    weights: ArrayView1<'a, A>,
+    cost: ArrayView2<'a, A>,
+    reg: A,
+    iterations: i32,
+    threshold: A,
+    log_domain: bool,
+    strict: bool,
+    log: SolverLog,
+}
+
+impl<'a, A> SinkhornBarycenter<'a, A>
+where
+    A: Float + ScalarOperand,
+{
+    pub fn new<S1, S2, S3>(
+        histograms: &'a ArrayBase<S1, Ix2>,
+        weights: &'a ArrayBase<S2, Ix1>,
+        cost: &'a ArrayBase<S3, Ix2>,
+        reg: A,
+    ) -> Self
+    where
+        S1: Data<Elem = A>,
+        S2: Data<Elem = A>,
+        S3: Data<Elem = A>,
+    {
+        Self {
+            histograms: histograms.view(),
+            weights: weights.view(),
+            cost: cost.view(),
+            reg,
+            iterations: 1000,
+            threshold: A::from(1E-9).unwrap(),
+            log_domain: false,
+            strict: false,
+            log: SolverLog::default(),
+        }
+    }
+
+    pub fn iterations<'b>(&'b mut self, iterations: i32) -> &'b mut Self {
+        self.iterations = iterations;
+        self
+    }
+
+    pub fn threshold<'b>(&'b mut self, threshold: A) -> &'b mut Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Returns a ConvergenceError if the stop threshold is not reached within the max number
+    /// of iterations
+    pub fn strict<'b>(&'b mut self, strict: bool) -> &'b mut Self {
+        self.strict = strict;
+        self
+    }
+
+    pub fn reg<'b>(&'b mut self, reg: A) -> &'b mut Self {
+        self.reg = reg;
+        self
+    }
+
+    /// Performs the Bregman projections on log-domain potentials, stable for small
+    /// regularization terms
+    pub fn log_domain<'b>(&'b mut self, log_domain: bool) -> &'b mut Self {
+        self.log_domain = log_domain;
+        self
+    }
+}
+
+impl<'a, A> OTSolver for SinkhornBarycenter<'a, A>
+where
+    A: Float + ScalarOperand,
+{
+    type Output = Array1<A>;
+
+    /// Ensures the cost matrix is defined on the support of the histograms and that there is
+    /// one weight per histogram
+    fn check_shape(&self) -> Result<(), OTError> {
+        let mshape = self.cost.shape();
+        let m0 = mshape[0];
+        let m1 = mshape[1];
+        let dim = self.histograms.nrows();
+
+        if dim != m0 || dim != m1 {
+            return Err(OTError::WeightDimensionError {
+                dim_a: dim,
+                dim_b: dim,
+                dim_m_0: m0,
+                dim_m_1: m1,
+            });
+        }
+
+        if !self.weights.is_empty() && self.weights.len() != self.histograms.ncols() {
+            return Err(OTError::ArgError(
+                "Number of weights does not match the number of histograms".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn solve(&mut self) -> Result<Array1<A>, OTError> {
+        self.check_shape()?;
+
+        if self.reg <= A::zero() {
+            return Err(OTError::ArgError("Regularization term <= 0".to_string()));
+        }
+
+        if self.iterations <= 0 {
+            return Err(OTError::ArgError(
+                "Iterations not a valid value. Must be > 0".to_string(),
+            ));
+        }
+
+        if self.histograms.ncols() == 0 {
+            return Err(OTError::ArgError("No histograms".to_string()));
+        }
+
+        // Normalized barycenter weights, on a copy
+        let n_hists = self.histograms.ncols();
+        let mut weights = if self.weights.is_empty() {
+            Array1::<A>::ones(n_hists)
+        } else {
+            self.weights.to_owned()
+        };
+        let total = weights.sum();
+        weights.mapv_inplace(|w| w / total);
+
+        let (barycenter, log) = if self.log_domain {
+            barycenter_sinkhorn_log(
+                self.histograms,
+                &weights,
+                self.cost,
+                self.reg,
+                self.iterations,
+                self.threshold,
+            )
+        } else {
+            barycenter_sinkhorn(
+                self.histograms,
+                &weights,
+                self.cost,
+                self.reg,
+                self.iterations,
+                self.threshold,
+            )
+        };
+
+        self.log = log;
+
+        if self.strict {
+            self.log.check_convergence(self.threshold)?;
+        }
+
+        Ok(barycenter)
+    }
+
+    fn log(&self) -> &SolverLog {
+        &self.log
+    }
+}
+
+/// Computes the entropic regularized Wasserstein barycenter with iterative Bregman
+/// projections and returns the barycenter
+///
+/// histograms: Histograms, (dim, n_hists)
+/// weights: Normalized weights of the histograms
+/// M: Loss matrix, (dim, dim)
+/// reg: Entropy regularization term > 0
+/// iterations: Max number of iterations
+/// threshold: Stop threshold on the L1 violation of the histogram marginals
+#[allow(non_snake_case)]
+fn barycenter_sinkhorn<A>(
+    histograms: ArrayView2<A>,
+    weights: &Array1<A>,
+    M: ArrayView2<A>,
+    reg: A,
+    iterations: i32,
+    threshold: A,
+) -> (Array1<A>, SolverLog)
+where
+    A: Float + ScalarOperand,
+{
+    let mut log = SolverLog::default();
+
+    // K = exp(-M/reg)
+    let k = M.mapv(|ele| (-ele / reg).exp());
+
+    // One pair of scalings per histogram, stored as columns
+    let mut u = Array2::<A>::ones(histograms.dim());
+    let mut v = Array2::<A>::ones(histograms.dim());
+    let mut barycenter = Array1::<A>::zeros(histograms.nrows());
+
+    for count in 0..iterations {
+        let barycenter_prev = barycenter.clone();
+
+        // Projection on the histogram marginals: u = A / K v
+        let kv = k.dot(&v);
+        azip!((u in &mut u, &a in &histograms, &kv in &kv) *u = a / kv);
+
+        // Projection on a common second marginal: the weighted geometric mean of K^T u
+        let ktu = k.t().dot(&u);
+        barycenter = ktu.mapv(A::ln).dot(weights).mapv_into(A::exp);
+
+        // v = barycenter / K^T u
+        for (mut v, ktu) in v.axis_iter_mut(Axis(1)).zip(ktu.axis_iter(Axis(1))) {
+            azip!((v in &mut v, &ktu in &ktu, &p in &barycenter) *v = p / ktu);
+        }
+
+        log.iterations = count + 1;
+
+        if count % 10 == 0 {
+            // Violation of the histogram marginals: sum(G_k, axis=1) = u_k * K v_k
+            let kv = k.dot(&v);
+            let err = (&u * &kv - histograms).mapv(A::abs).sum();
+
+            log.push(
+                (&barycenter - &barycenter_prev).mapv(A::abs).sum(),
+                err,
+                err,
+            );
+
+            if err < threshold {
+                log.converged = true;
+                break;
+            }
+        }
+    }
+
+    (barycenter, log)
+}
+
+/// Computes the entropic regularized Wasserstein barycenter with iterative Bregman
+/// projections in the log-domain and returns the barycenter
+///
+/// histograms: Histograms, (dim, n_hists)
+/// weights: Normalized weights of the histograms
+/// M: Loss matrix, (dim, dim)
+/// reg: Entropy regularization term > 0
+/// iterations: Max number of iterations
+/// threshold: Stop threshold on the L1 violation of the histogram marginals
+#[allow(non_snake_case)]
+fn barycenter_sinkhorn_log<A>(
+    histograms: ArrayView2<A>,
+    weights: &Array1<A>,
+    M: ArrayView2<A>,
+    reg: A,
+    iterations: i32,
+    threshold: A,
+) -> (Array1<A>, SolverLog)
+where
+    A: Float + ScalarOperand,
+{
+    let mut log = SolverLog::default();
+    let dim = histograms.nrows();
+
+    let log_a = histograms.mapv(A::ln);
+    let log_k = M.mapv(|ele| -ele / reg);
+
+    // Scaled dual potential g_k/reg, stored as columns
+    let mut g = Array2::<A>::zeros(histograms.dim());
+    let mut log_barycenter = Array1::<A>::zeros(dim);
+
+    // log(K exp(g)) for each column of g
+    let log_kv = |g: &Array2<A>| {
+        let mut out = Array2::<A>::zeros(g.dim());
+        for ((i, k), ele) in out.indexed_iter_mut() {
+            *ele = logsumexp(log_k.row(i).iter().zip(g.column(k)).map(|(&m, &g)| m + g));
+        }
+        out
+    };
+
+    for count in 0..iterations {
+        let log_barycenter_prev = log_barycenter.clone();
+
+        // Scaled dual potential f_k/reg = log(A) - log(K exp(g))
+        let f = &log_a - &log_kv(&g);
+
+        // log(K^T exp(f))
+        let mut log_ktu = Array2::<A>::zeros(histograms.dim());
+        for ((j, k), ele) in log_ktu.indexed_iter_mut() {
+            *ele = logsumexp(
+                log_k
+                    .column(j)
+                    .iter()
+                    .zip(f.column(k))
+                    .map(|(&m, &f)| m + f),
+            );
+        }
+
+        log_barycenter = log_ktu.dot(weights);
+
+        // g = log(barycenter) - log(K^T exp(f))
+        for (mut g, log_ktu) in g.axis_iter_mut(Axis(1)).zip(log_ktu.axis_iter(Axis(1))) {
+            azip!((g in &mut g, &l in &log_ktu, &p in &log_barycenter) *g = p - l);
+        }
+
+        log.iterations = count + 1;
+
+        if count % 10 == 0 {
+            // Violation of the histogram marginals
+            let lkv = log_kv(&g);
+            let mut err = A::zero();
+            azip!((&f in &f, &l in &lkv, &a in &histograms) err = err + ((f + l).exp() - a).abs());
+
+            let v_change = (&log_barycenter.mapv(A::exp) - &log_barycenter_prev.mapv(A::exp))
+                .mapv(A::abs)
+                .sum();
+            log.push(v_change, err, err);
+
+            if err < threshold {
+                log.converged = true;
+                break;
+            }
+        }
+    }
+
+    (log_barycenter.mapv_into(A::exp), log)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use ndarray::prelude::*;
+
+    use crate::utils::get_1D_gauss_histogram;
+    use crate::OTSolver;
+
+    #[test]
+    fn test_sinkhorn_barycenter() {
+        let n = 50;
+        let h1 = get_1D_gauss_histogram(n, 10., 3.).unwrap();
+        let h2 = get_1D_gauss_histogram(n, 30., 3.).unwrap();
+        let histograms = ndarray::stack![Axis(1), h1, h2];
+
+        let x = Array::range(0., n as f64, 1.).into_shape((n, 1)).unwrap();
+        let cost = crate::metrics::dist(&x, &x, crate::metrics::MetricType::SqEuclidean);
+        let cost = &cost / (n * n) as f64;
+
+        let weights = array![0.5, 0.5];
+        let reg = 1E-2;
+
+        let mut solver = super::SinkhornBarycenter::new(&histograms, &weights, &cost, reg);
+        let barycenter = match solver.solve() {
+            Ok(result) => result,
+            Err(error) => panic!("{:?}", error),
+        };
+
+        assert!(solver.log().converged);
+        assert!((barycenter.sum() - 1.).abs() < 1E-6);
+
+        // Displacement interpolation, the barycenter is centered between both histograms
+        let mean = barycenter.dot(&x.column(0));
+        assert!((mean - 20.).abs() < 0.5);
+
+        // Log-domain projections reach the same barycenter
+        let barycenter_log = match super::SinkhornBarycenter::new(&histograms, &weights, &cost, reg)
+            .log_domain(true)
+            .solve()
+        {
+            Ok(result) => result,
+            Err(error) => panic!("{:?}", error),
+        };
+
+        assert!(barycenter_log.abs_diff_eq(&barycenter, 1E-6));
+    }
+
+    #[test]
+    fn test_sinkhorn_barycenter_log_small_reg() {
+        let n = 50;
+        let h1 = get_1D_gauss_histogram(n, 10., 3.).unwrap();
+        let h2 = get_1D_gauss_histogram(n, 30., 3.).unwrap();
+        let histograms = ndarray::stack![Axis(1), h1, h2];
+
+        let x = Array::range(0., n as f64, 1.).into_shape((n, 1)).unwrap();
+        let cost = crate::metrics::dist(&x, &x, crate::metrics::MetricType::SqEuclidean);
+        let cost = &cost / (n * n) as f64;
+
+        let weights = Array1::<f64>::zeros(0);
+
+        let barycenter = match super::SinkhornBarycenter::new(&histograms, &weights, &cost, 1E-4)
+            .log_domain(true)
+            .solve()
+        {
+            Ok(result) => result,
+            Err(error) => panic!("{:?}", error),
+        };
+
+        assert!(!crate::ndarray_logical::is_nan(&barycenter));
+        assert!((barycenter.sum() - 1.).abs() < 1E-6);
+
+        // Close to the translated histogram for small regularization
+        let truth = get_1D_gauss_histogram(n, 20., 3.).unwrap();
+        assert!(barycenter.abs_diff_eq(&truth, 1E-2));
+    }
+}
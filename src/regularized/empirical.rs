@@ -0,0 +1,487 @@
+use ndarray::prelude::*;
+use ndarray::{Data, ScalarOperand, Zip};
+use num_traits::Float;
+
+use crate::error::OTError;
+use crate::metrics::{dist, MetricType};
+use crate::parallel::Element;
+use crate::{OTSolver, SolverLog};
+
+/// Dual potentials of the entropic regularization optimal transport problem between two point
+/// clouds, the plan exp((alpha_i + beta_j - M_ij) / reg) is evaluated on demand
+#[derive(Debug, Clone)]
+pub struct EmpiricalSinkhornResult<'a, A> {
+    /// Source dual potential
+    pub alpha: Array1<A>,
+    /// Target dual potential
+    pub beta: Array1<A>,
+    source_samples: ArrayView2<'a, A>,
+    target_samples: ArrayView2<'a, A>,
+    source_weights: ArrayView1<'a, A>,
+    target_weights: ArrayView1<'a, A>,
+    metric: MetricType,
+    reg: A,
+    batch_size: usize,
+}
+
+impl<'a, A> EmpiricalSinkhornResult<'a, A>
+where
+    A: Float + ScalarOperand + Element,
+{
+    /// Entry (i, j) of the OT matrix
+    pub fn plan_entry(&self, i: usize, j: usize) -> A {
+        let cost = dist(
+            &self.source_samples.slice(s![i..i + 1, ..]),
+            &self.target_samples.slice(s![j..j + 1, ..]),
+            self.metric,
+        );
+
+        ((self.alpha[i] + self.beta[j] - cost[(0, 0)]) / self.reg).exp()
+    }
+
+    /// Row i of the OT matrix, the transport of the source sample i
+    pub fn plan_row(&self, i: usize) -> Array1<A> {
+        let cost = dist(
+            &self.source_samples.slice(s![i..i + 1, ..]),
+            &self.target_samples,
+            self.metric,
+        );
+
+        Zip::from(cost.row(0))
+            .and(&self.beta)
+            .map_collect(|&m, &beta| ((self.alpha[i] + beta - m) / self.reg).exp())
+    }
+
+    /// Entropic regularized optimal transport loss of the plan <G, M> + reg * KL(G | a b^T),
+    /// accumulated over blocks of the cost matrix
+    pub fn loss(&self) -> A {
+        let (a, b) = (self.source_weights, self.target_weights);
+        let (reg, batch_size) = (self.reg, self.batch_size);
+
+        let mut loss = A::zero();
+        for_each_block(
+            self.source_samples,
+            self.target_samples,
+            self.metric,
+            batch_size,
+            |rows, cols, cost| {
+                for ((i, j), &m) in cost.indexed_iter() {
+                    let (i, j) = (rows + i, cols + j);
+                    let log_g = (self.alpha[i] + self.beta[j] - m) / reg;
+                    let g = log_g.exp();
+                    let ab = a[i] * b[j];
+
+                    // Generalized KL term, with 0 * log(0) = 0
+                    let kl = if g > A::zero() {
+                        g * (log_g - ab.ln()) - g + ab
+                    } else {
+                        ab
+                    };
+
+                    loss = loss + g * m + reg * kl;
+                }
+            },
+        );
+
+        loss
+    }
+}
+
+/// Solves the entropic regularization optimal transport problem between two point clouds
+/// and returns the dual potentials, without storing the cost matrix or the OT matrix
+///
+/// The Sinkhorn iterations are performed on the log-domain potentials. Their log-sum-exp
+/// reductions recompute the cost matrix by blocks of batch_size x batch_size samples with the
+/// given metric, the memory footprint is O(n + m + batch_size^2) instead of O(n * m). The
+/// returned potentials evaluate entries and rows of the plan and the loss on demand.
+///
+/// ```rust
+/// use rust_optimal_transport as ot;
+/// use ot::prelude::*;
+/// use ndarray::prelude::*;
+///
+/// // Generate data
+/// let n = 200;
+///
+/// let cov = array![[1., 0.], [0., 1.]];
+/// let source = ot::utils::sample_2D_gauss(n, &array![0., 0.], &cov).unwrap();
+/// let target = ot::utils::sample_2D_gauss(n, &array![4., 4.], &cov).unwrap();
+///
+/// // Uniform weights on the source and target distributions
+/// let source_weights = Array1::<f64>::from_elem(n, 1. / (n as f64));
+/// let target_weights = Array1::<f64>::from_elem(n, 1. / (n as f64));
+///
+/// let result = match EmpiricalSinkhorn::new(
+///     &source,
+///     &target,
+///     &source_weights,
+///     &target_weights,
+///     SqEuclidean,
+///     1.,
+/// )
+/// .batch_size(64)
+/// .solve()
+/// {
+///     Ok(result) => result,
+///     Err(error) => panic!("{:?}", error),
+/// };
+///
+/// // Transport of the first source sample and the entropic loss
+/// let row = result.plan_row(0);
+/// let loss = result.loss();
+///
+/// ```
+///
+/// source_samples and target_samples are (n, d) and (m, d) point clouds, source_weights and
+/// target_weights their histograms.
+///
+pub struct EmpiricalSinkhorn<'a, A> {
+    source_samples: ArrayView2<'a, A>,
+    target_samples: ArrayView2<'a, A>,
+    source_weights: ArrayView1<'a, A>,
+    target_weights: ArrayView1<'a, A>,
+    metric: MetricType,
+    reg: A,
+    batch_size: usize,
+    iterations: i32,
+    threshold: A,
+    strict: bool,
+    log: SolverLog,
+}
+
+impl<'a, A> EmpiricalSinkhorn<'a, A>
+where
+    A: Float + ScalarOperand,
+{
+    pub fn new<S1, S2, S3, S4>(
+        source_samples: &'a ArrayBase<S1, Ix2>,
+        target_samples: &'a ArrayBase<S2, Ix2>,
+        source_weights: &'a ArrayBase<S3, Ix1>,
+        target_weights: &'a ArrayBase<S4, Ix1>,
+        metric: MetricType,
+        reg: A,
+    ) -> Self
+    where
+        S1: Data<Elem = A>,
+        S2: Data<Elem = A>,
+        S3: Data<Elem = A>,
+        S4: Data<Elem = A>,
+    {
+        Self {
+            source_samples: source_samples.view(),
+            target_samples: target_samples.view(),
+            source_weights: source_weights.view(),
+            target_weights: target_weights.view(),
+            metric,
+            reg,
+            batch_size: 512,
+            iterations: 1000,
+            threshold: A::from(1E-9).unwrap(),
+            strict: false,
+            log: SolverLog::default(),
+        }
+    }
+
+    pub fn iterations<'b>(&'b mut self, iterations: i32) -> &'b mut Self {
+        self.iterations = iterations;
+        self
+    }
+
+    pub fn threshold<'b>(&'b mut self, threshold: A) -> &'b mut Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Returns a ConvergenceError if the stop threshold is not reached within the max number
+    /// of iterations
+    pub fn strict<'b>(&'b mut self, strict: bool) -> &'b mut Self {
+        self.strict = strict;
+        self
+    }
+
+    pub fn reg<'b>(&'b mut self, reg: A) -> &'b mut Self {
+        self.reg = reg;
+        self
+    }
+
+    /// Number of source and target samples of the cost blocks, 512 by default
+    pub fn batch_size<'b>(&'b mut self, batch_size: usize) -> &'b mut Self {
+        self.batch_size = batch_size;
+        self
+    }
+}
+
+impl<'a, A> OTSolver for EmpiricalSinkhorn<'a, A>
+where
+    A: Float + ScalarOperand + Element,
+{
+    type Output = EmpiricalSinkhornResult<'a, A>;
+
+    /// Ensures the number of samples are consistent with the sample weights and that both
+    /// point clouds have the same number of features
+    fn check_shape(&self) -> Result<(), OTError> {
+        let dim_a = self.source_weights.len();
+        let dim_b = self.target_weights.len();
+        let n_source = self.source_samples.nrows();
+        let n_target = self.target_samples.nrows();
+
+        if dim_a != n_source || dim_b != n_target {
+            return Err(OTError::WeightDimensionError {
+                dim_a,
+                dim_b,
+                dim_m_0: n_source,
+                dim_m_1: n_target,
+            });
+        }
+
+        if self.source_samples.ncols() != self.target_samples.ncols() {
+            return Err(OTError::ArgError(
+                "Source and target samples have a different number of features".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn solve(&mut self) -> Result<EmpiricalSinkhornResult<'a, A>, OTError> {
+        self.check_shape()?;
+
+        if self.reg <= A::zero() {
+            return Err(OTError::ArgError("Regularization term <= 0".to_string()));
+        }
+
+        if self.iterations <= 0 {
+            return Err(OTError::ArgError(
+                "Iterations not a valid value. Must be > 0".to_string(),
+            ));
+        }
+
+        if self.batch_size == 0 {
+            return Err(OTError::ArgError("Batch size must be > 0".to_string()));
+        }
+
+        let (alpha, beta, log) = empirical_sinkhorn(
+            self.source_samples,
+            self.target_samples,
+            self.source_weights,
+            self.target_weights,
+            self.metric,
+            self.reg,
+            self.batch_size,
+            self.iterations,
+            self.threshold,
+        );
+
+        self.log = log;
+
+        if self.strict {
+            self.log.check_convergence(self.threshold)?;
+        }
+
+        Ok(EmpiricalSinkhornResult {
+            alpha,
+            beta,
+            source_samples: self.source_samples,
+            target_samples: self.target_samples,
+            source_weights: self.source_weights,
+            target_weights: self.target_weights,
+            metric: self.metric,
+            reg: self.reg,
+            batch_size: self.batch_size,
+        })
+    }
+
+    fn log(&self) -> &SolverLog {
+        &self.log
+    }
+}
+
+/// Calls f(first row, first column, cost block) on the blocks of batch_size x batch_size
+/// samples of the cost matrix between x and y
+fn for_each_block<A, F>(
+    x: ArrayView2<A>,
+    y: ArrayView2<A>,
+    metric: MetricType,
+    batch_size: usize,
+    mut f: F,
+) where
+    A: Float + ScalarOperand + Element,
+    F: FnMut(usize, usize, &Array2<A>),
+{
+    for rows in (0..x.nrows()).step_by(batch_size) {
+        let x_block = x.slice(s![rows..(rows + batch_size).min(x.nrows()), ..]);
+
+        for cols in (0..y.nrows()).step_by(batch_size) {
+            let y_block = y.slice(s![cols..(cols + batch_size).min(y.nrows()), ..]);
+            f(rows, cols, &dist(&x_block, &y_block, metric));
+        }
+    }
+}
+
+/// logsumexp(-M_ij/reg + potential_j) over j for each sample i of x, with M the cost matrix
+/// between x and y computed by blocks
+fn log_kernel_dot<A>(
+    x: ArrayView2<A>,
+    y: ArrayView2<A>,
+    potential: &Array1<A>,
+    metric: MetricType,
+    reg: A,
+    batch_size: usize,
+) -> Array1<A>
+where
+    A: Float + ScalarOperand + Element,
+{
+    // Running max and sum of exp(. - max) of each row
+    let mut max = Array1::from_elem(x.nrows(), A::neg_infinity());
+    let mut sum = Array1::<A>::zeros(x.nrows());
+
+    for_each_block(x, y, metric, batch_size, |rows, cols, cost| {
+        for (r, row) in cost.axis_iter(Axis(0)).enumerate() {
+            let values = row
+                .iter()
+                .zip(potential.slice(s![cols..]))
+                .map(|(&m, &p)| -m / reg + p);
+
+            let block_max = values.clone().fold(A::neg_infinity(), A::max);
+            let new_max = max[rows + r].max(block_max);
+
+            if new_max == A::neg_infinity() {
+                continue;
+            }
+
+            sum[rows + r] = sum[rows + r] * (max[rows + r] - new_max).exp()
+                + values.fold(A::zero(), |acc, v| acc + (v - new_max).exp());
+            max[rows + r] = new_max;
+        }
+    });
+
+    max + sum.mapv_into(A::ln)
+}
+
+/// Solves the entropic regularization optimal transport problem between two point clouds
+/// in the log-domain with a cost matrix computed by blocks and returns the dual potentials
+///
+/// xs: Source samples, (n, d)
+/// xt: Target samples, (m, d)
+/// a: Source sample weights
+/// b: Target sample weights
+/// metric: Metric of the cost between samples
+/// reg: Entropy regularization term > 0
+/// batch_size: Number of samples of the cost blocks
+/// iterations: Max number of iterations
+/// threshold: Stop threshold on the L1 violation of the target marginal
+#[allow(clippy::too_many_arguments)]
+fn empirical_sinkhorn<A>(
+    xs: ArrayView2<A>,
+    xt: ArrayView2<A>,
+    a: ArrayView1<A>,
+    b: ArrayView1<A>,
+    metric: MetricType,
+    reg: A,
+    batch_size: usize,
+    iterations: i32,
+    threshold: A,
+) -> (Array1<A>, Array1<A>, SolverLog)
+where
+    A: Float + ScalarOperand + Element,
+{
+    let mut log = SolverLog::default();
+
+    let loga = a.mapv(A::ln);
+    let logb = b.mapv(A::ln);
+
+    // Scaled dual potentials: f/reg and g/reg
+    let mut u = Array1::<A>::zeros(xs.nrows());
+    let mut v = Array1::<A>::zeros(xt.nrows());
+
+    for count in 0..iterations {
+        let v_prev = v.clone();
+
+        // v = log(b) - logsumexp(-M/reg + u[:, None], axis=0)
+        v = &logb - &log_kernel_dot(xt, xs, &u, metric, reg, batch_size);
+
+        // u = log(a) - logsumexp(-M/reg + v[None, :], axis=1)
+        u = &loga - &log_kernel_dot(xs, xt, &v, metric, reg, batch_size);
+
+        log.iterations = count + 1;
+
+        if count % 10 == 0 {
+            // Violation of the target marginal
+            let lse = log_kernel_dot(xt, xs, &u, metric, reg, batch_size);
+            let err = Zip::from(&lse)
+                .and(&v)
+                .and(&b)
+                .fold(A::zero(), |acc, &l, &v, &b| acc + ((l + v).exp() - b).abs());
+
+            log.push((&v - &v_prev).mapv(A::abs).sum(), err, err);
+
+            if err < threshold {
+                log.converged = true;
+                break;
+            }
+        }
+    }
+
+    (u * reg, v * reg, log)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use ndarray::prelude::*;
+
+    use crate::metrics::{dist, MetricType};
+    use crate::regularized::loss::entropic_loss;
+    use crate::regularized::sinkhorn::SinkhornLog;
+    use crate::OTSolver;
+
+    #[test]
+    fn test_empirical_sinkhorn() {
+        let n = 30;
+        let m = 20;
+        let xs = Array::range(0., n as f64, 1.)
+            .into_shape((n / 2, 2))
+            .unwrap()
+            / n as f64;
+        let xt = Array::range(0., m as f64, 1.)
+            .into_shape((m / 2, 2))
+            .unwrap()
+            / m as f64
+            + 0.2;
+
+        let a = Array1::<f64>::from_elem(n / 2, 2. / n as f64);
+        let b = Array1::<f64>::from_elem(m / 2, 2. / m as f64);
+        let reg = 1E-2;
+
+        let cost = dist(&xs, &xt, MetricType::Euclidean);
+        let truth = match SinkhornLog::new(&a, &b, &cost, reg).solve() {
+            Ok(result) => result,
+            Err(error) => panic!("{:?}", error),
+        };
+
+        // Blocks smaller than the number of samples, with remainders
+        let mut solver =
+            super::EmpiricalSinkhorn::new(&xs, &xt, &a, &b, MetricType::Euclidean, reg);
+        let result = match solver.batch_size(4).solve() {
+            Ok(result) => result,
+            Err(error) => panic!("{:?}", error),
+        };
+
+        assert!(solver.log().converged);
+
+        let plan = Array2::from_shape_fn(cost.dim(), |(i, j)| result.plan_entry(i, j));
+        assert!(plan.abs_diff_eq(&truth, 1E-8));
+        assert!(result.plan_row(3).abs_diff_eq(&truth.row(3), 1E-8));
+
+        let loss = entropic_loss(&truth, &a, &b, &cost, reg);
+        assert!((result.loss() - loss).abs() < 1E-8);
+
+        // Samples with a different number of features
+        let xt = Array2::<f64>::zeros((m / 2, 3));
+        assert!(
+            super::EmpiricalSinkhorn::new(&xs, &xt, &a, &b, MetricType::Euclidean, reg)
+                .solve()
+                .is_err()
+        );
+    }
+}
@@ -1,9 +1,11 @@
-use anyhow::anyhow;
 use ndarray::prelude::*;
-use ndarray_stats::QuantileExt;
+use ndarray::{Data, ScalarOperand};
+use num_traits::Float;
 
+use super::loss::entropic_loss;
+use super::sinkhorn::logsumexp;
 use crate::error::OTError;
-use crate::OTSolver;
+use crate::{OTSolver, SolverLog};
 
 /// Solves the entropic regularization optimal transport problem and return the OT matrix
 /// Uses the Greedy Sinkhorn method:
@@ -60,29 +62,43 @@ use crate::OTSolver;
 /// source_weights and target_weights represent histograms of the Source and Target distributions,
 /// respectively.
 ///
-pub struct Greenkhorn<'a> {
-    source_weights: &'a Array1<f64>,
-    target_weights: &'a Array1<f64>,
-    cost: &'a Array2<f64>,
-    reg: f64,
+pub struct Greenkhorn<'a, A> {
+    source_weights: ArrayView1<'a, A>,
+    target_weights: ArrayView1<'a, A>,
+    cost: ArrayView2<'a, A>,
+    reg: A,
     iterations: i32,
-    threshold: f64,
+    threshold: A,
+    log_domain: bool,
+    strict: bool,
+    log: SolverLog,
 }
 
-impl<'a> Greenkhorn<'a> {
-    pub fn new(
-        source_weights: &'a Array1<f64>,
-        target_weights: &'a Array1<f64>,
-        cost: &'a Array2<f64>,
-        reg: f64,
-    ) -> Self {
+impl<'a, A> Greenkhorn<'a, A>
+where
+    A: Float + ScalarOperand,
+{
+    pub fn new<S1, S2, S3>(
+        source_weights: &'a ArrayBase<S1, Ix1>,
+        target_weights: &'a ArrayBase<S2, Ix1>,
+        cost: &'a ArrayBase<S3, Ix2>,
+        reg: A,
+    ) -> Self
+    where
+        S1: Data<Elem = A>,
+        S2: Data<Elem = A>,
+        S3: Data<Elem = A>,
+    {
         Self {
-            source_weights,
-            target_weights,
-            cost,
+            source_weights: source_weights.view(),
+            target_weights: target_weights.view(),
+            cost: cost.view(),
             reg,
             iterations: 1000,
-            threshold: 1E-9,
+            threshold: A::from(1E-9).unwrap(),
+            log_domain: false,
+            strict: false,
+            log: SolverLog::default(),
         }
     }
 
@@ -91,18 +107,51 @@ impl<'a> Greenkhorn<'a> {
         self
     }
 
-    pub fn threshold<'b>(&'b mut self, threshold: f64) -> &'b mut Self {
+    pub fn threshold<'b>(&'b mut self, threshold: A) -> &'b mut Self {
         self.threshold = threshold;
         self
     }
 
-    pub fn reg<'b>(&'b mut self, reg: f64) -> &'b mut Self {
+    /// Returns a ConvergenceError if the stop threshold is not reached within the max number
+    /// of iterations
+    pub fn strict<'b>(&'b mut self, strict: bool) -> &'b mut Self {
+        self.strict = strict;
+        self
+    }
+
+    pub fn reg<'b>(&'b mut self, reg: A) -> &'b mut Self {
         self.reg = reg;
         self
     }
+
+    /// Performs the greedy updates on log-domain potentials, stable for small
+    /// regularization terms
+    pub fn log_domain<'b>(&'b mut self, log_domain: bool) -> &'b mut Self {
+        self.log_domain = log_domain;
+        self
+    }
+
+    /// Solves the entropic regularization optimal transport problem and returns the loss
+    /// <G, M> + reg * KL(G | a b^T)
+    pub fn loss(&mut self) -> Result<A, OTError> {
+        let plan = self.solve()?;
+
+        Ok(entropic_loss(
+            &plan,
+            &self.source_weights,
+            &self.target_weights,
+            &self.cost,
+            self.reg,
+        ))
+    }
 }
 
-impl<'a> OTSolver for Greenkhorn<'a> {
+impl<'a, A> OTSolver for Greenkhorn<'a, A>
+where
+    A: Float + ScalarOperand,
+{
+    type Output = Array2<A>;
+
     /// Ensures dimensions of the source and target measures are consistent with the
     /// cost matrix dimensions
     fn check_shape(&self) -> Result<(), OTError> {
@@ -125,10 +174,10 @@ impl<'a> OTSolver for Greenkhorn<'a> {
         Ok(())
     }
 
-    fn solve(&mut self) -> Result<Array2<f64>, OTError> {
+    fn solve(&mut self) -> Result<Array2<A>, OTError> {
         self.check_shape()?;
 
-        if self.reg <= 0. {
+        if self.reg <= A::zero() {
             return Err(OTError::ArgError("Regularization term <= 0".to_string()));
         }
 
@@ -138,138 +187,250 @@ impl<'a> OTSolver for Greenkhorn<'a> {
             ));
         }
 
-        greenkhorn(
-            self.source_weights,
-            self.target_weights,
-            self.cost,
-            self.reg,
-            self.iterations,
-            self.threshold,
-        )
+        let (plan, log) = if self.log_domain {
+            greenkhorn_log(
+                self.source_weights,
+                self.target_weights,
+                self.cost,
+                self.reg,
+                self.iterations,
+                self.threshold,
+            )
+        } else {
+            greenkhorn(
+                self.source_weights,
+                self.target_weights,
+                self.cost,
+                self.reg,
+                self.iterations,
+                self.threshold,
+            )
+        };
+
+        self.log = log;
+
+        if self.strict {
+            self.log.check_convergence(self.threshold)?;
+        }
+
+        Ok(plan)
+    }
+
+    fn log(&self) -> &SolverLog {
+        &self.log
     }
 }
 
+/// Index and absolute value of the largest absolute entry of x
+fn argmax_abs<A: Float>(x: &Array1<A>) -> (usize, A) {
+    x.iter()
+        .enumerate()
+        .fold((0, A::zero()), |(i_max, max), (i, &ele)| {
+            if ele.abs() > max {
+                (i, ele.abs())
+            } else {
+                (i_max, max)
+            }
+        })
+}
+
 /// Solves the entropic regularization optimal transport problem and return the OT matrix
 /// Uses the Greedy Sinkhorn method:
 /// Near-linear time approximation algorithms for optimal transport via Sinkhorn iteration
 /// by Jason Altschuler, Jonathan Weed, Philippe Rigollet
 ///
+/// Only the scalings u, v and the violations of the marginals are kept along the iterations,
+/// each update of a row or column of the plan diag(u) K diag(v) costs O(n + m).
+///
 /// a: Source sample weights (defaults to uniform weight if empty)
 /// b: Target sample weights (defaults to uniform weight if empty)
 /// M: Loss matrix
 /// reg: Entropy regularization term > 0
 /// num_iter_max: Max number of iterations (default = 1000)
 /// stop_threshold: Stop threshold on error (> 0) (default = 1E-6)
-fn greenkhorn(
-    a: &Array1<f64>,
-    b: &Array1<f64>,
-    M: &Array2<f64>,
-    reg: f64,
+fn greenkhorn<A>(
+    a: ArrayView1<A>,
+    b: ArrayView1<A>,
+    M: ArrayView2<A>,
+    reg: A,
     iterations: i32,
-    threshold: f64,
-) -> Result<Array2<f64>, OTError> {
+    threshold: A,
+) -> (Array2<A>, SolverLog)
+where
+    A: Float + ScalarOperand,
+{
+    let mut log = SolverLog::default();
     let dim_a = a.len();
     let dim_b = b.len();
-    let mut stop_val;
 
-    let mut u = Array1::<f64>::from_vec(vec![1f64 / (dim_a as f64); dim_a]);
-    let mut v = Array1::<f64>::from_vec(vec![1f64 / (dim_b as f64); dim_b]);
+    let mut u = Array1::<A>::from_elem(dim_a, A::one() / A::from(dim_a).unwrap());
+    let mut v = Array1::<A>::from_elem(dim_b, A::one() / A::from(dim_b).unwrap());
 
     // K = exp(-M/reg)
-    let f = |ele: f64| (-ele / reg).exp();
-    let k = M.clone().mapv_into(f);
-
-    let mut G = &u.diag().t() * &k * &v.diag();
-    let mut viol = &G.sum_axis(Axis(1)) - a;
-    let mut viol_2 = &G.sum_axis(Axis(0)) - b;
-
-    for _ in 0..iterations {
-        // Absolute values
-        let viol_abs: Array1<f64> = viol.iter().map(|x| x.abs()).collect();
-        let viol_2_abs: Array1<f64> = viol_2.iter().map(|x| x.abs()).collect();
-
-        // Argmax
-        let i_1 = match viol_abs.argmax() {
-            Ok(val) => val,
-            // Propagate ndarray-stats error
-            Err(err) => return Err(OTError::Other(anyhow!(err))),
-        };
+    let f = |ele: A| (-ele / reg).exp();
+    let k = M.mapv(f);
 
-        let i_2 = match viol_2_abs.argmax() {
-            Ok(val) => val,
-            // Propagate ndarray-stats error
-            Err(err) => return Err(OTError::Other(anyhow!(err))),
-        };
+    // Marginal violations of the plan diag(u) K diag(v)
+    let mut viol = k.dot(&v) * &u - a;
+    let mut viol_2 = k.t().dot(&u) * &v - b;
+    let mut v_prev = v.clone();
 
-        // Max value
-        let m_viol_1 = viol_abs[i_1];
-        let m_viol_2 = viol_2_abs[i_2];
-
-        if m_viol_1 >= m_viol_2 {
-            stop_val = m_viol_1;
-        } else {
-            stop_val = m_viol_2;
-        }
+    for count in 0..iterations {
+        let (i_1, m_viol_1) = argmax_abs(&viol);
+        let (i_2, m_viol_2) = argmax_abs(&viol_2);
+        let stop_val = m_viol_1.max(m_viol_2);
 
         if m_viol_1 > m_viol_2 {
             let old_u = u[i_1];
-            let k_i1 = &k.row(i_1);
+            let k_i1 = k.row(i_1);
             let denom = k_i1.dot(&v);
             let new_u = a[i_1] / denom;
 
-            // G[i_1, :] = new_u * k[i_1, :] * v
-            for (i, mut row) in G.axis_iter_mut(Axis(0)).enumerate() {
-                if i != i_1 {
-                    continue;
-                }
-
-                for (j, g) in row.iter_mut().enumerate() {
-                    *g = new_u * k[(i_1, j)] * v[j];
-                }
-            }
+            viol[i_1] = denom * new_u - a[i_1];
 
-            // let tmp = k_i1.iter().map(|x| new_u*x).collect();
-            viol[i_1] = (k_i1.dot(&v) * new_u) - a[i_1];
-
-            // viol_2 += (K[i_1, :].T * (u[i_1] - old_u) * v)
-            for (j, ele) in viol_2.iter_mut().enumerate() {
-                *ele += k_i1.t()[j] * (new_u - old_u) * v[j];
-            }
+            // viol_2 += K[i_1, :] * (new_u - old_u) * v
+            azip!((viol_2 in &mut viol_2, &k in &k_i1, &v in &v) *viol_2 = *viol_2 + k * (new_u - old_u) * v);
 
             u[i_1] = new_u;
         } else {
             let old_v = v[i_2];
-            let k_i2 = &k.column(i_2);
+            let k_i2 = k.column(i_2);
             let denom = k_i2.dot(&u);
             let new_v = b[i_2] / denom;
 
-            // G[:, i_2] = u * k[:, i_2] * new_v
-            for (i, mut col) in G.axis_iter_mut(Axis(1)).enumerate() {
-                if i != i_2 {
-                    continue;
-                }
+            // viol += K[:, i_2] * (new_v - old_v) * u
+            azip!((viol in &mut viol, &k in &k_i2, &u in &u) *viol = *viol + k * (new_v - old_v) * u);
 
-                for (j, g) in col.iter_mut().enumerate() {
-                    *g = u[j] * k[(j, i_2)] * new_v;
-                }
-            }
+            viol_2[i_2] = denom * new_v - b[i_2];
 
-            // viol += (-old_v + v[i_2]) * K[:, i_2] * u
-            for (j, ele) in viol.iter_mut().enumerate() {
-                *ele += (-old_v + new_v) * k_i2[j] * u[j];
-            }
+            v[i_2] = new_v;
+        }
 
-            viol_2[i_2] = new_v * k_i2.dot(&u) - b[i_2];
+        log.iterations = count + 1;
+        log.error = stop_val.to_f64().unwrap();
 
-            v[i_2] = new_v;
+        if count % 10 == 0 {
+            let v_change = (&v - &v_prev).mapv(A::abs).sum();
+            let err = viol.mapv(A::abs).sum() + viol_2.mapv(A::abs).sum();
+            log.push(v_change, err, stop_val);
+            v_prev = v.clone();
         }
 
         if stop_val <= threshold {
+            log.converged = true;
             break;
         }
     }
 
-    Ok(G)
+    // G = diag(u) K diag(v)
+    let plan = &u.insert_axis(Axis(1)) * &k * &v.insert_axis(Axis(0));
+
+    (plan, log)
+}
+
+/// Solves the entropic regularization optimal transport problem with the Greedy Sinkhorn
+/// method in the log-domain and return the OT matrix
+///
+/// The scalings are kept as the scaled dual potentials f = log(u) and g = log(v), the kernel
+/// exp(-M/reg) is never formed so that small regularization terms do not underflow. Updates
+/// of a row or column cost O(n + m).
+///
+/// a: Source sample weights
+/// b: Target sample weights
+/// M: Loss matrix
+/// reg: Entropy regularization term > 0
+/// iterations: Max number of iterations
+/// threshold: Stop threshold on the largest marginal violation
+#[allow(non_snake_case)]
+fn greenkhorn_log<A>(
+    a: ArrayView1<A>,
+    b: ArrayView1<A>,
+    M: ArrayView2<A>,
+    reg: A,
+    iterations: i32,
+    threshold: A,
+) -> (Array2<A>, SolverLog)
+where
+    A: Float + ScalarOperand,
+{
+    let mut log = SolverLog::default();
+    let dim_a = a.len();
+    let dim_b = b.len();
+
+    let mut f = Array1::<A>::from_elem(dim_a, -A::from(dim_a).unwrap().ln());
+    let mut g = Array1::<A>::from_elem(dim_b, -A::from(dim_b).unwrap().ln());
+
+    // Marginal violations of the plan exp(f_i - M_ij/reg + g_j)
+    let mut viol =
+        Array1::from_iter(M.axis_iter(Axis(0)).zip(&f).zip(&a).map(|((row, &f), &a)| {
+            logsumexp(row.iter().zip(&g).map(|(&m, &g)| f - m / reg + g)).exp() - a
+        }));
+    let mut viol_2 =
+        Array1::from_iter(M.axis_iter(Axis(1)).zip(&g).zip(&b).map(|((col, &g), &b)| {
+            logsumexp(col.iter().zip(&f).map(|(&m, &f)| f - m / reg + g)).exp() - b
+        }));
+    let mut g_prev = g.clone();
+
+    for count in 0..iterations {
+        let (i_1, m_viol_1) = argmax_abs(&viol);
+        let (i_2, m_viol_2) = argmax_abs(&viol_2);
+        let stop_val = m_viol_1.max(m_viol_2);
+
+        if m_viol_1 > m_viol_2 {
+            let old_f = f[i_1];
+            let m_i1 = M.row(i_1);
+            let new_f = a[i_1].ln() - logsumexp(m_i1.iter().zip(&g).map(|(&m, &g)| g - m / reg));
+
+            // Row sum and column violations from the old and new entries of the row
+            let mut row_sum = A::zero();
+            azip!((viol_2 in &mut viol_2, &m in &m_i1, &g in &g) {
+                let entry = (new_f - m / reg + g).exp();
+                row_sum = row_sum + entry;
+                *viol_2 = *viol_2 + entry - (old_f - m / reg + g).exp();
+            });
+
+            viol[i_1] = row_sum - a[i_1];
+            f[i_1] = new_f;
+        } else {
+            let old_g = g[i_2];
+            let m_i2 = M.column(i_2);
+            let new_g = b[i_2].ln() - logsumexp(m_i2.iter().zip(&f).map(|(&m, &f)| f - m / reg));
+
+            // Column sum and row violations from the old and new entries of the column
+            let mut col_sum = A::zero();
+            azip!((viol in &mut viol, &m in &m_i2, &f in &f) {
+                let entry = (f - m / reg + new_g).exp();
+                col_sum = col_sum + entry;
+                *viol = *viol + entry - (f - m / reg + old_g).exp();
+            });
+
+            viol_2[i_2] = col_sum - b[i_2];
+            g[i_2] = new_g;
+        }
+
+        log.iterations = count + 1;
+        log.error = stop_val.to_f64().unwrap();
+
+        if count % 10 == 0 {
+            let v_change = (&g.mapv(A::exp) - &g_prev.mapv(A::exp)).mapv(A::abs).sum();
+            let err = viol.mapv(A::abs).sum() + viol_2.mapv(A::abs).sum();
+            log.push(v_change, err, stop_val);
+            g_prev = g.clone();
+        }
+
+        if stop_val <= threshold {
+            log.converged = true;
+            break;
+        }
+    }
+
+    // G = exp(f[:, None] - M/reg + g[None, :])
+    let mut plan = M.mapv(|ele| -ele / reg);
+    for ((i, j), ele) in plan.indexed_iter_mut() {
+        *ele = (*ele + f[i] + g[j]).exp();
+    }
+
+    (plan, log)
 }
 
 #[cfg(test)]
@@ -285,10 +446,7 @@ mod tests {
         let reg = 1.0;
         let m = array![[0.0, 1.0], [1.0, 0.0]];
 
-        let result = match super::greenkhorn(&a, &b, &m, reg, 1000, 1E-9) {
-            Ok(result) => result,
-            Err(error) => panic!("{:?}", error),
-        };
+        let (result, _log) = super::greenkhorn(a.view(), b.view(), m.view(), reg, 1000, 1E-9);
 
         println!("{:?}", result);
 
@@ -315,4 +473,51 @@ mod tests {
 
         assert!(result.relative_eq(&truth, 1E-6, 1E-2));
     }
+
+    #[test]
+    fn test_greenkhorn_log() {
+        let n = 20;
+        let x = Array::range(0., n as f64, 1.).into_shape((n, 1)).unwrap();
+        let y = Array::range(0.5, n as f64, 1.).into_shape((n, 1)).unwrap();
+        let m = crate::metrics::dist(&x, &y, crate::metrics::MetricType::SqEuclidean);
+        let m = &m / (n * n) as f64;
+
+        let a = Array1::<f64>::from_elem(n, 1. / n as f64);
+        let b = crate::utils::get_1D_gauss_histogram(n, 10., 4.).unwrap();
+
+        // Same plan as Sinkhorn-Knopp, with and without log-domain updates
+        let truth = match crate::regularized::sinkhorn::SinkhornKnopp::new(&a, &b, &m, 1E-1)
+            .threshold(1E-12)
+            .solve()
+        {
+            Ok(result) => result,
+            Err(error) => panic!("{:?}", error),
+        };
+
+        for log_domain in [false, true] {
+            let mut solver = super::Greenkhorn::new(&a, &b, &m, 1E-1);
+            let plan = match solver.log_domain(log_domain).iterations(100000).solve() {
+                Ok(result) => result,
+                Err(error) => panic!("{:?}", error),
+            };
+
+            assert!(solver.log().converged);
+            assert!(plan.abs_diff_eq(&truth, 1E-8));
+        }
+
+        // exp(-M/reg) underflows for small regularization terms, the marginals are only
+        // approximately met within the iterations budget
+        let plan = match super::Greenkhorn::new(&a, &b, &m, 1E-4)
+            .log_domain(true)
+            .iterations(100000)
+            .solve()
+        {
+            Ok(result) => result,
+            Err(error) => panic!("{:?}", error),
+        };
+
+        assert!(!crate::ndarray_logical::is_nan(&plan));
+        assert!(plan.sum_axis(Axis(1)).abs_diff_eq(&a, 1E-4));
+        assert!(plan.sum_axis(Axis(0)).abs_diff_eq(&b, 1E-4));
+    }
 }
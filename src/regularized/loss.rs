@@ -0,0 +1,314 @@
+use ndarray::prelude::*;
+use ndarray::{Data, ScalarOperand};
+use num_traits::Float;
+
+use super::sinkhorn::SinkhornKnopp;
+use crate::error::OTError;
+use crate::metrics::{dist, MetricType};
+use crate::parallel::Element;
+use crate::{OTSolver, SolverLog};
+
+/// Generalized Kullback-Leibler divergence between two non-negative arrays
+/// KL(p|q) = sum(p * log(p/q) - p + q), with the convention 0 * log(0) = 0
+pub fn kl_divergence<A, S1, S2, D>(p: &ArrayBase<S1, D>, q: &ArrayBase<S2, D>) -> A
+where
+    A: Float,
+    S1: Data<Elem = A>,
+    S2: Data<Elem = A>,
+    D: Dimension,
+{
+    p.iter().zip(q.iter()).fold(A::zero(), |acc, (&p, &q)| {
+        if p > A::zero() {
+            acc + p * (p / q).ln() - p + q
+        } else {
+            acc + q
+        }
+    })
+}
+
+/// Returns the entropic regularized optimal transport loss of a plan
+/// <G, M> + reg * KL(G | a b^T)
+///
+/// plan: OT matrix
+/// a: Source sample weights
+/// b: Target sample weights
+/// M: Loss matrix
+/// reg: Entropy regularization term > 0
+#[allow(non_snake_case)]
+pub fn entropic_loss<A, S1, S2, S3, S4>(
+    plan: &ArrayBase<S1, Ix2>,
+    a: &ArrayBase<S2, Ix1>,
+    b: &ArrayBase<S3, Ix1>,
+    M: &ArrayBase<S4, Ix2>,
+    reg: A,
+) -> A
+where
+    A: Float,
+    S1: Data<Elem = A>,
+    S2: Data<Elem = A>,
+    S3: Data<Elem = A>,
+    S4: Data<Elem = A>,
+{
+    let mut loss = A::zero();
+
+    for ((i, j), &g) in plan.indexed_iter() {
+        // KL(G | a b^T)
+        let ab = a[i] * b[j];
+        let kl = if g > A::zero() {
+            g * (g / ab).ln() - g + ab
+        } else {
+            ab
+        };
+
+        loss = loss + g * M[(i, j)] + reg * kl;
+    }
+
+    loss
+}
+
+/// Computes the debiased Sinkhorn divergence between two weighted point clouds
+///
+/// S(a, b) = OT(a, b) - OT(a, a) / 2 - OT(b, b) / 2
+///
+/// where OT is the entropic regularized optimal transport loss <G, M> + reg * KL(G | a b^T).
+/// The divergence is non-negative and zero when both point clouds are identical.
+///
+/// Interpolating between Optimal Transport and MMD using Sinkhorn Divergences
+/// by Jean Feydy, Thibault Séjourné, François-Xavier Vialard, Shun-ichi Amari, Alain Trouvé,
+/// Gabriel Peyré
+///
+/// ```rust
+/// use rust_optimal_transport as ot;
+/// use ot::prelude::*;
+/// use ndarray::prelude::*;
+///
+/// let source = array![[0., 0.], [1., 0.], [0., 1.]];
+/// let target = array![[2., 2.], [3., 2.]];
+///
+/// let source_weights = Array1::<f64>::from_elem(3, 1. / 3.);
+/// let target_weights = Array1::<f64>::from_elem(2, 1. / 2.);
+///
+/// let divergence = match SinkhornDivergence::new(
+///     &source,
+///     &target,
+///     &source_weights,
+///     &target_weights,
+///     SqEuclidean,
+///     1.,
+/// ).solve() {
+///     Ok(result) => result,
+///     Err(error) => panic!("{:?}", error),
+/// };
+///
+/// assert!(divergence > 0.);
+///
+/// ```
+///
+pub struct SinkhornDivergence<'a, A> {
+    source_samples: ArrayView2<'a, A>,
+    target_samples: ArrayView2<'a, A>,
+    source_weights: ArrayView1<'a, A>,
+    target_weights: ArrayView1<'a, A>,
+    metric: MetricType,
+    reg: A,
+    iterations: i32,
+    threshold: A,
+    strict: bool,
+    log: SolverLog,
+}
+
+impl<'a, A> SinkhornDivergence<'a, A>
+where
+    A: Float + ScalarOperand + Element,
+{
+    pub fn new<S1, S2, S3, S4>(
+        source_samples: &'a ArrayBase<S1, Ix2>,
+        target_samples: &'a ArrayBase<S2, Ix2>,
+        source_weights: &'a ArrayBase<S3, Ix1>,
+        target_weights: &'a ArrayBase<S4, Ix1>,
+        metric: MetricType,
+        reg: A,
+    ) -> Self
+    where
+        S1: Data<Elem = A>,
+        S2: Data<Elem = A>,
+        S3: Data<Elem = A>,
+        S4: Data<Elem = A>,
+    {
+        Self {
+            source_samples: source_samples.view(),
+            target_samples: target_samples.view(),
+            source_weights: source_weights.view(),
+            target_weights: target_weights.view(),
+            metric,
+            reg,
+            iterations: 1000,
+            threshold: A::from(1E-9).unwrap(),
+            strict: false,
+            log: SolverLog::default(),
+        }
+    }
+
+    pub fn iterations<'b>(&'b mut self, iterations: i32) -> &'b mut Self {
+        self.iterations = iterations;
+        self
+    }
+
+    pub fn threshold<'b>(&'b mut self, threshold: A) -> &'b mut Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Returns a ConvergenceError if the stop threshold is not reached within the max number
+    /// of iterations
+    pub fn strict<'b>(&'b mut self, strict: bool) -> &'b mut Self {
+        self.strict = strict;
+        self
+    }
+
+    pub fn reg<'b>(&'b mut self, reg: A) -> &'b mut Self {
+        self.reg = reg;
+        self
+    }
+
+    /// Entropic OT loss between two weighted point clouds
+    fn ot_loss(
+        &self,
+        x1: ArrayView2<A>,
+        x2: ArrayView2<A>,
+        a: ArrayView1<A>,
+        b: ArrayView1<A>,
+    ) -> Result<(A, SolverLog), OTError> {
+        let cost = dist(&x1, &x2, self.metric);
+
+        let mut solver = SinkhornKnopp::new(&a, &b, &cost, self.reg);
+        solver
+            .iterations(self.iterations)
+            .threshold(self.threshold)
+            .strict(self.strict);
+
+        let loss = solver.loss()?;
+
+        Ok((loss, solver.log().clone()))
+    }
+}
+
+impl<'a, A> OTSolver for SinkhornDivergence<'a, A>
+where
+    A: Float + ScalarOperand + Element,
+{
+    type Output = A;
+
+    /// Ensures the number of samples are consistent with the sample weights and that both
+    /// point clouds have the same number of features
+    fn check_shape(&self) -> Result<(), OTError> {
+        let dim_a = self.source_weights.len();
+        let dim_b = self.target_weights.len();
+        let n_source = self.source_samples.nrows();
+        let n_target = self.target_samples.nrows();
+
+        if dim_a != n_source || dim_b != n_target {
+            return Err(OTError::WeightDimensionError {
+                dim_a,
+                dim_b,
+                dim_m_0: n_source,
+                dim_m_1: n_target,
+            });
+        }
+
+        if self.source_samples.ncols() != self.target_samples.ncols() {
+            return Err(OTError::ArgError(
+                "Source and target samples have a different number of features".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn solve(&mut self) -> Result<A, OTError> {
+        self.check_shape()?;
+
+        let (xs, xt) = (self.source_samples, self.target_samples);
+        let (a, b) = (self.source_weights, self.target_weights);
+
+        let (ot_ab, log_ab) = self.ot_loss(xs, xt, a, b)?;
+        let (ot_aa, log_aa) = self.ot_loss(xs, xs, a, a)?;
+        let (ot_bb, log_bb) = self.ot_loss(xt, xt, b, b)?;
+
+        // Log of the cross term, converged only if all three problems converged
+        self.log = log_ab;
+        self.log.converged &= log_aa.converged && log_bb.converged;
+
+        Ok(ot_ab - (ot_aa + ot_bb) / A::from(2.).unwrap())
+    }
+
+    fn log(&self) -> &SolverLog {
+        &self.log
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use ndarray::prelude::*;
+
+    use crate::metrics::MetricType;
+    use crate::OTSolver;
+
+    #[test]
+    fn test_entropic_loss() {
+        let a = array![0.5, 0.5];
+        let b = array![0.5, 0.5];
+        let reg = 1.0;
+        let m = array![[0.0, 1.0], [1.0, 0.0]];
+        let plan = array![
+            [0.36552928931500245, 0.13447071068499755],
+            [0.13447071068499755, 0.36552928931500245]
+        ];
+
+        let loss: f64 = super::entropic_loss(&plan, &a, &b, &m, reg);
+
+        assert!((loss - 0.3798854930417225).abs() < 1E-12);
+    }
+
+    #[test]
+    fn test_sinkhorn_divergence() {
+        let source = array![[0., 0.], [1., 0.], [0., 1.]];
+        let target = array![[2., 2.], [3., 2.]];
+        let a = Array1::<f64>::from_elem(3, 1. / 3.);
+        let b = Array1::<f64>::from_elem(2, 1. / 2.);
+
+        let divergence = match super::SinkhornDivergence::new(
+            &source,
+            &target,
+            &a,
+            &b,
+            MetricType::SqEuclidean,
+            1.,
+        )
+        .solve()
+        {
+            Ok(result) => result,
+            Err(error) => panic!("{:?}", error),
+        };
+
+        assert!(divergence > 0.);
+
+        // Divergence between identical point clouds vanishes
+        let divergence = match super::SinkhornDivergence::new(
+            &source,
+            &source,
+            &a,
+            &a,
+            MetricType::SqEuclidean,
+            1.,
+        )
+        .solve()
+        {
+            Ok(result) => result,
+            Err(error) => panic!("{:?}", error),
+        };
+
+        assert!(divergence.abs() < 1E-9);
+    }
+}
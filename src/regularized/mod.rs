@@ -1,2 +1,8 @@
+pub mod barycenter;
+pub mod empirical;
 pub mod greenkhorn;
+pub mod loss;
+pub mod partial;
+pub mod screenkhorn;
 pub mod sinkhorn;
+pub mod sinkhorn_stabilized;
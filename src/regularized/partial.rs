@@ -0,0 +1,366 @@
+use ndarray::prelude::*;
+use ndarray::{Data, ScalarOperand, Zip};
+use num_traits::Float;
+
+use crate::error::OTError;
+use crate::exact::partial::check_mass;
+use crate::{OTSolver, SolverLog};
+
+/// Solves the entropic regularized partial Optimal Transport problem, moving only a total mass
+/// m between the source and target distributions, and returns the OT matrix
+///
+/// The plan is the KL projection of the Sinkhorn kernel K = exp(-M/reg) on the intersection
+/// of the constraints sum(G, axis=1) <= a, sum(G, axis=0) <= b and sum(G) = m, computed with
+/// Dykstra's algorithm.
+///
+/// Iterative Bregman Projections for Regularized Transportation Problems
+/// by Jean-David Benamou, Guillaume Carlier, Marco Cuturi, Luca Nenna, Gabriel Peyré
+///
+/// ```rust
+/// use rust_optimal_transport as ot;
+/// use ot::prelude::*;
+/// use ndarray::prelude::*;
+/// use ndarray_stats::QuantileExt;
+///
+/// // The last sample of each distribution is an outlier
+/// let source = array![[0., 0.], [1., 0.], [10., 10.]];
+/// let target = array![[0., 0.1], [1., 0.1], [-10., -10.]];
+///
+/// let source_weights = Array1::<f64>::from_elem(3, 1. / 3.);
+/// let target_weights = Array1::<f64>::from_elem(3, 1. / 3.);
+///
+/// // Normalize cost matrix for numerical stability
+/// let mut cost = dist(&source, &target, SqEuclidean);
+/// cost = &cost / *cost.max().unwrap();
+///
+/// // Only transport two thirds of the mass
+/// let ot_matrix = match SinkhornPartial::new(
+///     &source_weights,
+///     &target_weights,
+///     &cost,
+///     1E-2,
+///     2. / 3.,
+/// ).solve() {
+///     Ok(result) => result,
+///     Err(error) => panic!("{:?}", error),
+/// };
+///
+/// assert!((ot_matrix.sum() - 2. / 3.).abs() < 1E-6);
+///
+/// ```
+///
+/// source_weights and target_weights represent histograms of the Source and Target distributions,
+/// respectively. Their total masses may differ, the transported mass m must be in
+/// [0, min(sum(a), sum(b))].
+///
+pub struct SinkhornPartial<'a, A> {
+    source_weights: ArrayView1<'a, A>,
+    target_weights: ArrayView1<'a, A>,
+    cost: ArrayView2<'a, A>,
+    reg: A,
+    mass: A,
+    iterations: i32,
+    threshold: A,
+    strict: bool,
+    log: SolverLog,
+}
+
+impl<'a, A> SinkhornPartial<'a, A>
+where
+    A: Float + ScalarOperand,
+{
+    pub fn new<S1, S2, S3>(
+        source_weights: &'a ArrayBase<S1, Ix1>,
+        target_weights: &'a ArrayBase<S2, Ix1>,
+        cost: &'a ArrayBase<S3, Ix2>,
+        reg: A,
+        mass: A,
+    ) -> Self
+    where
+        S1: Data<Elem = A>,
+        S2: Data<Elem = A>,
+        S3: Data<Elem = A>,
+    {
+        Self {
+            source_weights: source_weights.view(),
+            target_weights: target_weights.view(),
+            cost: cost.view(),
+            reg,
+            mass,
+            iterations: 1000,
+            threshold: A::from(1E-9).unwrap(),
+            strict: false,
+            log: SolverLog::default(),
+        }
+    }
+
+    pub fn iterations<'b>(&'b mut self, iterations: i32) -> &'b mut Self {
+        self.iterations = iterations;
+        self
+    }
+
+    pub fn threshold<'b>(&'b mut self, threshold: A) -> &'b mut Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Returns a ConvergenceError if the stop threshold is not reached within the max number
+    /// of iterations
+    pub fn strict<'b>(&'b mut self, strict: bool) -> &'b mut Self {
+        self.strict = strict;
+        self
+    }
+
+    pub fn reg<'b>(&'b mut self, reg: A) -> &'b mut Self {
+        self.reg = reg;
+        self
+    }
+
+    /// Total mass to transport
+    pub fn mass<'b>(&'b mut self, mass: A) -> &'b mut Self {
+        self.mass = mass;
+        self
+    }
+}
+
+impl<'a, A> OTSolver for SinkhornPartial<'a, A>
+where
+    A: Float + ScalarOperand,
+{
+    type Output = Array2<A>;
+
+    /// Ensures dimensions of the source and target measures are consistent with the
+    /// cost matrix dimensions
+    fn check_shape(&self) -> Result<(), OTError> {
+        let mshape = self.cost.shape();
+        let m0 = mshape[0];
+        let m1 = mshape[1];
+        let dim_a = self.source_weights.len();
+        let dim_b = self.target_weights.len();
+
+        if dim_a != m0 || dim_b != m1 {
+            return Err(OTError::WeightDimensionError {
+                dim_a,
+                dim_b,
+                dim_m_0: m0,
+                dim_m_1: m1,
+            });
+        }
+
+        Ok(())
+    }
+
+    fn solve(&mut self) -> Result<Array2<A>, OTError> {
+        self.check_shape()?;
+
+        if self.reg <= A::zero() {
+            return Err(OTError::ArgError("Regularization term <= 0".to_string()));
+        }
+
+        if self.iterations <= 0 {
+            return Err(OTError::ArgError(
+                "Iterations not a valid value. Must be > 0".to_string(),
+            ));
+        }
+
+        let mass = check_mass(self.source_weights, self.target_weights, self.mass)?;
+
+        let (plan, log) = sinkhorn_partial(
+            self.source_weights,
+            self.target_weights,
+            self.cost,
+            self.reg,
+            mass,
+            self.iterations,
+            self.threshold,
+        );
+
+        self.log = log;
+
+        if self.strict {
+            self.log.check_convergence(self.threshold)?;
+        }
+
+        Ok(plan)
+    }
+
+    fn log(&self) -> &SolverLog {
+        &self.log
+    }
+}
+
+/// Dykstra's algorithm alternating the KL projections on the source capacity, target capacity
+/// and total mass constraints
+///
+/// a: Source sample weights, capacities of the source samples
+/// b: Target sample weights, capacities of the target samples
+/// M: Loss matrix
+/// reg: Entropy regularization term > 0
+/// mass: Total transported mass
+/// iterations: Max number of iterations
+/// threshold: Stop threshold on the L1 change of the plan
+#[allow(non_snake_case)]
+fn sinkhorn_partial<A>(
+    a: ArrayView1<A>,
+    b: ArrayView1<A>,
+    M: ArrayView2<A>,
+    reg: A,
+    mass: A,
+    iterations: i32,
+    threshold: A,
+) -> (Array2<A>, SolverLog)
+where
+    A: Float + ScalarOperand,
+{
+    let mut log = SolverLog::default();
+
+    // Nothing to transport, the mass constraint alone gives the zero plan
+    if mass == A::zero() {
+        log.converged = true;
+        return (Array2::<A>::zeros(M.dim()), log);
+    }
+
+    // K = exp(-M/reg), scaled to the transported mass
+    let mut k = M.mapv(|ele| (-ele / reg).exp());
+    let total = k.sum();
+    k.mapv_inplace(|ele| ele * mass / total);
+
+    // Dykstra corrections of each constraint set
+    let mut q1 = Array2::<A>::ones(k.dim());
+    let mut q2 = Array2::<A>::ones(k.dim());
+    let mut q3 = Array2::<A>::ones(k.dim());
+
+    // Scales G along an axis by min(capacity / marginal, 1), empty marginals are left as is
+    let project = |g: &Array2<A>, capacity: ArrayView1<A>, axis: Axis| {
+        let scale = Zip::from(&capacity)
+            .and(&g.sum_axis(axis))
+            .map_collect(|&c, &s| {
+                if s > A::zero() {
+                    A::min(c / s, A::one())
+                } else {
+                    A::one()
+                }
+            });
+        let shape = if axis == Axis(1) {
+            (scale.len(), 1)
+        } else {
+            (1, scale.len())
+        };
+        g * &scale.into_shape(shape).unwrap()
+    };
+
+    // Dykstra correction q * K_prev / K_next, unchanged where the projection vanishes, as on
+    // the samples with zero weight
+    let correct = |q: &Array2<A>, k_prev: &Array2<A>, k_next: &Array2<A>| {
+        Zip::from(q)
+            .and(k_prev)
+            .and(k_next)
+            .map_collect(|&q, &kp, &kn| if kn > A::zero() { q * kp / kn } else { q })
+    };
+
+    for count in 0..iterations {
+        let k_prev = k.clone();
+
+        // Source capacities
+        let g = &k * &q1;
+        let k1 = project(&g, a, Axis(1));
+        q1 = correct(&q1, &k, &k1);
+
+        // Target capacities
+        let g = &k1 * &q2;
+        let k2 = project(&g, b, Axis(0));
+        q2 = correct(&q2, &k1, &k2);
+
+        // Total transported mass
+        let g = &k2 * &q3;
+        let total = g.sum();
+        k = g.mapv(|ele| ele * mass / total);
+        q3 = correct(&q3, &k2, &k);
+
+        log.iterations = count + 1;
+
+        if count % 10 == 0 {
+            let err = (&k - &k_prev).mapv(A::abs).sum();
+
+            // Excess mass over the capacities
+            let excess = |marginal: Array1<A>, capacity: ArrayView1<A>| {
+                Zip::from(&marginal)
+                    .and(&capacity)
+                    .fold(A::zero(), |acc, &m, &c| acc + A::max(m - c, A::zero()))
+            };
+            let viol = excess(k.sum_axis(Axis(1)), a) + excess(k.sum_axis(Axis(0)), b);
+
+            log.push(err, viol, err);
+
+            if err < threshold {
+                log.converged = true;
+                break;
+            }
+        }
+    }
+
+    (k, log)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use ndarray::prelude::*;
+
+    use crate::metrics::{dist, MetricType};
+    use crate::OTSolver;
+
+    #[test]
+    fn test_sinkhorn_partial() {
+        let source = array![[0., 0.], [1., 0.], [10., 10.]];
+        let target = array![[0., 0.1], [1., 0.1], [-10., -10.]];
+
+        let a = Array1::<f64>::from_elem(3, 1. / 3.);
+        let b = Array1::<f64>::from_elem(3, 1. / 3.);
+        let m = dist(&source, &target, MetricType::SqEuclidean) / 400.;
+
+        let mut solver = super::SinkhornPartial::new(&a, &b, &m, 1E-2, 2. / 3.);
+        let plan = match solver.solve() {
+            Ok(result) => result,
+            Err(error) => panic!("{:?}", error),
+        };
+
+        assert!(solver.log().converged);
+        assert!((plan.sum() - 2. / 3.).abs() < 1E-9);
+
+        // Capacities are respected
+        for (&marginal, &capacity) in plan.sum_axis(Axis(1)).iter().zip(&a) {
+            assert!(marginal <= capacity + 1E-6);
+        }
+        for (&marginal, &capacity) in plan.sum_axis(Axis(0)).iter().zip(&b) {
+            assert!(marginal <= capacity + 1E-6);
+        }
+
+        // The outliers are left out
+        assert!(plan.row(2).sum() < 1E-3);
+        assert!(plan.column(2).sum() < 1E-3);
+
+        // Samples with zero weight receive no mass
+        let a = array![0.5, 0.5, 0.];
+        let mut solver = super::SinkhornPartial::new(&a, &b, &m, 1E-2, 2. / 3.);
+        let plan = match solver.solve() {
+            Ok(result) => result,
+            Err(error) => panic!("{:?}", error),
+        };
+
+        assert!(plan.iter().all(|g| g.is_finite()));
+        assert!(plan.row(2).sum() == 0.);
+        assert!((plan.sum() - 2. / 3.).abs() < 1E-6);
+
+        // No transported mass
+        let plan = match super::SinkhornPartial::new(&a, &b, &m, 1E-2, 2. / 3.)
+            .mass(0.)
+            .solve()
+        {
+            Ok(result) => result,
+            Err(error) => panic!("{:?}", error),
+        };
+
+        assert_eq!(plan, Array2::<f64>::zeros((3, 3)));
+    }
+}
@@ -0,0 +1,546 @@
+use ndarray::prelude::*;
+use ndarray::{concatenate, Data, ScalarOperand};
+use num_traits::Float;
+use std::cmp::Ordering;
+
+use crate::error::OTError;
+use crate::optim::lbfgs::lbfgsb;
+use crate::{OTSolver, SolverLog};
+
+/// Approximate entropic OT matrix and screening sets of Screenkhorn
+#[derive(Debug, Clone)]
+pub struct ScreenkhornResult<A> {
+    /// OT matrix diag(u) K diag(v), with the scalings of the screened out samples fixed
+    pub plan: Array2<A>,
+    /// Sorted indices of the active source samples
+    pub source_active: Vec<usize>,
+    /// Sorted indices of the active target samples
+    pub target_active: Vec<usize>,
+}
+
+/// Solves the entropic regularization optimal transport problem on a budget of active samples
+/// with the Screenkhorn method and returns the approximate OT matrix and the screening sets
+///
+/// The samples which scalings are expected at their lower bound in the dual of the Sinkhorn
+/// problem are screened out, their scalings are fixed to the bounds eps / kappa and
+/// eps * kappa which leaves n_budget source and m_budget target active samples. The dual
+/// problem restricted to the active scalings is solved with L-BFGS-B. With the full budgets
+/// the plan is the Sinkhorn plan. Samples with zero weight are left out of the screening and
+/// receive no mass.
+///
+/// kappa balances the screening thresholds of both sides, the marginals of the active samples
+/// are met up to the factors kappa and 1 / kappa. Budgets screening out samples of comparable
+/// weights on both sides keep kappa close to 1.
+///
+/// Screening Sinkhorn Algorithm for Regularized Optimal Transport
+/// by Mokhtar Z. Alaya, Maxime Bérar, Gilles Gasso, Alain Rakotomamonjy
+///
+/// ```rust
+/// use rust_optimal_transport as ot;
+/// use ot::prelude::*;
+/// use ndarray::prelude::*;
+/// use ndarray_stats::QuantileExt;
+///
+/// // Generate data
+/// let n = 100;
+///
+/// let cov = array![[1., 0.], [0., 1.]];
+/// let source = ot::utils::sample_2D_gauss(n, &array![0., 0.], &cov).unwrap();
+/// let target = ot::utils::sample_2D_gauss(n, &array![4., 4.], &cov).unwrap();
+///
+/// // Uniform weights on the source and target distributions
+/// let source_weights = Array1::<f64>::from_elem(n, 1. / (n as f64));
+/// let target_weights = Array1::<f64>::from_elem(n, 1. / (n as f64));
+///
+/// // Normalize cost matrix for numerical stability
+/// let mut cost = dist(&source, &target, SqEuclidean);
+/// cost = &cost / *cost.max().unwrap();
+///
+/// // Half of the samples of each distribution are kept active
+/// let result = match Screenkhorn::new(
+///     &source_weights,
+///     &target_weights,
+///     &cost,
+///     1E-1,
+///     n / 2,
+///     n / 2,
+/// ).solve() {
+///     Ok(result) => result,
+///     Err(error) => panic!("{:?}", error),
+/// };
+///
+/// let ot_matrix = result.plan;
+/// assert_eq!(result.source_active.len(), n / 2);
+///
+/// ```
+///
+/// source_weights and target_weights represent histograms of the Source and Target distributions,
+/// respectively.
+///
+pub struct Screenkhorn<'a, A> {
+    source_weights: ArrayView1<'a, A>,
+    target_weights: ArrayView1<'a, A>,
+    cost: ArrayView2<'a, A>,
+    reg: A,
+    n_budget: usize,
+    m_budget: usize,
+    iterations: i32,
+    threshold: A,
+    strict: bool,
+    log: SolverLog,
+}
+
+impl<'a, A> Screenkhorn<'a, A>
+where
+    A: Float + ScalarOperand,
+{
+    pub fn new<S1, S2, S3>(
+        source_weights: &'a ArrayBase<S1, Ix1>,
+        target_weights: &'a ArrayBase<S2, Ix1>,
+        cost: &'a ArrayBase<S3, Ix2>,
+        reg: A,
+        n_budget: usize,
+        m_budget: usize,
+    ) -> Self
+    where
+        S1: Data<Elem = A>,
+        S2: Data<Elem = A>,
+        S3: Data<Elem = A>,
+    {
+        Self {
+            source_weights: source_weights.view(),
+            target_weights: target_weights.view(),
+            cost: cost.view(),
+            reg,
+            n_budget,
+            m_budget,
+            iterations: 1000,
+            threshold: A::from(1E-9).unwrap(),
+            strict: false,
+            log: SolverLog::default(),
+        }
+    }
+
+    /// Max number of L-BFGS-B iterations
+    pub fn iterations<'b>(&'b mut self, iterations: i32) -> &'b mut Self {
+        self.iterations = iterations;
+        self
+    }
+
+    /// Stop threshold on the largest entry of the projected gradient of the restricted dual
+    pub fn threshold<'b>(&'b mut self, threshold: A) -> &'b mut Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Returns a ConvergenceError if the stop threshold is not reached within the max number
+    /// of iterations
+    pub fn strict<'b>(&'b mut self, strict: bool) -> &'b mut Self {
+        self.strict = strict;
+        self
+    }
+
+    pub fn reg<'b>(&'b mut self, reg: A) -> &'b mut Self {
+        self.reg = reg;
+        self
+    }
+
+    /// Number of active source samples, 1 <= n_budget <= n
+    pub fn n_budget<'b>(&'b mut self, n_budget: usize) -> &'b mut Self {
+        self.n_budget = n_budget;
+        self
+    }
+
+    /// Number of active target samples, 1 <= m_budget <= m
+    pub fn m_budget<'b>(&'b mut self, m_budget: usize) -> &'b mut Self {
+        self.m_budget = m_budget;
+        self
+    }
+}
+
+impl<'a, A> OTSolver for Screenkhorn<'a, A>
+where
+    A: Float + ScalarOperand,
+{
+    type Output = ScreenkhornResult<A>;
+
+    /// Ensures dimensions of the source and target measures are consistent with the
+    /// cost matrix dimensions
+    fn check_shape(&self) -> Result<(), OTError> {
+        let mshape = self.cost.shape();
+        let m0 = mshape[0];
+        let m1 = mshape[1];
+        let dim_a = self.source_weights.len();
+        let dim_b = self.target_weights.len();
+
+        if dim_a != m0 || dim_b != m1 {
+            return Err(OTError::WeightDimensionError {
+                dim_a,
+                dim_b,
+                dim_m_0: m0,
+                dim_m_1: m1,
+            });
+        }
+
+        Ok(())
+    }
+
+    fn solve(&mut self) -> Result<ScreenkhornResult<A>, OTError> {
+        self.check_shape()?;
+
+        if self.reg <= A::zero() {
+            return Err(OTError::ArgError("Regularization term <= 0".to_string()));
+        }
+
+        if self.iterations <= 0 {
+            return Err(OTError::ArgError(
+                "Iterations not a valid value. Must be > 0".to_string(),
+            ));
+        }
+
+        if !self.source_weights.iter().any(|&w| w > A::zero())
+            || !self.target_weights.iter().any(|&w| w > A::zero())
+        {
+            return Err(OTError::ArgError(
+                "Source or target weights without positive entries".to_string(),
+            ));
+        }
+
+        if self.n_budget == 0
+            || self.n_budget > self.source_weights.len()
+            || self.m_budget == 0
+            || self.m_budget > self.target_weights.len()
+        {
+            return Err(OTError::ArgError(
+                "Budgets must be between 1 and the number of samples".to_string(),
+            ));
+        }
+
+        let (result, log) = screenkhorn(
+            self.source_weights,
+            self.target_weights,
+            self.cost,
+            self.reg,
+            (self.n_budget, self.m_budget),
+            self.iterations,
+            self.threshold,
+        );
+
+        self.log = log;
+
+        if self.strict {
+            self.log.check_convergence(self.threshold)?;
+        }
+
+        Ok(result)
+    }
+
+    fn log(&self) -> &SolverLog {
+        &self.log
+    }
+}
+
+/// Sorted indices of the budget largest ratios and the smallest of the selected ratios
+fn screen<A: Float>(ratios: &Array1<A>, budget: usize) -> (Vec<usize>, A) {
+    let mut order: Vec<usize> = (0..ratios.len()).collect();
+    order.sort_by(|&i, &j| ratios[j].partial_cmp(&ratios[i]).unwrap_or(Ordering::Equal));
+
+    let bound = ratios[order[budget - 1]];
+    order.truncate(budget);
+    order.sort_unstable();
+
+    (order, bound)
+}
+
+/// Solves the screened dual of the entropic regularization optimal transport problem and
+/// returns the OT matrix and the screening sets
+///
+/// a: Source sample weights
+/// b: Target sample weights
+/// M: Loss matrix
+/// reg: Entropy regularization term > 0
+/// budget: Number of active source and target samples, capped at the number of samples with
+/// positive weight
+/// iterations: Max number of L-BFGS-B iterations
+/// threshold: Stop threshold on the largest entry of the projected gradient
+#[allow(non_snake_case)]
+fn screenkhorn<A>(
+    a: ArrayView1<A>,
+    b: ArrayView1<A>,
+    M: ArrayView2<A>,
+    reg: A,
+    budget: (usize, usize),
+    iterations: i32,
+    threshold: A,
+) -> (ScreenkhornResult<A>, SolverLog)
+where
+    A: Float + ScalarOperand,
+{
+    // Samples with zero weight receive no mass, the screening runs on the other samples
+    let positive =
+        |w: ArrayView1<A>| -> Vec<usize> { (0..w.len()).filter(|&i| w[i] > A::zero()).collect() };
+    let (source_support, target_support) = (positive(a), positive(b));
+    let budget = (
+        budget.0.min(source_support.len()),
+        budget.1.min(target_support.len()),
+    );
+
+    let (a_full, b_full) = (a, b);
+    let a = a.select(Axis(0), &source_support);
+    let b = b.select(Axis(0), &target_support);
+    let M = M
+        .select(Axis(0), &source_support)
+        .select(Axis(1), &target_support);
+    let (dim_a, dim_b) = M.dim();
+
+    // The kernel exp(-M/reg) is evaluated on the fly, the optimization only stores it on the
+    // active samples
+    let kernel = |m: A| (-m / reg).exp();
+    let row_sums = M.fold_axis(Axis(1), A::zero(), |&acc, &m| acc + kernel(m));
+    let col_sums = M.fold_axis(Axis(0), A::zero(), |&acc, &m| acc + kernel(m));
+
+    // Active samples have the largest a_i / (K 1)_i and b_j / (K^T 1)_j, the screening
+    // thresholds eps^2 / kappa and eps^2 * kappa select the budgets
+    let (source_active, eps_u) = screen(&(&a / &row_sums), budget.0);
+    let (target_active, eps_v) = screen(&(&b / &col_sums), budget.1);
+
+    let (epsilon, kappa) = if budget == (dim_a, dim_b) {
+        (A::zero(), A::one())
+    } else {
+        ((eps_u * eps_v).sqrt().sqrt(), (eps_v / eps_u).sqrt())
+    };
+
+    // Lower bounds of the scalings, the screened out scalings are fixed to them
+    let u_bound = epsilon / kappa;
+    let v_bound = epsilon * kappa;
+
+    let mut is_source_active = vec![false; dim_a];
+    source_active
+        .iter()
+        .for_each(|&i| is_source_active[i] = true);
+    let mut is_target_active = vec![false; dim_b];
+    target_active
+        .iter()
+        .for_each(|&j| is_target_active[j] = true);
+
+    let (n_active, m_active) = (source_active.len(), target_active.len());
+    let a_active = a.select(Axis(0), &source_active);
+    let b_active = b.select(Axis(0), &target_active);
+    let k_active = M
+        .select(Axis(0), &source_active)
+        .select(Axis(1), &target_active)
+        .mapv_into(kernel);
+
+    // Kernel mass between the active samples and the screened out samples of the other side
+    let k_screened_rows = Array1::from_iter(source_active.iter().map(|&i| {
+        M.row(i)
+            .iter()
+            .zip(&is_target_active)
+            .filter(|(_, &active)| !active)
+            .fold(A::zero(), |acc, (&m, _)| acc + kernel(m))
+    }));
+    let k_screened_cols = Array1::from_iter(target_active.iter().map(|&j| {
+        M.column(j)
+            .iter()
+            .zip(&is_source_active)
+            .filter(|(_, &active)| !active)
+            .fold(A::zero(), |acc, (&m, _)| acc + kernel(m))
+    }));
+
+    // Restricted Sinkhorn iterations as a warm start
+    let mut u = Array1::<A>::ones(n_active);
+    let mut v = Array1::<A>::ones(m_active);
+    for _ in 0..5 {
+        let ktu = k_active.t().dot(&u) + &k_screened_cols * u_bound;
+        v = (&b_active / kappa / &ktu).mapv_into(|v| v.max(v_bound));
+
+        let kv = k_active.dot(&v) + &k_screened_rows * v_bound;
+        u = (&a_active * kappa / &kv).mapv_into(|u| u.max(u_bound));
+    }
+
+    // Restricted dual over x = log(u), y = log(v)
+    // 1^T diag(u) K diag(v) 1 - kappa <a, x> - <b, y> / kappa
+    let objective = |z: &Array1<A>| {
+        let (x, y) = z.view().split_at(Axis(0), n_active);
+        let (u, v) = (x.mapv(A::exp), y.mapv(A::exp));
+
+        let kv = k_active.dot(&v) + &k_screened_rows * v_bound;
+        let ktu = k_active.t().dot(&u) + &k_screened_cols * u_bound;
+
+        let value = u.dot(&kv) + v.dot(&k_screened_cols) * u_bound
+            - a_active.dot(&x) * kappa
+            - b_active.dot(&y) / kappa;
+
+        let grad_x = &u * &kv - &a_active * kappa;
+        let grad_y = &v * &ktu - &b_active / kappa;
+
+        (value, concatenate![Axis(0), grad_x, grad_y])
+    };
+
+    // Box constraints on the active scalings, Proposition 1 of the paper
+    let (n, m) = (A::from(dim_a).unwrap(), A::from(dim_b).unwrap());
+    let (n_b, m_b) = (A::from(n_active).unwrap(), A::from(m_active).unwrap());
+    let extent = |x: &Array1<A>| {
+        x.fold((A::infinity(), A::zero()), |(lo, hi), &x| {
+            (lo.min(x), hi.max(x))
+        })
+    };
+    let (a_min, a_max) = extent(&a_active);
+    let (b_min, b_max) = extent(&b_active);
+    let k_min = k_active.fold(A::infinity(), |acc, &k| acc.min(k));
+
+    let u_lower =
+        (a_min / ((m - m_b) * epsilon + m_b * b_max / (n * epsilon * kappa * k_min))).max(u_bound);
+    let u_upper = a_max / (m * epsilon * k_min);
+    let v_lower =
+        (b_min / ((n - n_b) * epsilon + n_b * kappa * a_max / (m * epsilon * k_min))).max(v_bound);
+    let v_upper = b_max / (n * epsilon * k_min);
+
+    let lower = concatenate![
+        Axis(0),
+        Array1::from_elem(n_active, u_lower.ln()),
+        Array1::from_elem(m_active, v_lower.ln())
+    ];
+    let upper = concatenate![
+        Axis(0),
+        Array1::from_elem(n_active, u_upper.ln()),
+        Array1::from_elem(m_active, v_upper.ln())
+    ];
+
+    let result = lbfgsb(
+        objective,
+        concatenate![Axis(0), u.mapv(A::ln), v.mapv(A::ln)],
+        &lower,
+        &upper,
+        iterations,
+        threshold,
+        10,
+    );
+
+    // Scalings of all samples
+    let mut u = Array1::from_elem(dim_a, u_bound);
+    for (&i, &x) in source_active.iter().zip(result.x.slice(s![..n_active])) {
+        u[i] = x.exp();
+    }
+    let mut v = Array1::from_elem(dim_b, v_bound);
+    for (&j, &y) in target_active.iter().zip(result.x.slice(s![n_active..])) {
+        v[j] = y.exp();
+    }
+
+    // G = diag(u) K diag(v), zero on the samples with zero weight
+    let mut plan = Array2::<A>::zeros((a_full.len(), b_full.len()));
+    for (i_s, &i) in source_support.iter().enumerate() {
+        for (j_s, &j) in target_support.iter().enumerate() {
+            plan[(i, j)] = u[i_s] * kernel(M[(i_s, j_s)]) * v[j_s];
+        }
+    }
+
+    let viol = (&plan.sum_axis(Axis(1)) - &a_full).mapv(A::abs).sum()
+        + (&plan.sum_axis(Axis(0)) - &b_full).mapv(A::abs).sum();
+
+    // L-BFGS-B does not report the change of its iterates
+    let log = SolverLog {
+        iterations: result.iterations,
+        marginal_violation: vec![viol.to_f64().unwrap()],
+        error: result.grad_norm.to_f64().unwrap(),
+        converged: result.converged,
+        ..SolverLog::default()
+    };
+
+    let result = ScreenkhornResult {
+        plan,
+        source_active: source_active.iter().map(|&i| source_support[i]).collect(),
+        target_active: target_active.iter().map(|&j| target_support[j]).collect(),
+    };
+
+    (result, log)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use ndarray::prelude::*;
+
+    use crate::regularized::sinkhorn::SinkhornKnopp;
+    use crate::OTSolver;
+
+    #[test]
+    fn test_screenkhorn() {
+        let n = 50;
+        let x = Array::range(0., n as f64, 1.).into_shape((n, 1)).unwrap();
+        let m = crate::metrics::dist(&x, &x, crate::metrics::MetricType::SqEuclidean);
+        let m = &m / (n * n) as f64;
+
+        let a = crate::utils::get_1D_gauss_histogram(n, 25., 4.).unwrap();
+        let b = a.clone();
+        let reg = 1E-1;
+
+        let truth = match SinkhornKnopp::new(&a, &b, &m, reg).threshold(1E-12).solve() {
+            Ok(result) => result,
+            Err(error) => panic!("{:?}", error),
+        };
+
+        // The full budgets give back the Sinkhorn plan
+        let mut solver = super::Screenkhorn::new(&a, &b, &m, reg, n, n);
+        let result = match solver.solve() {
+            Ok(result) => result,
+            Err(error) => panic!("{:?}", error),
+        };
+
+        assert!(solver.log().converged);
+        assert_eq!(result.source_active.len(), n);
+        assert!(result.plan.abs_diff_eq(&truth, 1E-8));
+
+        // Screening out the tails of the histograms approximates the Sinkhorn plan
+        let result = match super::Screenkhorn::new(&a, &b, &m, reg, n, n)
+            .n_budget(30)
+            .m_budget(30)
+            .solve()
+        {
+            Ok(result) => result,
+            Err(error) => panic!("{:?}", error),
+        };
+
+        assert_eq!(result.source_active, (11..41).collect::<Vec<usize>>());
+        assert_eq!(result.target_active.len(), 30);
+        assert!((result.plan.sum_axis(Axis(1)) - &a).mapv(f64::abs).sum() < 2E-2);
+        assert!((result.plan.sum_axis(Axis(0)) - &b).mapv(f64::abs).sum() < 2E-2);
+        assert!((&result.plan - &truth).mapv(f64::abs).sum() < 5E-2);
+
+        // Sparse histograms, the budgets are capped at the samples with positive weight
+        let mut a_sparse = Array1::<f64>::zeros(n);
+        a_sparse.slice_mut(s![20..30]).fill(0.1);
+        let truth = match SinkhornKnopp::new(&a_sparse, &b, &m, reg)
+            .threshold(1E-12)
+            .solve()
+        {
+            Ok(result) => result,
+            Err(error) => panic!("{:?}", error),
+        };
+
+        for n_budget in [n, 5] {
+            let result = match super::Screenkhorn::new(&a_sparse, &b, &m, reg, n_budget, n).solve()
+            {
+                Ok(result) => result,
+                Err(error) => panic!("{:?}", error),
+            };
+
+            assert!(result.plan.iter().all(|g| g.is_finite()));
+            assert!(result.source_active.iter().all(|&i| a_sparse[i] > 0.));
+            assert_eq!(result.plan.slice(s![..20, ..]).sum(), 0.);
+
+            if n_budget == n {
+                assert_eq!(result.source_active, (20..30).collect::<Vec<usize>>());
+                assert!(result.plan.abs_diff_eq(&truth, 1E-6));
+            }
+        }
+
+        // Invalid budgets
+        assert!(super::Screenkhorn::new(&a, &b, &m, reg, 0, n)
+            .solve()
+            .is_err());
+        assert!(super::Screenkhorn::new(&a, &b, &m, reg, n, n + 1)
+            .solve()
+            .is_err());
+    }
+}
@@ -1,9 +1,12 @@
 // use crate::ndarray_logical;
 use ndarray::prelude::*;
-use ndarray_linalg::norm;
+use ndarray::{Data, ScalarOperand};
+use num_traits::Float;
 
+use super::loss::entropic_loss;
 use crate::error::OTError;
-use crate::OTSolver;
+use crate::parallel::{self, Element};
+use crate::{OTSolver, SolverLog};
 
 /// Solves the entropic regularization optimal transport problem using the SinkhornKnopp algorithm and returns the OT matrix
 ///
@@ -58,29 +61,41 @@ use crate::OTSolver;
 /// respectively.
 ///
 
-pub struct SinkhornKnopp<'a> {
-    source_weights: &'a Array1<f64>,
-    target_weights: &'a Array1<f64>,
-    cost: &'a Array2<f64>,
-    reg: f64,
+pub struct SinkhornKnopp<'a, A> {
+    source_weights: ArrayView1<'a, A>,
+    target_weights: ArrayView1<'a, A>,
+    cost: ArrayView2<'a, A>,
+    reg: A,
     iterations: i32,
-    threshold: f64,
+    threshold: A,
+    strict: bool,
+    log: SolverLog,
 }
 
-impl<'a> SinkhornKnopp<'a> {
-    pub fn new(
-        source_weights: &'a Array1<f64>,
-        target_weights: &'a Array1<f64>,
-        cost: &'a Array2<f64>,
-        reg: f64,
-    ) -> Self {
+impl<'a, A> SinkhornKnopp<'a, A>
+where
+    A: Float + ScalarOperand + Element,
+{
+    pub fn new<S1, S2, S3>(
+        source_weights: &'a ArrayBase<S1, Ix1>,
+        target_weights: &'a ArrayBase<S2, Ix1>,
+        cost: &'a ArrayBase<S3, Ix2>,
+        reg: A,
+    ) -> Self
+    where
+        S1: Data<Elem = A>,
+        S2: Data<Elem = A>,
+        S3: Data<Elem = A>,
+    {
         Self {
-            source_weights,
-            target_weights,
-            cost,
+            source_weights: source_weights.view(),
+            target_weights: target_weights.view(),
+            cost: cost.view(),
             reg,
             iterations: 1000,
-            threshold: 1E-9,
+            threshold: A::from(1E-9).unwrap(),
+            strict: false,
+            log: SolverLog::default(),
         }
     }
 
@@ -89,18 +104,44 @@ impl<'a> SinkhornKnopp<'a> {
         self
     }
 
-    pub fn threshold<'b>(&'b mut self, threshold: f64) -> &'b mut Self {
+    pub fn threshold<'b>(&'b mut self, threshold: A) -> &'b mut Self {
         self.threshold = threshold;
         self
     }
 
-    pub fn reg<'b>(&'b mut self, reg: f64) -> &'b mut Self {
+    /// Returns a ConvergenceError if the stop threshold is not reached within the max number
+    /// of iterations
+    pub fn strict<'b>(&'b mut self, strict: bool) -> &'b mut Self {
+        self.strict = strict;
+        self
+    }
+
+    pub fn reg<'b>(&'b mut self, reg: A) -> &'b mut Self {
         self.reg = reg;
         self
     }
+
+    /// Solves the entropic regularization optimal transport problem and returns the loss
+    /// <G, M> + reg * KL(G | a b^T)
+    pub fn loss(&mut self) -> Result<A, OTError> {
+        let plan = self.solve()?;
+
+        Ok(entropic_loss(
+            &plan,
+            &self.source_weights,
+            &self.target_weights,
+            &self.cost,
+            self.reg,
+        ))
+    }
 }
 
-impl<'a> OTSolver for SinkhornKnopp<'a> {
+impl<'a, A> OTSolver for SinkhornKnopp<'a, A>
+where
+    A: Float + ScalarOperand + Element,
+{
+    type Output = Array2<A>;
+
     /// Ensures dimensions of the source and target measures are consistent with the
     /// cost matrix dimensions
     fn check_shape(&self) -> Result<(), OTError> {
@@ -123,10 +164,10 @@ impl<'a> OTSolver for SinkhornKnopp<'a> {
         Ok(())
     }
 
-    fn solve(&mut self) -> Result<Array2<f64>, OTError> {
+    fn solve(&mut self) -> Result<Array2<A>, OTError> {
         self.check_shape()?;
 
-        if self.reg <= 0. {
+        if self.reg <= A::zero() {
             return Err(OTError::ArgError("Regularization term <= 0".to_string()));
         }
 
@@ -136,26 +177,42 @@ impl<'a> OTSolver for SinkhornKnopp<'a> {
             ));
         }
 
-        sinkhorn_knopp(
+        let (plan, log) = sinkhorn_knopp(
             self.source_weights,
             self.target_weights,
             self.cost,
             self.reg,
             self.iterations,
             self.threshold,
-        )
+        )?;
+
+        self.log = log;
+
+        if self.strict {
+            self.log.check_convergence(self.threshold)?;
+        }
+
+        Ok(plan)
+    }
+
+    fn log(&self) -> &SolverLog {
+        &self.log
     }
 }
 
-fn sinkhorn_knopp(
-    a: &Array1<f64>,
-    b: &Array1<f64>,
-    M: &Array2<f64>,
-    reg: f64,
+fn sinkhorn_knopp<A>(
+    a: ArrayView1<A>,
+    b: ArrayView1<A>,
+    M: ArrayView2<A>,
+    reg: A,
     iterations: i32,
-    threshold: f64,
-) -> Result<Array2<f64>, OTError> {
-    let mut err: f64;
+    threshold: A,
+) -> Result<(Array2<A>, SolverLog), OTError>
+where
+    A: Float + ScalarOperand + Element,
+{
+    let mut log = SolverLog::default();
+    let mut err: A;
     let mut ktu;
     let mut v_prev;
     let kp;
@@ -164,18 +221,15 @@ fn sinkhorn_knopp(
     let dim_b = b.len();
 
     // we assume that no distances are null except those of the diagonal distances
-    let mut u = Array1::<f64>::from_elem(dim_a, 1. / (dim_a as f64));
-    let mut v = Array1::<f64>::from_elem(dim_b, 1. / (dim_b as f64));
+    let mut u = Array1::<A>::from_elem(dim_a, A::one() / A::from(dim_a).unwrap());
+    let mut v = Array1::<A>::from_elem(dim_b, A::one() / A::from(dim_b).unwrap());
 
     // K = exp(-M/reg)
-    let f = |ele: f64| (-ele / reg).exp();
-    let k = M.clone().mapv_into(f);
-
-    let a_cache = a.clone();
-    let b_cache = b.clone();
+    let f = move |ele: A| (-ele / reg).exp();
+    let k = parallel::map(&M, f);
 
     // Kp = (1./a) * K
-    let numerator: Array1<f64> = a_cache.mapv_into(|a| 1. / a);
+    let numerator: Array1<A> = a.mapv(|a| A::one() / a);
     kp = numerator.into_shape((dim_a, 1)).unwrap() * &k;
 
     // K.transpose()
@@ -185,25 +239,326 @@ fn sinkhorn_knopp(
         v_prev = v.clone();
 
         // Update v
-        ktu = k_transpose.dot(&u);
+        ktu = parallel::mat_vec(&k_transpose, &u);
 
         // v = b/ktu
-        azip!((v in &mut v, &b in &b_cache, &ktu in &ktu) *v = b / ktu);
+        azip!((v in &mut v, &b in &b, &ktu in &ktu) *v = b / ktu);
 
         // Update u
         // u = a/kv = 1 / (dot(kp, v)
-        azip!((u in &mut u, &kpdotv in &kp.dot(&v)) *u = 1. / kpdotv);
+        azip!((u in &mut u, &kpdotv in &parallel::mat_vec(&kp, &v)) *u = A::one() / kpdotv);
+
+        log.iterations = count + 1;
+
+        if count % 10 == 0 {
+            err = (&v - &v_prev).mapv(A::abs).sum();
+
+            // Violation of the target marginal, sum(G, axis=0) = v * K^T u
+            let viol = marginal_violation(&v, &parallel::mat_vec(&k_transpose, &u), b);
+
+            log.push(err, viol, err);
+
+            if err < threshold {
+                log.converged = true;
+                break;
+            }
+        }
+    }
+
+    let plan = u.into_shape((dim_a, 1)).unwrap() * k * v.into_shape((1, dim_b)).unwrap();
+
+    Ok((plan, log))
+}
+
+/// Solves the entropic regularization optimal transport problem using the Sinkhorn-Knopp
+/// algorithm in the log-domain and returns the OT matrix
+///
+/// The iterations are performed on the dual potentials with log-sum-exp reductions, which keeps
+/// the solver stable for small regularization terms where SinkhornKnopp underflows.
+///
+/// ```rust
+/// use rust_optimal_transport as ot;
+/// use ot::prelude::*;
+/// use ndarray::prelude::*;
+/// use ndarray_stats::QuantileExt;
+///
+/// // Generate data
+/// let n = 100;
+///
+/// // Mean, Covariance of the source distribution
+/// let mu_source = array![0., 0.];
+/// let cov_source = array![[1., 0.], [0., 1.]];
+///
+/// // Mean, Covariance of the target distribution
+/// let mu_target = array![4., 4.];
+/// let cov_target = array![[1., -0.8], [-0.8, 1.]];
+///
+/// // Samples of a 2D gaussian distribution
+/// let source = ot::utils::sample_2D_gauss(n, &mu_source, &cov_source).unwrap();
+/// let target = ot::utils::sample_2D_gauss(n, &mu_target, &cov_target).unwrap();
+///
+/// // Uniform weights on the source and target distributions
+/// let source_weights = Array1::<f64>::from_elem(n, 1. / (n as f64));
+/// let target_weights = Array1::<f64>::from_elem(n, 1. / (n as f64));
+///
+/// // Compute the cost between distributions
+/// let mut cost = dist(&source, &target, SqEuclidean);
+///
+/// // Normalize cost matrix for numerical stability
+/// let max_cost = cost.max().unwrap();
+/// cost = &cost / *max_cost;
+///
+/// let regularization = 1E-4;
+///
+/// // Compute optimal transport matrix
+/// let ot_matrix = match SinkhornLog::new(
+///     &source_weights,
+///     &target_weights,
+///     &cost,
+///     regularization,
+/// ).solve() {
+///     Ok(result) => result,
+///     Err(error) => panic!("{:?}", error),
+/// };
+///
+/// ```
+///
+/// source_weights and target_weights represent histograms of the Source and Target distributions,
+/// respectively.
+///
+pub struct SinkhornLog<'a, A> {
+    source_weights: ArrayView1<'a, A>,
+    target_weights: ArrayView1<'a, A>,
+    cost: ArrayView2<'a, A>,
+    reg: A,
+    iterations: i32,
+    threshold: A,
+    strict: bool,
+    log: SolverLog,
+}
+
+impl<'a, A> SinkhornLog<'a, A>
+where
+    A: Float + ScalarOperand,
+{
+    pub fn new<S1, S2, S3>(
+        source_weights: &'a ArrayBase<S1, Ix1>,
+        target_weights: &'a ArrayBase<S2, Ix1>,
+        cost: &'a ArrayBase<S3, Ix2>,
+        reg: A,
+    ) -> Self
+    where
+        S1: Data<Elem = A>,
+        S2: Data<Elem = A>,
+        S3: Data<Elem = A>,
+    {
+        Self {
+            source_weights: source_weights.view(),
+            target_weights: target_weights.view(),
+            cost: cost.view(),
+            reg,
+            iterations: 1000,
+            threshold: A::from(1E-9).unwrap(),
+            strict: false,
+            log: SolverLog::default(),
+        }
+    }
+
+    pub fn iterations<'b>(&'b mut self, iterations: i32) -> &'b mut Self {
+        self.iterations = iterations;
+        self
+    }
+
+    pub fn threshold<'b>(&'b mut self, threshold: A) -> &'b mut Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Returns a ConvergenceError if the stop threshold is not reached within the max number
+    /// of iterations
+    pub fn strict<'b>(&'b mut self, strict: bool) -> &'b mut Self {
+        self.strict = strict;
+        self
+    }
+
+    pub fn reg<'b>(&'b mut self, reg: A) -> &'b mut Self {
+        self.reg = reg;
+        self
+    }
+}
+
+impl<'a, A> OTSolver for SinkhornLog<'a, A>
+where
+    A: Float + ScalarOperand,
+{
+    type Output = Array2<A>;
+
+    /// Ensures dimensions of the source and target measures are consistent with the
+    /// cost matrix dimensions
+    fn check_shape(&self) -> Result<(), OTError> {
+        let mshape = self.cost.shape();
+        let m0 = mshape[0];
+        let m1 = mshape[1];
+        let dim_a = self.source_weights.len();
+        let dim_b = self.target_weights.len();
+
+        // Check dimensions
+        if dim_a != m0 || dim_b != m1 {
+            return Err(OTError::WeightDimensionError {
+                dim_a,
+                dim_b,
+                dim_m_0: m0,
+                dim_m_1: m1,
+            });
+        }
+
+        Ok(())
+    }
+
+    fn solve(&mut self) -> Result<Array2<A>, OTError> {
+        self.check_shape()?;
+
+        if self.reg <= A::zero() {
+            return Err(OTError::ArgError("Regularization term <= 0".to_string()));
+        }
+
+        if self.iterations <= 0 {
+            return Err(OTError::ArgError(
+                "Iterations not a valid value. Must be > 0".to_string(),
+            ));
+        }
+
+        let (plan, log) = sinkhorn_log(
+            self.source_weights,
+            self.target_weights,
+            self.cost,
+            self.reg,
+            self.iterations,
+            self.threshold,
+        )?;
+
+        self.log = log;
+
+        if self.strict {
+            self.log.check_convergence(self.threshold)?;
+        }
+
+        Ok(plan)
+    }
+
+    fn log(&self) -> &SolverLog {
+        &self.log
+    }
+}
+
+/// Computes log(sum(exp(x))) without overflow by factoring out the max value
+/// Returns -inf if all values are -inf
+pub(crate) fn logsumexp<A, I>(values: I) -> A
+where
+    A: Float,
+    I: Iterator<Item = A> + Clone,
+{
+    let max = values.clone().fold(A::neg_infinity(), A::max);
+
+    if max == A::neg_infinity() {
+        return max;
+    }
+
+    max + values.fold(A::zero(), |acc, x| acc + (x - max).exp()).ln()
+}
+
+/// L1 violation of a marginal constraint for a plan diag(u) K diag(v)
+/// scaling: v (resp. u)
+/// kernel_product: K^T u (resp. K v)
+/// marginal: b (resp. a)
+pub(crate) fn marginal_violation<A: Float>(
+    scaling: &Array1<A>,
+    kernel_product: &Array1<A>,
+    marginal: ArrayView1<A>,
+) -> A {
+    scaling
+        .iter()
+        .zip(kernel_product.iter())
+        .zip(marginal.iter())
+        .fold(A::zero(), |acc, ((&s, &kp), &m)| acc + (s * kp - m).abs())
+}
+
+/// Solves the entropic regularization optimal transport problem in the log-domain and return
+/// the OT matrix
+///
+/// a: Source sample weights
+/// b: Target sample weights
+/// M: Loss matrix
+/// reg: Entropy regularization term > 0
+/// iterations: Max number of iterations
+/// threshold: Stop threshold on the L1 violation of the target marginal
+#[allow(non_snake_case)]
+fn sinkhorn_log<A>(
+    a: ArrayView1<A>,
+    b: ArrayView1<A>,
+    M: ArrayView2<A>,
+    reg: A,
+    iterations: i32,
+    threshold: A,
+) -> Result<(Array2<A>, SolverLog), OTError>
+where
+    A: Float + ScalarOperand,
+{
+    let mut log = SolverLog::default();
+    let dim_a = a.len();
+    let dim_b = b.len();
+
+    let loga = a.mapv(A::ln);
+    let logb = b.mapv(A::ln);
+
+    // Scaled dual potentials: f/reg and g/reg
+    let mut u = Array1::<A>::zeros(dim_a);
+    let mut v = Array1::<A>::zeros(dim_b);
+
+    // log(G) = -M/reg + u[:, None] + v[None, :]
+    let log_plan = |u: &Array1<A>, v: &Array1<A>| {
+        let mut log_g = M.mapv(|ele| -ele / reg);
+        for ((i, j), ele) in log_g.indexed_iter_mut() {
+            *ele = *ele + u[i] + v[j];
+        }
+        log_g
+    };
+
+    for count in 0..iterations {
+        let v_prev = v.clone();
+
+        // v = log(b) - logsumexp(-M/reg + u[:, None], axis=0)
+        for (j, col) in M.axis_iter(Axis(1)).enumerate() {
+            let lse = logsumexp(col.iter().zip(u.iter()).map(|(&m, &u)| -m / reg + u));
+            v[j] = logb[j] - lse;
+        }
+
+        // u = log(a) - logsumexp(-M/reg + v[None, :], axis=1)
+        for (i, row) in M.axis_iter(Axis(0)).enumerate() {
+            let lse = logsumexp(row.iter().zip(v.iter()).map(|(&m, &v)| -m / reg + v));
+            u[i] = loga[i] - lse;
+        }
+
+        log.iterations = count + 1;
 
         if count % 10 == 0 {
-            err = norm::Norm::norm_l1(&(&v - &v_prev));
+            // Violation of the target marginal
+            let mut err = A::zero();
+            for (j, col) in M.axis_iter(Axis(1)).enumerate() {
+                let lse = logsumexp(col.iter().zip(u.iter()).map(|(&m, &u)| -m / reg + u));
+                err = err + ((lse + v[j]).exp() - b[j]).abs();
+            }
+
+            log.push((&v - &v_prev).mapv(A::abs).sum(), err, err);
 
             if err < threshold {
+                log.converged = true;
                 break;
             }
         }
     }
 
-    Ok(u.into_shape((dim_a, 1)).unwrap() * k * v.into_shape((1, dim_b)).unwrap())
+    Ok((log_plan(&u, &v).mapv_into(A::exp), log))
 }
 
 #[cfg(test)]
@@ -215,13 +570,13 @@ mod tests {
 
     #[test]
     fn test_sinkhorn_knopp() {
-        let mut a = array![0.5, 0.5];
-        let mut b = array![0.5, 0.5];
+        let a = array![0.5, 0.5];
+        let b = array![0.5, 0.5];
         let reg = 1.0;
-        let mut m = array![[0.0, 1.0], [1.0, 0.0]];
+        let m = array![[0.0, 1.0], [1.0, 0.0]];
 
-        let result = match super::sinkhorn_knopp(&mut a, &mut b, &mut m, reg, 1000, 1E-9) {
-            Ok(result) => result,
+        let result = match super::sinkhorn_knopp(a.view(), b.view(), m.view(), reg, 1000, 1E-9) {
+            Ok((result, _log)) => result,
             Err(error) => panic!("{:?}", error),
         };
 
@@ -246,4 +601,136 @@ mod tests {
 
         assert!(result.relative_eq(&truth, 1E-6, 1E-2));
     }
+
+    #[test]
+    fn test_sinkhorn_f32() {
+        let a = array![0.5f32, 0.5];
+        let b = array![0.5f32, 0.5];
+        let reg = 1.0f32;
+        let m = array![[0.0f32, 1.0], [1.0, 0.0]];
+
+        let result = match super::SinkhornKnopp::new(&a, &b, &m, reg)
+            .threshold(1E-6)
+            .solve()
+        {
+            Ok(result) => result,
+            Err(error) => panic!("{:?}", error),
+        };
+
+        let truth = array![[0.3655293f32, 0.13447071], [0.13447071, 0.3655293]];
+
+        assert!(result.relative_eq(&truth, 1E-5, 1E-2));
+    }
+
+    #[test]
+    fn test_sinkhorn_loss() {
+        let a = array![0.5, 0.5];
+        let b = array![0.5, 0.5];
+        let reg = 1.0;
+        let m = array![[0.0, 1.0], [1.0, 0.0]];
+
+        let loss: f64 = match super::SinkhornKnopp::new(&a, &b, &m, reg).loss() {
+            Ok(result) => result,
+            Err(error) => panic!("{:?}", error),
+        };
+
+        assert!((loss - 0.3798854930417225).abs() < 1E-6);
+    }
+
+    #[test]
+    fn test_sinkhorn_log() {
+        let a = array![0.5, 0.5];
+        let b = array![0.5, 0.5];
+        let reg = 1.0;
+        let m = array![[0.0, 1.0], [1.0, 0.0]];
+
+        let result = match super::sinkhorn_log(a.view(), b.view(), m.view(), reg, 1000, 1E-9) {
+            Ok((result, _log)) => result,
+            Err(error) => panic!("{:?}", error),
+        };
+
+        let truth = array![[0.36552929, 0.13447071], [0.13447071, 0.36552929]];
+
+        assert!(result.relative_eq(&truth, 1E-6, 1E-2));
+    }
+
+    #[test]
+    fn test_sinkhorn_log_builder() {
+        let a = array![0.5, 0.5];
+        let b = array![0.5, 0.5];
+        let reg = 1.0;
+        let m = array![[0.0, 1.0], [1.0, 0.0]];
+
+        let result = match super::SinkhornLog::new(&a, &b, &m, reg).solve() {
+            Ok(result) => result,
+            Err(error) => panic!("{:?}", error),
+        };
+
+        let truth = array![[0.36552929, 0.13447071], [0.13447071, 0.36552929]];
+
+        assert!(result.relative_eq(&truth, 1E-6, 1E-2));
+    }
+
+    #[test]
+    fn test_sinkhorn_log_small_reg() {
+        let a = array![0.5, 0.5];
+        let b = array![0.5, 0.5];
+        let reg = 1E-4;
+        let m = array![[0.0, 1.0], [1.0, 0.0]];
+
+        let result = match super::SinkhornLog::new(&a, &b, &m, reg).solve() {
+            Ok(result) => result,
+            Err(error) => panic!("{:?}", error),
+        };
+
+        // SinkhornKnopp underflows to NaN for this regularization
+        assert!(!crate::ndarray_logical::is_nan(&result));
+
+        let truth = array![[0.5, 0.0], [0.0, 0.5]];
+
+        assert!(result.abs_diff_eq(&truth, 1E-9));
+    }
+
+    #[test]
+    fn test_sinkhorn_convergence() {
+        let a = array![0.5, 0.5];
+        let b = array![0.5, 0.5];
+        let reg = 1.0;
+        let m = array![[0.0, 1.0], [1.0, 0.0]];
+
+        let mut solver = super::SinkhornKnopp::new(&a, &b, &m, reg);
+
+        if let Err(error) = solver.solve() {
+            panic!("{:?}", error);
+        }
+
+        let log = solver.log();
+        assert!(log.converged);
+        assert!(log.error < 1E-9);
+        assert!(log.iterations < 1000);
+        assert_eq!(log.v_change.len(), log.marginal_violation.len());
+    }
+
+    #[test]
+    fn test_sinkhorn_strict() {
+        let a = array![0.2, 0.8];
+        let b = array![0.5, 0.5];
+        let reg = 1E-2;
+        let m = array![[0.0, 1.0], [1.0, 0.0]];
+
+        let mut solver = super::SinkhornKnopp::new(&a, &b, &m, reg);
+        solver.iterations(1);
+
+        // Without strict mode the plan is returned and the log reports the failure
+        assert!(solver.solve().is_ok());
+        assert!(!solver.log().converged);
+        assert_eq!(solver.log().iterations, 1);
+
+        match solver.strict(true).solve() {
+            Err(crate::error::OTError::ConvergenceError { iterations, .. }) => {
+                assert_eq!(iterations, 1)
+            }
+            result => panic!("{:?}", result),
+        }
+    }
 }
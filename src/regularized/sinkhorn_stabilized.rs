@@ -0,0 +1,737 @@
+use ndarray::prelude::*;
+use ndarray::{Data, ScalarOperand};
+use num_traits::Float;
+
+use super::sinkhorn::marginal_violation;
+use crate::error::OTError;
+use crate::{OTSolver, SolverLog};
+
+/// Solves the entropic regularization optimal transport problem with the stabilized
+/// Sinkhorn-Knopp algorithm and returns the OT matrix
+///
+/// Stabilized sparse scaling algorithms for entropy regularized transport problems
+/// by Bernhard Schmitzer
+///
+/// Scalings larger than tau are absorbed into the dual potentials and the kernel is rebuilt,
+/// which avoids the overflow and underflow of SinkhornKnopp for small regularization terms.
+///
+/// ```rust
+/// use rust_optimal_transport as ot;
+/// use ot::prelude::*;
+/// use ndarray::prelude::*;
+/// use ndarray_stats::QuantileExt;
+///
+/// // Generate data
+/// let n = 100;
+///
+/// // Mean, Covariance of the source distribution
+/// let mu_source = array![0., 0.];
+/// let cov_source = array![[1., 0.], [0., 1.]];
+///
+/// // Mean, Covariance of the target distribution
+/// let mu_target = array![4., 4.];
+/// let cov_target = array![[1., -0.8], [-0.8, 1.]];
+///
+/// // Samples of a 2D gaussian distribution
+/// let source = ot::utils::sample_2D_gauss(n, &mu_source, &cov_source).unwrap();
+/// let target = ot::utils::sample_2D_gauss(n, &mu_target, &cov_target).unwrap();
+///
+/// // Uniform weights on the source and target distributions
+/// let source_weights = Array1::<f64>::from_elem(n, 1. / (n as f64));
+/// let target_weights = Array1::<f64>::from_elem(n, 1. / (n as f64));
+///
+/// // Compute the cost between distributions
+/// let mut cost = dist(&source, &target, SqEuclidean);
+///
+/// // Normalize cost matrix for numerical stability
+/// let max_cost = cost.max().unwrap();
+/// cost = &cost / *max_cost;
+///
+/// let regularization = 1E-3;
+///
+/// // Compute optimal transport matrix
+/// let ot_matrix = match SinkhornStabilized::new(
+///     &source_weights,
+///     &target_weights,
+///     &cost,
+///     regularization,
+/// ).tau(1E3).solve() {
+///     Ok(result) => result,
+///     Err(error) => panic!("{:?}", error),
+/// };
+///
+/// ```
+///
+/// source_weights and target_weights represent histograms of the Source and Target distributions,
+/// respectively.
+///
+pub struct SinkhornStabilized<'a, A> {
+    source_weights: ArrayView1<'a, A>,
+    target_weights: ArrayView1<'a, A>,
+    cost: ArrayView2<'a, A>,
+    reg: A,
+    iterations: i32,
+    threshold: A,
+    tau: A,
+    strict: bool,
+    log: SolverLog,
+}
+
+impl<'a, A> SinkhornStabilized<'a, A>
+where
+    A: Float + ScalarOperand,
+{
+    pub fn new<S1, S2, S3>(
+        source_weights: &'a ArrayBase<S1, Ix1>,
+        target_weights: &'a ArrayBase<S2, Ix1>,
+        cost: &'a ArrayBase<S3, Ix2>,
+        reg: A,
+    ) -> Self
+    where
+        S1: Data<Elem = A>,
+        S2: Data<Elem = A>,
+        S3: Data<Elem = A>,
+    {
+        Self {
+            source_weights: source_weights.view(),
+            target_weights: target_weights.view(),
+            cost: cost.view(),
+            reg,
+            iterations: 1000,
+            threshold: A::from(1E-9).unwrap(),
+            tau: A::from(1E3).unwrap(),
+            strict: false,
+            log: SolverLog::default(),
+        }
+    }
+
+    pub fn iterations<'b>(&'b mut self, iterations: i32) -> &'b mut Self {
+        self.iterations = iterations;
+        self
+    }
+
+    pub fn threshold<'b>(&'b mut self, threshold: A) -> &'b mut Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Returns a ConvergenceError if the stop threshold is not reached within the max number
+    /// of iterations
+    pub fn strict<'b>(&'b mut self, strict: bool) -> &'b mut Self {
+        self.strict = strict;
+        self
+    }
+
+    pub fn reg<'b>(&'b mut self, reg: A) -> &'b mut Self {
+        self.reg = reg;
+        self
+    }
+
+    /// Threshold on the scalings above which they are absorbed into the dual potentials
+    pub fn tau<'b>(&'b mut self, tau: A) -> &'b mut Self {
+        self.tau = tau;
+        self
+    }
+}
+
+impl<'a, A> OTSolver for SinkhornStabilized<'a, A>
+where
+    A: Float + ScalarOperand,
+{
+    type Output = Array2<A>;
+
+    /// Ensures dimensions of the source and target measures are consistent with the
+    /// cost matrix dimensions
+    fn check_shape(&self) -> Result<(), OTError> {
+        check_shape(self.source_weights, self.target_weights, self.cost)
+    }
+
+    fn solve(&mut self) -> Result<Array2<A>, OTError> {
+        self.check_shape()?;
+
+        check_args(self.reg, self.iterations, self.tau)?;
+
+        let (plan, _alpha, _beta, log) = sinkhorn_stabilized(
+            self.source_weights,
+            self.target_weights,
+            self.cost,
+            self.reg,
+            self.iterations,
+            self.threshold,
+            self.tau,
+            None,
+        );
+
+        self.log = log;
+
+        if self.strict {
+            self.log.check_convergence(self.threshold)?;
+        }
+
+        Ok(plan)
+    }
+
+    fn log(&self) -> &SolverLog {
+        &self.log
+    }
+}
+
+/// Solves the entropic regularization optimal transport problem with the stabilized
+/// Sinkhorn-Knopp algorithm and epsilon-scaling and returns the OT matrix
+///
+/// Stabilized sparse scaling algorithms for entropy regularized transport problems
+/// by Bernhard Schmitzer
+///
+/// The regularization term is annealed from reg_init down to reg. Each step runs the
+/// stabilized solver warm started from the dual potentials of the previous step.
+///
+/// At least 35 epsilon-scaling steps are run, such that the last steps are at the target
+/// regularization term, whatever the number of iterations.
+///
+/// ```rust
+/// use rust_optimal_transport as ot;
+/// use ot::prelude::*;
+/// use ndarray::prelude::*;
+///
+/// let source_weights = array![0.5, 0.5];
+/// let target_weights = array![0.5, 0.5];
+/// let cost = array![[0.0, 1.0], [1.0, 0.0]];
+///
+/// let ot_matrix = match SinkhornEpsilonScaling::new(
+///     &source_weights,
+///     &target_weights,
+///     &cost,
+///     1E-4,
+/// ).reg_init(1E2).solve() {
+///     Ok(result) => result,
+///     Err(error) => panic!("{:?}", error),
+/// };
+///
+/// ```
+///
+/// source_weights and target_weights represent histograms of the Source and Target distributions,
+/// respectively.
+///
+pub struct SinkhornEpsilonScaling<'a, A> {
+    source_weights: ArrayView1<'a, A>,
+    target_weights: ArrayView1<'a, A>,
+    cost: ArrayView2<'a, A>,
+    reg: A,
+    reg_init: A,
+    iterations: i32,
+    inner_iterations: i32,
+    threshold: A,
+    inner_threshold: A,
+    tau: A,
+    strict: bool,
+    log: SolverLog,
+}
+
+impl<'a, A> SinkhornEpsilonScaling<'a, A>
+where
+    A: Float + ScalarOperand,
+{
+    pub fn new<S1, S2, S3>(
+        source_weights: &'a ArrayBase<S1, Ix1>,
+        target_weights: &'a ArrayBase<S2, Ix1>,
+        cost: &'a ArrayBase<S3, Ix2>,
+        reg: A,
+    ) -> Self
+    where
+        S1: Data<Elem = A>,
+        S2: Data<Elem = A>,
+        S3: Data<Elem = A>,
+    {
+        Self {
+            source_weights: source_weights.view(),
+            target_weights: target_weights.view(),
+            cost: cost.view(),
+            reg,
+            reg_init: A::from(1E4).unwrap(),
+            iterations: 100,
+            inner_iterations: 100,
+            threshold: A::from(1E-9).unwrap(),
+            inner_threshold: A::from(1E-9).unwrap(),
+            tau: A::from(1E3).unwrap(),
+            strict: false,
+            log: SolverLog::default(),
+        }
+    }
+
+    /// Max number of epsilon-scaling steps, including the 35 steps that are always run
+    ///
+    /// Values below 35 are raised to 35.
+    pub fn iterations<'b>(&'b mut self, iterations: i32) -> &'b mut Self {
+        self.iterations = iterations;
+        self
+    }
+
+    /// Max number of stabilized Sinkhorn iterations for each epsilon-scaling step
+    pub fn inner_iterations<'b>(&'b mut self, inner_iterations: i32) -> &'b mut Self {
+        self.inner_iterations = inner_iterations;
+        self
+    }
+
+    pub fn threshold<'b>(&'b mut self, threshold: A) -> &'b mut Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Stop threshold of the stabilized Sinkhorn iterations within each epsilon-scaling step
+    pub fn inner_threshold<'b>(&'b mut self, inner_threshold: A) -> &'b mut Self {
+        self.inner_threshold = inner_threshold;
+        self
+    }
+
+    /// Returns a ConvergenceError if the stop threshold is not reached within the max number
+    /// of iterations
+    pub fn strict<'b>(&'b mut self, strict: bool) -> &'b mut Self {
+        self.strict = strict;
+        self
+    }
+
+    pub fn reg<'b>(&'b mut self, reg: A) -> &'b mut Self {
+        self.reg = reg;
+        self
+    }
+
+    /// Regularization term of the first epsilon-scaling step
+    pub fn reg_init<'b>(&'b mut self, reg_init: A) -> &'b mut Self {
+        self.reg_init = reg_init;
+        self
+    }
+
+    /// Threshold on the scalings above which they are absorbed into the dual potentials
+    pub fn tau<'b>(&'b mut self, tau: A) -> &'b mut Self {
+        self.tau = tau;
+        self
+    }
+}
+
+impl<'a, A> OTSolver for SinkhornEpsilonScaling<'a, A>
+where
+    A: Float + ScalarOperand,
+{
+    type Output = Array2<A>;
+
+    /// Ensures dimensions of the source and target measures are consistent with the
+    /// cost matrix dimensions
+    fn check_shape(&self) -> Result<(), OTError> {
+        check_shape(self.source_weights, self.target_weights, self.cost)
+    }
+
+    fn solve(&mut self) -> Result<Array2<A>, OTError> {
+        self.check_shape()?;
+
+        check_args(self.reg, self.iterations, self.tau)?;
+
+        if self.reg_init < self.reg {
+            return Err(OTError::ArgError(
+                "Initial regularization term < regularization term".to_string(),
+            ));
+        }
+
+        if self.inner_iterations <= 0 {
+            return Err(OTError::ArgError(
+                "Inner iterations not a valid value. Must be > 0".to_string(),
+            ));
+        }
+
+        let (plan, log) = sinkhorn_epsilon_scaling(
+            self.source_weights,
+            self.target_weights,
+            self.cost,
+            self.reg,
+            self.reg_init,
+            self.iterations,
+            self.inner_iterations,
+            self.threshold,
+            self.inner_threshold,
+            self.tau,
+        );
+
+        self.log = log;
+
+        if self.strict {
+            self.log.check_convergence(self.threshold)?;
+        }
+
+        Ok(plan)
+    }
+
+    fn log(&self) -> &SolverLog {
+        &self.log
+    }
+}
+
+#[allow(non_snake_case)]
+fn check_shape<A>(a: ArrayView1<A>, b: ArrayView1<A>, M: ArrayView2<A>) -> Result<(), OTError> {
+    let mshape = M.shape();
+    let m0 = mshape[0];
+    let m1 = mshape[1];
+    let dim_a = a.len();
+    let dim_b = b.len();
+
+    // Check dimensions
+    if dim_a != m0 || dim_b != m1 {
+        return Err(OTError::WeightDimensionError {
+            dim_a,
+            dim_b,
+            dim_m_0: m0,
+            dim_m_1: m1,
+        });
+    }
+
+    Ok(())
+}
+
+fn check_args<A: Float>(reg: A, iterations: i32, tau: A) -> Result<(), OTError> {
+    if reg <= A::zero() {
+        return Err(OTError::ArgError("Regularization term <= 0".to_string()));
+    }
+
+    if iterations <= 0 {
+        return Err(OTError::ArgError(
+            "Iterations not a valid value. Must be > 0".to_string(),
+        ));
+    }
+
+    if tau <= A::one() {
+        return Err(OTError::ArgError("Absorption threshold <= 1".to_string()));
+    }
+
+    Ok(())
+}
+
+/// K = exp(-(M - alpha[:, None] - beta[None, :]) / reg)
+#[allow(non_snake_case)]
+fn get_kernel<A: Float>(
+    M: ArrayView2<A>,
+    alpha: &Array1<A>,
+    beta: &Array1<A>,
+    reg: A,
+) -> Array2<A> {
+    let mut k = M.to_owned();
+    for ((i, j), ele) in k.indexed_iter_mut() {
+        *ele = (-(*ele - alpha[i] - beta[j]) / reg).exp();
+    }
+
+    k
+}
+
+/// G = exp(-(M - alpha[:, None] - beta[None, :]) / reg + log(u)[:, None] + log(v)[None, :])
+#[allow(non_snake_case)]
+fn get_plan<A: Float>(
+    M: ArrayView2<A>,
+    alpha: &Array1<A>,
+    beta: &Array1<A>,
+    u: &Array1<A>,
+    v: &Array1<A>,
+    reg: A,
+) -> Array2<A> {
+    let mut g = M.to_owned();
+    for ((i, j), ele) in g.indexed_iter_mut() {
+        *ele = (-(*ele - alpha[i] - beta[j]) / reg + u[i].ln() + v[j].ln()).exp();
+    }
+
+    g
+}
+
+/// Solves the entropic regularization optimal transport problem with the stabilized
+/// Sinkhorn-Knopp algorithm and returns the OT matrix, the dual potentials and the
+/// convergence log
+///
+/// a: Source sample weights
+/// b: Target sample weights
+/// M: Loss matrix
+/// reg: Entropy regularization term > 0
+/// iterations: Max number of iterations
+/// threshold: Stop threshold on the L1 violation of the target marginal
+/// tau: Threshold on the scalings for absorption into the dual potentials
+/// warmstart: Initial dual potentials (alpha, beta)
+#[allow(non_snake_case)]
+#[allow(clippy::too_many_arguments)]
+#[allow(clippy::type_complexity)]
+pub(crate) fn sinkhorn_stabilized<A>(
+    a: ArrayView1<A>,
+    b: ArrayView1<A>,
+    M: ArrayView2<A>,
+    reg: A,
+    iterations: i32,
+    threshold: A,
+    tau: A,
+    warmstart: Option<(&Array1<A>, &Array1<A>)>,
+) -> (Array2<A>, Array1<A>, Array1<A>, SolverLog)
+where
+    A: Float + ScalarOperand,
+{
+    let mut log = SolverLog::default();
+    let dim_a = a.len();
+    let dim_b = b.len();
+
+    let (mut alpha, mut beta) = match warmstart {
+        Some((alpha, beta)) => (alpha.clone(), beta.clone()),
+        None => (Array1::<A>::zeros(dim_a), Array1::<A>::zeros(dim_b)),
+    };
+
+    let mut u = Array1::<A>::from_elem(dim_a, A::one() / A::from(dim_a).unwrap());
+    let mut v = Array1::<A>::from_elem(dim_b, A::one() / A::from(dim_b).unwrap());
+
+    let mut k = get_kernel(M, &alpha, &beta, reg);
+
+    // Target dual potential at the last error evaluation
+    let mut g_prev = beta.clone();
+
+    for count in 0..iterations {
+        let u_prev = u.clone();
+        let v_prev = v.clone();
+
+        // v = b / K^T u
+        let ktu = k.t().dot(&u);
+        azip!((v in &mut v, &b in b, &ktu in &ktu) *v = b / ktu);
+
+        // u = a / K v
+        let kv = k.dot(&v);
+        azip!((u in &mut u, &a in a, &kv in &kv) *u = a / kv);
+
+        // Numerical errors, keep the last valid scalings
+        if u.iter().chain(v.iter()).any(|x| !x.is_finite()) {
+            u = u_prev;
+            v = v_prev;
+            break;
+        }
+
+        // Absorb large scalings into the dual potentials
+        let max_u = u.iter().fold(A::zero(), |acc, &x| acc.max(x.abs()));
+        let max_v = v.iter().fold(A::zero(), |acc, &x| acc.max(x.abs()));
+        if max_u > tau || max_v > tau {
+            azip!((alpha in &mut alpha, &u in &u) *alpha = *alpha + reg * u.ln());
+            azip!((beta in &mut beta, &v in &v) *beta = *beta + reg * v.ln());
+            u.fill(A::one() / A::from(dim_a).unwrap());
+            v.fill(A::one() / A::from(dim_b).unwrap());
+            k = get_kernel(M, &alpha, &beta, reg);
+        }
+
+        log.iterations = count + 1;
+
+        if count % 10 == 0 {
+            // Violation of the target marginal: sum(G, axis=0) = v * K^T u
+            let err = marginal_violation(&v, &k.t().dot(&u), b);
+
+            let g = &beta + &v.mapv(|v| reg * v.ln());
+            log.push((&g - &g_prev).mapv(A::abs).sum(), err, err);
+            g_prev = g;
+
+            if err < threshold {
+                log.converged = true;
+                break;
+            }
+        }
+    }
+
+    let plan = get_plan(M, &alpha, &beta, &u, &v, reg);
+
+    let alpha = &alpha + &u.mapv(|u| reg * u.ln());
+    let beta = &beta + &v.mapv(|v| reg * v.ln());
+
+    (plan, alpha, beta, log)
+}
+
+/// Minimum number of epsilon-scaling steps, such that the last steps are at the target
+/// regularization term
+const SCALING_MIN_ITERATIONS: i32 = 35;
+
+/// Solves the entropic regularization optimal transport problem with the stabilized
+/// Sinkhorn-Knopp algorithm and epsilon-scaling and returns the OT matrix
+///
+/// a: Source sample weights
+/// b: Target sample weights
+/// M: Loss matrix
+/// reg: Entropy regularization term > 0
+/// reg_init: Regularization term of the first scaling step
+/// iterations: Max number of epsilon-scaling steps
+/// inner_iterations: Max number of stabilized Sinkhorn iterations per step
+/// threshold: Stop threshold on the L1 violation of the marginals
+/// inner_threshold: Stop threshold of the stabilized Sinkhorn iterations per step
+/// tau: Threshold on the scalings for absorption into the dual potentials
+#[allow(non_snake_case)]
+#[allow(clippy::too_many_arguments)]
+fn sinkhorn_epsilon_scaling<A>(
+    a: ArrayView1<A>,
+    b: ArrayView1<A>,
+    M: ArrayView2<A>,
+    reg: A,
+    reg_init: A,
+    iterations: i32,
+    inner_iterations: i32,
+    threshold: A,
+    inner_threshold: A,
+    tau: A,
+) -> (Array2<A>, SolverLog)
+where
+    A: Float + ScalarOperand,
+{
+    let mut log = SolverLog::default();
+    let iterations = iterations.max(SCALING_MIN_ITERATIONS);
+
+    let mut alpha = Array1::<A>::zeros(a.len());
+    let mut beta = Array1::<A>::zeros(b.len());
+    let mut plan = Array2::<A>::zeros(M.dim());
+
+    for count in 0..iterations {
+        // Exponentially decreasing regularization term
+        let reg_k = (reg_init - reg) * A::from(-count).unwrap().exp() + reg;
+
+        let (plan_k, alpha_k, beta_k, _log) = sinkhorn_stabilized(
+            a,
+            b,
+            M,
+            reg_k,
+            inner_iterations,
+            inner_threshold,
+            tau,
+            Some((&alpha, &beta)),
+        );
+
+        let beta_change = (&beta_k - &beta).mapv(A::abs).sum();
+
+        plan = plan_k;
+        alpha = alpha_k;
+        beta = beta_k;
+
+        let err = (&plan.sum_axis(Axis(0)) - &b).mapv(A::abs).sum()
+            + (&plan.sum_axis(Axis(1)) - &a).mapv(A::abs).sum();
+
+        log.iterations = count + 1;
+        log.push(beta_change, err, err);
+
+        if count + 1 >= SCALING_MIN_ITERATIONS && err <= threshold {
+            log.converged = true;
+            break;
+        }
+    }
+
+    (plan, log)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use ndarray::prelude::*;
+
+    use crate::OTSolver;
+
+    #[test]
+    fn test_sinkhorn_stabilized() {
+        let a = array![0.5, 0.5];
+        let b = array![0.5, 0.5];
+        let reg = 1.0;
+        let m = array![[0.0, 1.0], [1.0, 0.0]];
+
+        let (result, _alpha, _beta, _log) =
+            super::sinkhorn_stabilized(a.view(), b.view(), m.view(), reg, 1000, 1E-9, 1E3, None);
+
+        let truth = array![[0.36552929, 0.13447071], [0.13447071, 0.36552929]];
+
+        assert!(result.relative_eq(&truth, 1E-6, 1E-2));
+    }
+
+    #[test]
+    fn test_sinkhorn_stabilized_builder() {
+        let a = array![0.5, 0.5];
+        let b = array![0.5, 0.5];
+        let reg = 1.0;
+        let m = array![[0.0, 1.0], [1.0, 0.0]];
+
+        let result = match super::SinkhornStabilized::new(&a, &b, &m, reg).solve() {
+            Ok(result) => result,
+            Err(error) => panic!("{:?}", error),
+        };
+
+        let truth = array![[0.36552929, 0.13447071], [0.13447071, 0.36552929]];
+
+        assert!(result.relative_eq(&truth, 1E-6, 1E-2));
+    }
+
+    #[test]
+    fn test_sinkhorn_stabilized_small_reg() {
+        let a = array![0.2, 0.3, 0.5];
+        let b = array![0.4, 0.4, 0.2];
+        let reg = 1E-3;
+        let m = array![[0.0, 0.5, 1.0], [0.5, 0.0, 0.5], [1.0, 0.5, 0.0]];
+
+        let result = match super::SinkhornStabilized::new(&a, &b, &m, reg)
+            .iterations(10000)
+            .solve()
+        {
+            Ok(result) => result,
+            Err(error) => panic!("{:?}", error),
+        };
+
+        assert!(!crate::ndarray_logical::is_nan(&result));
+        assert!(result.sum_axis(Axis(0)).abs_diff_eq(&b, 1E-6));
+        assert!(result.sum_axis(Axis(1)).abs_diff_eq(&a, 1E-6));
+    }
+
+    #[test]
+    fn test_sinkhorn_epsilon_scaling_builder() {
+        let a = array![0.5, 0.5];
+        let b = array![0.5, 0.5];
+        let reg = 1.0;
+        let m = array![[0.0, 1.0], [1.0, 0.0]];
+
+        let result = match super::SinkhornEpsilonScaling::new(&a, &b, &m, reg)
+            .reg_init(1E2)
+            .solve()
+        {
+            Ok(result) => result,
+            Err(error) => panic!("{:?}", error),
+        };
+
+        let truth = array![[0.36552929, 0.13447071], [0.13447071, 0.36552929]];
+
+        assert!(result.relative_eq(&truth, 1E-6, 1E-2));
+    }
+
+    #[test]
+    fn test_sinkhorn_epsilon_scaling_small_reg() {
+        let a = array![0.5, 0.5];
+        let b = array![0.5, 0.5];
+        let reg = 1E-4;
+        let m = array![[0.0, 1.0], [1.0, 0.0]];
+
+        let result = match super::SinkhornEpsilonScaling::new(&a, &b, &m, reg).solve() {
+            Ok(result) => result,
+            Err(error) => panic!("{:?}", error),
+        };
+
+        let truth = array![[0.5, 0.0], [0.0, 0.5]];
+
+        assert!(result.abs_diff_eq(&truth, 1E-9));
+    }
+
+    #[test]
+    fn test_sinkhorn_epsilon_scaling_min_iterations() {
+        let a = array![0.5, 0.5];
+        let b = array![0.5, 0.5];
+        let reg = 1.0;
+        let m = array![[0.0, 1.0], [1.0, 0.0]];
+
+        let mut solver = super::SinkhornEpsilonScaling::new(&a, &b, &m, reg);
+        solver.reg_init(1E2).iterations(5).inner_threshold(1E-12);
+
+        let result = match solver.solve() {
+            Ok(result) => result,
+            Err(error) => panic!("{:?}", error),
+        };
+
+        let truth = array![[0.36552929, 0.13447071], [0.13447071, 0.36552929]];
+
+        assert!(result.relative_eq(&truth, 1E-6, 1E-2));
+        assert_eq!(solver.log().iterations, 35);
+        assert!(solver.log().converged);
+
+        // Convergence is reported to strict mode
+        assert!(solver.strict(true).solve().is_ok());
+    }
+}
@@ -0,0 +1,618 @@
+use ndarray::prelude::*;
+use ndarray::{Data, ScalarOperand};
+use ndarray_rand::rand::rngs::StdRng;
+use ndarray_rand::rand::{Rng, SeedableRng};
+use ndarray_rand::rand_distr::StandardNormal;
+use num_traits::Float;
+
+use crate::error::OTError;
+use crate::exact::solver_1d::{check_positions, check_weights, emd_1d, emd_1d_loss};
+use crate::{OTSolver, SolverLog};
+
+/// Computes the sliced Wasserstein distance between two weighted point clouds
+///
+/// SW_p(a, b) = (E_theta[ W_p^p(theta # a, theta # b) ])^(1/p)
+///
+/// The expectation over the unit sphere is estimated with random projection directions. Each
+/// projection is a 1D Optimal Transport problem solved by sorting, the n x m cost matrix is
+/// never built.
+///
+/// Sliced and Radon Wasserstein Barycenters of Measures
+/// by Nicolas Bonneel, Julien Rabin, Gabriel Peyré, Hanspeter Pfister
+///
+/// ```rust
+/// use rust_optimal_transport as ot;
+/// use ot::prelude::*;
+/// use ndarray::prelude::*;
+///
+/// let n = 100;
+///
+/// let mu_source = array![0., 0.];
+/// let cov_source = array![[1., 0.], [0., 1.]];
+///
+/// let mu_target = array![4., 4.];
+/// let cov_target = array![[1., -0.8], [-0.8, 1.]];
+///
+/// let source = ot::utils::sample_2D_gauss(n, &mu_source, &cov_source).unwrap();
+/// let target = ot::utils::sample_2D_gauss(n, &mu_target, &cov_target).unwrap();
+///
+/// // Uniform weights on the source and target distributions
+/// let uniform = Array1::<f64>::zeros(0);
+///
+/// let distance = match SlicedWasserstein::new(&source, &target, &uniform, &uniform)
+///     .n_projections(50)
+///     .seed(42)
+///     .solve()
+/// {
+///     Ok(result) => result,
+///     Err(error) => panic!("{:?}", error),
+/// };
+///
+/// ```
+///
+/// source_weights and target_weights represent histograms of the Source and Target
+/// distributions, uniform if empty. The target weights are rescaled to the mass of the source.
+///
+pub struct SlicedWasserstein<'a, A> {
+    source_samples: ArrayView2<'a, A>,
+    target_samples: ArrayView2<'a, A>,
+    source_weights: ArrayView1<'a, A>,
+    target_weights: ArrayView1<'a, A>,
+    n_projections: usize,
+    p: A,
+    seed: Option<u64>,
+    log: SolverLog,
+}
+
+impl<'a, A> SlicedWasserstein<'a, A>
+where
+    A: Float + ScalarOperand,
+{
+    pub fn new<S1, S2, S3, S4>(
+        source_samples: &'a ArrayBase<S1, Ix2>,
+        target_samples: &'a ArrayBase<S2, Ix2>,
+        source_weights: &'a ArrayBase<S3, Ix1>,
+        target_weights: &'a ArrayBase<S4, Ix1>,
+    ) -> Self
+    where
+        S1: Data<Elem = A>,
+        S2: Data<Elem = A>,
+        S3: Data<Elem = A>,
+        S4: Data<Elem = A>,
+    {
+        Self {
+            source_samples: source_samples.view(),
+            target_samples: target_samples.view(),
+            source_weights: source_weights.view(),
+            target_weights: target_weights.view(),
+            n_projections: 50,
+            p: A::from(2.).unwrap(),
+            seed: None,
+            log: SolverLog::default(),
+        }
+    }
+
+    /// Number of random projection directions
+    pub fn n_projections<'b>(&'b mut self, n_projections: usize) -> &'b mut Self {
+        self.n_projections = n_projections;
+        self
+    }
+
+    /// Power of the ground cost |x - y|^p
+    pub fn p<'b>(&'b mut self, p: A) -> &'b mut Self {
+        self.p = p;
+        self
+    }
+
+    /// Seed of the random projection directions, drawn from system entropy if not set
+    pub fn seed<'b>(&'b mut self, seed: u64) -> &'b mut Self {
+        self.seed = Some(seed);
+        self
+    }
+}
+
+impl<'a, A> OTSolver for SlicedWasserstein<'a, A>
+where
+    A: Float + ScalarOperand,
+{
+    type Output = A;
+
+    /// Ensures the number of samples are consistent with the sample weights and that both
+    /// point clouds have the same number of features
+    fn check_shape(&self) -> Result<(), OTError> {
+        check_shape(
+            self.source_samples,
+            self.target_samples,
+            self.source_weights,
+            self.target_weights,
+        )
+    }
+
+    fn solve(&mut self) -> Result<A, OTError> {
+        self.check_shape()?;
+
+        if self.n_projections == 0 {
+            return Err(OTError::ArgError(
+                "Number of projections not a valid value. Must be > 0".to_string(),
+            ));
+        }
+
+        let (xs, xt) = (self.source_samples, self.target_samples);
+        let (a, b) = check_weights(
+            xs.nrows(),
+            xt.nrows(),
+            self.source_weights,
+            self.target_weights,
+            self.p,
+        )?;
+        check_positions(xs.iter().chain(xt.iter()))?;
+
+        let directions = random_directions(xs.ncols(), self.n_projections, self.seed);
+
+        let mut loss = A::zero();
+        for theta in directions.axis_iter(Axis(1)) {
+            let (proj_s, proj_t) = (xs.dot(&theta), xt.dot(&theta));
+            loss = loss + emd_1d_loss(proj_s.view(), proj_t.view(), &a, &b, self.p);
+        }
+
+        self.log = SolverLog {
+            iterations: self.n_projections as i32,
+            converged: true,
+            ..SolverLog::default()
+        };
+
+        Ok((loss / A::from(self.n_projections).unwrap()).powf(self.p.recip()))
+    }
+
+    fn log(&self) -> &SolverLog {
+        &self.log
+    }
+}
+
+/// Computes the max-sliced Wasserstein distance between two weighted point clouds
+///
+/// MSW_p(a, b) = max_theta W_p(theta # a, theta # b)
+///
+/// The search starts from the best of n_projections random directions and refines it by
+/// projected gradient ascent on the unit sphere. The gradient is computed from the sparse 1D
+/// OT plan of the current direction, the n x m cost matrix is never built.
+///
+/// Max-Sliced Wasserstein Distance and its use for GANs
+/// by Ishan Deshpande, Yuan-Ting Hu, Ruoyu Sun, Ayis Pyrros, Nasir Siddiqui, Sanmi Koyejo,
+/// Zhizhen Zhao, David Forsyth, Alexander Schwing
+///
+/// ```rust
+/// use rust_optimal_transport as ot;
+/// use ot::prelude::*;
+/// use ndarray::prelude::*;
+///
+/// // Point clouds differing along the second axis only
+/// let source = array![[0., 0.], [1., 0.], [2., 0.]];
+/// let target = array![[0., 3.], [1., 3.], [2., 3.]];
+///
+/// let uniform = Array1::<f64>::zeros(0);
+///
+/// let distance = match MaxSlicedWasserstein::new(&source, &target, &uniform, &uniform)
+///     .seed(42)
+///     .solve()
+/// {
+///     Ok(result) => result,
+///     Err(error) => panic!("{:?}", error),
+/// };
+///
+/// assert!((distance - 3.).abs() < 1E-6);
+///
+/// ```
+///
+/// source_weights and target_weights represent histograms of the Source and Target
+/// distributions, uniform if empty. The target weights are rescaled to the mass of the source.
+///
+pub struct MaxSlicedWasserstein<'a, A> {
+    source_samples: ArrayView2<'a, A>,
+    target_samples: ArrayView2<'a, A>,
+    source_weights: ArrayView1<'a, A>,
+    target_weights: ArrayView1<'a, A>,
+    n_projections: usize,
+    p: A,
+    seed: Option<u64>,
+    iterations: i32,
+    threshold: A,
+    log: SolverLog,
+}
+
+impl<'a, A> MaxSlicedWasserstein<'a, A>
+where
+    A: Float + ScalarOperand,
+{
+    pub fn new<S1, S2, S3, S4>(
+        source_samples: &'a ArrayBase<S1, Ix2>,
+        target_samples: &'a ArrayBase<S2, Ix2>,
+        source_weights: &'a ArrayBase<S3, Ix1>,
+        target_weights: &'a ArrayBase<S4, Ix1>,
+    ) -> Self
+    where
+        S1: Data<Elem = A>,
+        S2: Data<Elem = A>,
+        S3: Data<Elem = A>,
+        S4: Data<Elem = A>,
+    {
+        Self {
+            source_samples: source_samples.view(),
+            target_samples: target_samples.view(),
+            source_weights: source_weights.view(),
+            target_weights: target_weights.view(),
+            n_projections: 50,
+            p: A::from(2.).unwrap(),
+            seed: None,
+            iterations: 100,
+            threshold: A::from(1E-9).unwrap(),
+            log: SolverLog::default(),
+        }
+    }
+
+    /// Number of random directions the search starts from
+    pub fn n_projections<'b>(&'b mut self, n_projections: usize) -> &'b mut Self {
+        self.n_projections = n_projections;
+        self
+    }
+
+    /// Power of the ground cost |x - y|^p
+    pub fn p<'b>(&'b mut self, p: A) -> &'b mut Self {
+        self.p = p;
+        self
+    }
+
+    /// Seed of the random projection directions, drawn from system entropy if not set
+    pub fn seed<'b>(&'b mut self, seed: u64) -> &'b mut Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Max number of gradient ascent iterations
+    pub fn iterations<'b>(&'b mut self, iterations: i32) -> &'b mut Self {
+        self.iterations = iterations;
+        self
+    }
+
+    /// Stop threshold on the step size of the gradient ascent
+    pub fn threshold<'b>(&'b mut self, threshold: A) -> &'b mut Self {
+        self.threshold = threshold;
+        self
+    }
+}
+
+impl<'a, A> OTSolver for MaxSlicedWasserstein<'a, A>
+where
+    A: Float + ScalarOperand,
+{
+    type Output = A;
+
+    /// Ensures the number of samples are consistent with the sample weights and that both
+    /// point clouds have the same number of features
+    fn check_shape(&self) -> Result<(), OTError> {
+        check_shape(
+            self.source_samples,
+            self.target_samples,
+            self.source_weights,
+            self.target_weights,
+        )
+    }
+
+    fn solve(&mut self) -> Result<A, OTError> {
+        self.check_shape()?;
+
+        if self.n_projections == 0 {
+            return Err(OTError::ArgError(
+                "Number of projections not a valid value. Must be > 0".to_string(),
+            ));
+        }
+
+        if self.iterations < 0 {
+            return Err(OTError::ArgError(
+                "Iterations not a valid value. Must be >= 0".to_string(),
+            ));
+        }
+
+        let (xs, xt) = (self.source_samples, self.target_samples);
+        let (a, b) = check_weights(
+            xs.nrows(),
+            xt.nrows(),
+            self.source_weights,
+            self.target_weights,
+            self.p,
+        )?;
+        check_positions(xs.iter().chain(xt.iter()))?;
+
+        let (loss, log) = max_sliced(
+            xs,
+            xt,
+            &a,
+            &b,
+            self.p,
+            self.n_projections,
+            self.seed,
+            self.iterations,
+            self.threshold,
+        )?;
+
+        self.log = log;
+
+        Ok(loss.powf(self.p.recip()))
+    }
+
+    fn log(&self) -> &SolverLog {
+        &self.log
+    }
+}
+
+fn check_shape<A>(
+    xs: ArrayView2<A>,
+    xt: ArrayView2<A>,
+    a: ArrayView1<A>,
+    b: ArrayView1<A>,
+) -> Result<(), OTError> {
+    let dim_a = a.len();
+    let dim_b = b.len();
+    let n_source = xs.nrows();
+    let n_target = xt.nrows();
+
+    // Empty weights stand for uniform weights
+    if (dim_a != 0 && dim_a != n_source) || (dim_b != 0 && dim_b != n_target) {
+        return Err(OTError::WeightDimensionError {
+            dim_a,
+            dim_b,
+            dim_m_0: n_source,
+            dim_m_1: n_target,
+        });
+    }
+
+    if xs.ncols() != xt.ncols() {
+        return Err(OTError::ArgError(
+            "Source and target samples have a different number of features".to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Draws n_projections directions uniformly on the unit sphere of dimension d and returns
+/// them as the columns of a (d, n_projections) array
+fn random_directions<A: Float>(d: usize, n_projections: usize, seed: Option<u64>) -> Array2<A> {
+    let mut rng = match seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    };
+
+    let mut directions = Array2::<A>::zeros((d, n_projections));
+    for mut theta in directions.axis_iter_mut(Axis(1)) {
+        theta.mapv_inplace(|_| A::from(rng.sample::<f64, _>(StandardNormal)).unwrap());
+        let norm = theta.fold(A::zero(), |acc, &x| acc + x * x).sqrt();
+        theta.mapv_inplace(|x| x / norm);
+    }
+
+    directions
+}
+
+/// Maximizes the 1D Optimal Transport loss over the projection directions and returns the
+/// maximal loss W_p^p and the log of the gradient ascent
+///
+/// xs: Source samples
+/// xt: Target samples
+/// a: Source sample weights
+/// b: Target sample weights, same total mass as a
+/// p: Power of the ground cost
+/// n_projections: Number of random initial directions
+/// seed: Seed of the random directions
+/// iterations: Max number of gradient ascent iterations
+/// threshold: Stop threshold on the step size
+#[allow(clippy::too_many_arguments)]
+fn max_sliced<A>(
+    xs: ArrayView2<A>,
+    xt: ArrayView2<A>,
+    a: &Array1<A>,
+    b: &Array1<A>,
+    p: A,
+    n_projections: usize,
+    seed: Option<u64>,
+    iterations: i32,
+    threshold: A,
+) -> Result<(A, SolverLog), OTError>
+where
+    A: Float + ScalarOperand,
+{
+    let mut log = SolverLog::default();
+    let projected_loss =
+        |theta: &Array1<A>| emd_1d_loss(xs.dot(theta).view(), xt.dot(theta).view(), a, b, p);
+
+    // Best random direction
+    let directions = random_directions::<A>(xs.ncols(), n_projections, seed);
+    let mut theta = directions.column(0).to_owned();
+    let mut loss = projected_loss(&theta);
+    for direction in directions.axis_iter(Axis(1)).skip(1) {
+        let direction = direction.to_owned();
+        let direction_loss = projected_loss(&direction);
+        if direction_loss > loss {
+            theta = direction;
+            loss = direction_loss;
+        }
+    }
+
+    // Projected gradient ascent on the unit sphere, the step is halved on every rejected move
+    let mut step = A::from(0.5).unwrap();
+    for count in 0..iterations {
+        if step < threshold {
+            log.converged = true;
+            break;
+        }
+
+        log.iterations = count + 1;
+
+        let grad = loss_gradient(xs, xt, a, b, p, &theta)?;
+        let grad_norm = grad.fold(A::zero(), |acc, &x| acc + x * x).sqrt();
+        if grad_norm == A::zero() {
+            log.converged = true;
+            break;
+        }
+
+        let mut candidate = &theta + &(grad * (step / grad_norm));
+        let norm = candidate.fold(A::zero(), |acc, &x| acc + x * x).sqrt();
+        candidate.mapv_inplace(|x| x / norm);
+
+        let candidate_loss = projected_loss(&candidate);
+        if candidate_loss > loss {
+            let change = (&candidate - &theta).mapv(A::abs).sum();
+            theta = candidate;
+            loss = candidate_loss;
+            log.push_change(change, step);
+        } else {
+            step = step / A::from(2.).unwrap();
+        }
+    }
+
+    if step < threshold {
+        log.converged = true;
+    }
+
+    Ok((loss, log))
+}
+
+/// Gradient of theta -> sum_ij G_ij |<theta, x_i - y_j>|^p for the 1D OT plan G of the
+/// current projection
+///
+/// Pairs with a zero projected difference have a zero subgradient, they are skipped since
+/// |diff|^(p - 1) is infinite for p < 1.
+fn loss_gradient<A>(
+    xs: ArrayView2<A>,
+    xt: ArrayView2<A>,
+    a: &Array1<A>,
+    b: &Array1<A>,
+    p: A,
+    theta: &Array1<A>,
+) -> Result<Array1<A>, OTError>
+where
+    A: Float + ScalarOperand,
+{
+    let (proj_s, proj_t) = (xs.dot(theta), xt.dot(theta));
+    let plan = emd_1d(&proj_s, &proj_t, a, b, p)?;
+
+    let mut grad = Array1::<A>::zeros(theta.len());
+    for ((&i, &j), &mass) in plan.rows.iter().zip(&plan.cols).zip(&plan.values) {
+        let diff = proj_s[i] - proj_t[j];
+        if diff == A::zero() {
+            continue;
+        }
+
+        let scale = mass * p * diff.abs().powf(p - A::one()) * diff.signum();
+        azip!((g in &mut grad, &x in &xs.row(i), &y in &xt.row(j)) *g = *g + scale * (x - y));
+    }
+
+    Ok(grad)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use ndarray::prelude::*;
+
+    use crate::OTSolver;
+
+    #[test]
+    fn test_sliced_wasserstein() {
+        let source = array![[0., 0.], [1., 0.], [0., 1.]];
+        let target = array![[2., 2.], [3., 2.], [2., 3.]];
+        let uniform = Array1::<f64>::zeros(0);
+
+        let distance = match super::SlicedWasserstein::new(&source, &target, &uniform, &uniform)
+            .seed(0)
+            .solve()
+        {
+            Ok(result) => result,
+            Err(error) => panic!("{:?}", error),
+        };
+
+        // Translation by (2, 2): the projected distances are |<theta, (2, 2)>|
+        assert!(distance > 0. && distance <= 8f64.sqrt());
+
+        // Same seed, same directions
+        let repeated = match super::SlicedWasserstein::new(&source, &target, &uniform, &uniform)
+            .seed(0)
+            .solve()
+        {
+            Ok(result) => result,
+            Err(error) => panic!("{:?}", error),
+        };
+
+        assert_eq!(distance, repeated);
+
+        // Identical point clouds
+        let distance = match super::SlicedWasserstein::new(&source, &source, &uniform, &uniform)
+            .seed(0)
+            .solve()
+        {
+            Ok(result) => result,
+            Err(error) => panic!("{:?}", error),
+        };
+
+        assert!(distance.abs() < 1E-12);
+
+        // NaN samples cannot be sorted once projected
+        let target = array![[2., 2.], [f64::NAN, 2.], [2., 3.]];
+        assert!(
+            super::SlicedWasserstein::new(&source, &target, &uniform, &uniform)
+                .solve()
+                .is_err()
+        );
+        assert!(
+            super::MaxSlicedWasserstein::new(&source, &target, &uniform, &uniform)
+                .solve()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_max_sliced_wasserstein() {
+        let source = array![[0., 0.], [1., 0.], [0., 1.]];
+        let target = array![[2., 2.], [3., 2.], [2., 3.]];
+        let uniform = Array1::<f64>::zeros(0);
+
+        let mut solver = super::MaxSlicedWasserstein::new(&source, &target, &uniform, &uniform);
+        let distance = match solver.n_projections(5).seed(0).solve() {
+            Ok(result) => result,
+            Err(error) => panic!("{:?}", error),
+        };
+
+        // The worst direction is along the translation (2, 2)
+        assert!((distance - 8f64.sqrt()).abs() < 1E-6);
+        assert!(solver.log().converged);
+
+        // The projections are not constrained by marginals
+        assert!(solver.log().marginal_violation.is_empty());
+
+        // The first samples coincide, their projected difference is zero in every direction
+        let target = array![[0., 0.], [3., 2.], [2., 3.]];
+        let mut solver = super::MaxSlicedWasserstein::new(&source, &target, &uniform, &uniform);
+        let distance = match solver.n_projections(5).seed(0).p(0.5).solve() {
+            Ok(result) => result,
+            Err(error) => panic!("{:?}", error),
+        };
+
+        assert!(distance.is_finite() && distance > 0.);
+        assert!(solver.log().converged);
+
+        let weights = Array1::from_elem(3, 1. / 3.);
+        let theta = array![1., 1.] / 2f64.sqrt();
+        let grad = match super::loss_gradient(
+            source.view(),
+            target.view(),
+            &weights,
+            &weights,
+            0.5,
+            &theta,
+        ) {
+            Ok(result) => result,
+            Err(error) => panic!("{:?}", error),
+        };
+
+        assert!(grad.iter().all(|g| g.is_finite()));
+    }
+}
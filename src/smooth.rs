@@ -0,0 +1,504 @@
+use ndarray::prelude::*;
+use ndarray::{concatenate, Data, ScalarOperand, Zip};
+use num_traits::Float;
+use std::cmp::Ordering;
+
+use crate::error::OTError;
+use crate::optim::lbfgs::lbfgs;
+use crate::{OTSolver, SolverLog};
+
+/// Sparse OT matrix stored in coordinate format
+#[derive(Debug, Clone)]
+pub struct SparsePlan<A> {
+    /// Source sample index of each non-zero entry of the plan
+    pub rows: Vec<usize>,
+    /// Target sample index of each non-zero entry of the plan
+    pub cols: Vec<usize>,
+    /// Transported mass of each non-zero entry of the plan
+    pub values: Vec<A>,
+    /// Number of source samples
+    pub dim_a: usize,
+    /// Number of target samples
+    pub dim_b: usize,
+}
+
+impl<A: Float> SparsePlan<A> {
+    /// Returns the OT matrix as a dense (dim_a, dim_b) array
+    pub fn to_dense(&self) -> Array2<A> {
+        let mut plan = Array2::<A>::zeros((self.dim_a, self.dim_b));
+        for ((&i, &j), &value) in self.rows.iter().zip(&self.cols).zip(&self.values) {
+            plan[(i, j)] = plan[(i, j)] + value;
+        }
+
+        plan
+    }
+}
+
+/// OT matrix returned by the smooth OT solvers, dense or in coordinate format depending on its
+/// number of non-zero entries
+#[derive(Debug, Clone)]
+pub enum SmoothPlan<A> {
+    Dense(Array2<A>),
+    Sparse(SparsePlan<A>),
+}
+
+impl<A: Float> SmoothPlan<A> {
+    /// Returns the OT matrix as a dense (dim_a, dim_b) array
+    pub fn to_dense(&self) -> Array2<A> {
+        match self {
+            SmoothPlan::Dense(plan) => plan.clone(),
+            SmoothPlan::Sparse(plan) => plan.to_dense(),
+        }
+    }
+}
+
+/// Solution of the smooth Optimal Transport problem
+#[derive(Debug, Clone)]
+pub struct SmoothOTResult<A> {
+    /// OT matrix G = max(alpha_i + beta_j - M_ij, 0) / reg
+    pub plan: SmoothPlan<A>,
+    /// Source dual potential
+    pub alpha: Array1<A>,
+    /// Target dual potential
+    pub beta: Array1<A>,
+}
+
+/// Solves the smooth Optimal Transport problem min <G, M> + reg ||G||^2 / 2 and returns the
+/// OT matrix and the dual potentials
+///
+/// Unlike the entropic regularization, the squared L2 regularization yields sparse plans. The
+/// problem is solved on its dual, or on its semi-dual where the target potential is obtained in
+/// closed form by projections on the simplex, with the L-BFGS quasi-Newton method.
+///
+/// Smooth and Sparse Optimal Transport
+/// by Mathieu Blondel, Vivien Seguy, Antoine Rolet
+///
+/// ```rust
+/// use rust_optimal_transport as ot;
+/// use ot::prelude::*;
+/// use ndarray::prelude::*;
+/// use ndarray_stats::QuantileExt;
+///
+/// // Generate data
+/// let n = 50;
+///
+/// let cov = array![[1., 0.], [0., 1.]];
+/// let source = ot::utils::sample_2D_gauss(n, &array![0., 0.], &cov).unwrap();
+/// let target = ot::utils::sample_2D_gauss(n, &array![4., 4.], &cov).unwrap();
+///
+/// let source_weights = Array1::<f64>::from_elem(n, 1. / (n as f64));
+/// let target_weights = Array1::<f64>::from_elem(n, 1. / (n as f64));
+///
+/// // Normalize cost matrix for numerical stability
+/// let mut cost = dist(&source, &target, SqEuclidean);
+/// cost = &cost / *cost.max().unwrap();
+///
+/// let result = match SmoothOT::new(
+///     &source_weights,
+///     &target_weights,
+///     &cost,
+///     1E-2,
+/// )
+/// .semi_dual(true)
+/// .solve()
+/// {
+///     Ok(result) => result,
+///     Err(error) => panic!("{:?}", error),
+/// };
+///
+/// let ot_matrix = result.plan.to_dense();
+///
+/// ```
+///
+/// source_weights and target_weights represent histograms of the Source and Target distributions,
+/// respectively.
+///
+/// The target weights are rescaled to the total mass of the source weights on an internal copy.
+///
+pub struct SmoothOT<'a, A> {
+    source_weights: ArrayView1<'a, A>,
+    target_weights: ArrayView1<'a, A>,
+    cost: ArrayView2<'a, A>,
+    reg: A,
+    semi_dual: bool,
+    iterations: i32,
+    threshold: A,
+    max_density: A,
+    strict: bool,
+    log: SolverLog,
+}
+
+impl<'a, A> SmoothOT<'a, A>
+where
+    A: Float + ScalarOperand,
+{
+    pub fn new<S1, S2, S3>(
+        source_weights: &'a ArrayBase<S1, Ix1>,
+        target_weights: &'a ArrayBase<S2, Ix1>,
+        cost: &'a ArrayBase<S3, Ix2>,
+        reg: A,
+    ) -> Self
+    where
+        S1: Data<Elem = A>,
+        S2: Data<Elem = A>,
+        S3: Data<Elem = A>,
+    {
+        Self {
+            source_weights: source_weights.view(),
+            target_weights: target_weights.view(),
+            cost: cost.view(),
+            reg,
+            semi_dual: false,
+            iterations: 500,
+            threshold: A::from(1E-9).unwrap(),
+            max_density: A::from(1. / 3.).unwrap(),
+            strict: false,
+            log: SolverLog::default(),
+        }
+    }
+
+    /// Solves the semi-dual instead of the dual, requires positive target weights
+    pub fn semi_dual<'b>(&'b mut self, semi_dual: bool) -> &'b mut Self {
+        self.semi_dual = semi_dual;
+        self
+    }
+
+    pub fn iterations<'b>(&'b mut self, iterations: i32) -> &'b mut Self {
+        self.iterations = iterations;
+        self
+    }
+
+    /// Stop threshold on the largest violation of the marginal constraints
+    pub fn threshold<'b>(&'b mut self, threshold: A) -> &'b mut Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Plans with at most this fraction of non-zero entries are returned in coordinate format,
+    /// 1/3 by default
+    pub fn max_density<'b>(&'b mut self, max_density: A) -> &'b mut Self {
+        self.max_density = max_density;
+        self
+    }
+
+    /// Returns a ConvergenceError if the stop threshold is not reached within the max number
+    /// of iterations
+    pub fn strict<'b>(&'b mut self, strict: bool) -> &'b mut Self {
+        self.strict = strict;
+        self
+    }
+
+    pub fn reg<'b>(&'b mut self, reg: A) -> &'b mut Self {
+        self.reg = reg;
+        self
+    }
+}
+
+impl<'a, A> OTSolver for SmoothOT<'a, A>
+where
+    A: Float + ScalarOperand,
+{
+    type Output = SmoothOTResult<A>;
+
+    /// Ensures dimensions of the source and target measures are consistent with the
+    /// cost matrix dimensions
+    fn check_shape(&self) -> Result<(), OTError> {
+        let mshape = self.cost.shape();
+        let m0 = mshape[0];
+        let m1 = mshape[1];
+        let dim_a = self.source_weights.len();
+        let dim_b = self.target_weights.len();
+
+        if dim_a != m0 || dim_b != m1 {
+            return Err(OTError::WeightDimensionError {
+                dim_a,
+                dim_b,
+                dim_m_0: m0,
+                dim_m_1: m1,
+            });
+        }
+
+        Ok(())
+    }
+
+    #[allow(non_snake_case)]
+    fn solve(&mut self) -> Result<SmoothOTResult<A>, OTError> {
+        self.check_shape()?;
+
+        if self.reg <= A::zero() {
+            return Err(OTError::ArgError("Regularization term <= 0".to_string()));
+        }
+
+        if self.iterations <= 0 {
+            return Err(OTError::ArgError(
+                "Iterations not a valid value. Must be > 0".to_string(),
+            ));
+        }
+
+        let a = self.source_weights.to_owned();
+        let scale = a.sum() / self.target_weights.sum();
+        let b = self.target_weights.mapv(|w| w * scale);
+
+        if self.semi_dual && b.iter().any(|&w| w <= A::zero()) {
+            return Err(OTError::ArgError(
+                "Semi-dual requires positive target weights".to_string(),
+            ));
+        }
+
+        let (M, reg) = (self.cost, self.reg);
+        let dim_a = a.len();
+
+        let (alpha, beta, iterations, converged) = if self.semi_dual {
+            let objective = |alpha: &Array1<A>| semi_dual_obj_grad(alpha, &a, &b, M, reg);
+            let result = lbfgs(
+                objective,
+                Array1::zeros(dim_a),
+                self.iterations,
+                self.threshold,
+                10,
+            );
+            let beta = semi_dual_beta(&result.x, &b, M, reg);
+
+            (result.x, beta, result.iterations, result.converged)
+        } else {
+            let objective = |x: &Array1<A>| dual_obj_grad(x, &a, &b, M, reg);
+            let result = lbfgs(
+                objective,
+                Array1::zeros(dim_a + b.len()),
+                self.iterations,
+                self.threshold,
+                10,
+            );
+
+            (
+                result.x.slice(s![..dim_a]).to_owned(),
+                result.x.slice(s![dim_a..]).to_owned(),
+                result.iterations,
+                result.converged,
+            )
+        };
+
+        let plan = plan_from_potentials(&alpha, &beta, M, reg);
+
+        // L1 and largest violations of the marginal constraints, L-BFGS does not report the
+        // change of its iterates
+        let deviations = concatenate![
+            Axis(0),
+            &plan.sum_axis(Axis(1)) - &a,
+            &plan.sum_axis(Axis(0)) - &b
+        ];
+        let violation = deviations.mapv(A::abs).sum();
+        let max_violation = deviations.fold(A::zero(), |acc, &x| acc.max(x.abs()));
+
+        self.log = SolverLog {
+            iterations,
+            marginal_violation: vec![violation.to_f64().unwrap()],
+            error: max_violation.to_f64().unwrap(),
+            converged,
+            ..SolverLog::default()
+        };
+
+        if self.strict {
+            self.log.check_convergence(self.threshold)?;
+        }
+
+        let nnz = plan.iter().filter(|&&g| g > A::zero()).count();
+        let density = A::from(nnz).unwrap() / A::from(plan.len()).unwrap();
+
+        let plan = if density <= self.max_density {
+            let mut sparse = SparsePlan {
+                rows: Vec::with_capacity(nnz),
+                cols: Vec::with_capacity(nnz),
+                values: Vec::with_capacity(nnz),
+                dim_a,
+                dim_b: b.len(),
+            };
+
+            for ((i, j), &g) in plan.indexed_iter() {
+                if g > A::zero() {
+                    sparse.rows.push(i);
+                    sparse.cols.push(j);
+                    sparse.values.push(g);
+                }
+            }
+
+            SmoothPlan::Sparse(sparse)
+        } else {
+            SmoothPlan::Dense(plan)
+        };
+
+        Ok(SmoothOTResult { plan, alpha, beta })
+    }
+
+    fn log(&self) -> &SolverLog {
+        &self.log
+    }
+}
+
+/// G = max(alpha_i + beta_j - M_ij, 0) / reg
+#[allow(non_snake_case)]
+fn plan_from_potentials<A>(
+    alpha: &Array1<A>,
+    beta: &Array1<A>,
+    M: ArrayView2<A>,
+    reg: A,
+) -> Array2<A>
+where
+    A: Float + ScalarOperand,
+{
+    let potentials = &alpha.view().insert_axis(Axis(1)) + &beta.view().insert_axis(Axis(0));
+
+    (&potentials - &M).mapv_into(|x| x.max(A::zero()) / reg)
+}
+
+/// Negated dual objective and its gradient at x = (alpha, beta)
+///
+/// -<alpha, a> - <beta, b> + sum(max(alpha_i + beta_j - M_ij, 0)^2) / (2 reg)
+#[allow(non_snake_case)]
+fn dual_obj_grad<A>(
+    x: &Array1<A>,
+    a: &Array1<A>,
+    b: &Array1<A>,
+    M: ArrayView2<A>,
+    reg: A,
+) -> (A, Array1<A>)
+where
+    A: Float + ScalarOperand,
+{
+    let alpha = x.slice(s![..a.len()]).to_owned();
+    let beta = x.slice(s![a.len()..]).to_owned();
+    let plan = plan_from_potentials(&alpha, &beta, M, reg);
+
+    let half = A::from(0.5).unwrap();
+    let value =
+        plan.fold(A::zero(), |acc, &g| acc + g * g) * reg * half - alpha.dot(a) - beta.dot(b);
+
+    let grad = concatenate![
+        Axis(0),
+        plan.sum_axis(Axis(1)) - a,
+        plan.sum_axis(Axis(0)) - b
+    ];
+
+    (value, grad)
+}
+
+/// Negated semi-dual objective and its gradient at alpha
+///
+/// -<alpha, a> + sum_j b_j max_Omega(alpha - M_:j), with max_Omega the smoothed max over the
+/// simplex scaled by b_j
+#[allow(non_snake_case)]
+fn semi_dual_obj_grad<A>(
+    alpha: &Array1<A>,
+    a: &Array1<A>,
+    b: &Array1<A>,
+    M: ArrayView2<A>,
+    reg: A,
+) -> (A, Array1<A>)
+where
+    A: Float + ScalarOperand,
+{
+    let half = A::from(0.5).unwrap();
+    let beta = semi_dual_beta(alpha, b, M, reg);
+    let plan = plan_from_potentials(alpha, &beta, M, reg);
+
+    // b_j max_Omega(X_:j) = <X_:j, G_:j> - reg ||G_:j||^2 / 2 with X = alpha - M
+    let x = &alpha.view().insert_axis(Axis(1)) - &M;
+    let value = Zip::from(&x).and(&plan).fold(-alpha.dot(a), |acc, &x, &g| {
+        acc + g * x - reg * half * g * g
+    });
+
+    (value, plan.sum_axis(Axis(1)) - a)
+}
+
+/// Target potential maximizing the dual for a fixed source potential
+///
+/// Each column of the plan is b_j times the projection of (alpha - M_:j) / (reg b_j) on the
+/// simplex, beta_j = -reg b_j theta_j with theta_j the threshold of the projection.
+#[allow(non_snake_case)]
+fn semi_dual_beta<A>(alpha: &Array1<A>, b: &Array1<A>, M: ArrayView2<A>, reg: A) -> Array1<A>
+where
+    A: Float + ScalarOperand,
+{
+    Array1::from_iter(M.axis_iter(Axis(1)).zip(b).map(|(column, &b_j)| {
+        let x = (alpha - &column) / (reg * b_j);
+        -reg * b_j * simplex_threshold(&x)
+    }))
+}
+
+/// Threshold theta of the euclidean projection max(x - theta, 0) of x on the simplex
+fn simplex_threshold<A: Float>(x: &Array1<A>) -> A {
+    let mut sorted = x.to_vec();
+    sorted.sort_by(|u, v| v.partial_cmp(u).unwrap_or(Ordering::Equal));
+
+    let mut cumsum = A::zero();
+    let mut theta = A::zero();
+    for (k, &u) in sorted.iter().enumerate() {
+        cumsum = cumsum + u;
+        let candidate = (cumsum - A::one()) / A::from(k + 1).unwrap();
+        if u - candidate > A::zero() {
+            theta = candidate;
+        }
+    }
+
+    theta
+}
+
+#[cfg(test)]
+mod tests {
+
+    use ndarray::prelude::*;
+
+    use crate::metrics::{dist, MetricType};
+    use crate::OTSolver;
+
+    #[test]
+    fn test_simplex_threshold() {
+        // Projection of (1, 0.5, -1) on the simplex is (0.75, 0.25, 0)
+        let x: Array1<f64> = array![1., 0.5, -1.];
+        assert!((super::simplex_threshold(&x) - 0.25).abs() < 1E-12);
+    }
+
+    #[test]
+    fn test_smooth_ot() {
+        // Samples along two parallel lines
+        let source = Array2::from_shape_fn((8, 2), |(i, k)| if k == 0 { i as f64 } else { 0. });
+        let target = Array2::from_shape_fn((8, 2), |(i, k)| if k == 0 { i as f64 } else { 1. });
+
+        let a = Array1::<f64>::from_elem(8, 1. / 8.);
+        let b = Array1::<f64>::from_elem(8, 1. / 8.);
+        let m = dist(&source, &target, MetricType::SqEuclidean) / 50.;
+
+        let mut solver = super::SmoothOT::new(&a, &b, &m, 1E-2);
+        let dual = match solver.solve() {
+            Ok(result) => result,
+            Err(error) => panic!("{:?}", error),
+        };
+
+        assert!(solver.log().converged);
+
+        let plan = dual.plan.to_dense();
+        assert!(plan.sum_axis(Axis(1)).abs_diff_eq(&a, 1E-7));
+        assert!(plan.sum_axis(Axis(0)).abs_diff_eq(&b, 1E-7));
+
+        // Both formulations share the same solution
+        let mut solver = super::SmoothOT::new(&a, &b, &m, 1E-2);
+        let semi_dual = match solver.semi_dual(true).solve() {
+            Ok(result) => result,
+            Err(error) => panic!("{:?}", error),
+        };
+
+        assert!(solver.log().converged);
+        assert!(semi_dual.plan.to_dense().abs_diff_eq(&plan, 1E-6));
+
+        // The squared L2 regularization yields a sparse plan
+        assert!(matches!(semi_dual.plan, super::SmoothPlan::Sparse(_)));
+
+        // Large regularizations spread the mass over all the entries
+        let result = match super::SmoothOT::new(&a, &b, &m, 1E2).solve() {
+            Ok(result) => result,
+            Err(error) => panic!("{:?}", error),
+        };
+
+        assert!(matches!(result.plan, super::SmoothPlan::Dense(_)));
+    }
+}
@@ -0,0 +1,485 @@
+use ndarray::prelude::*;
+use ndarray::{Data, ScalarOperand};
+use num_traits::Float;
+
+use super::check_regularization;
+use crate::error::OTError;
+use crate::regularized::sinkhorn::logsumexp;
+use crate::{OTSolver, SolverLog};
+
+/// Computes the entropic regularized unbalanced Wasserstein barycenter of histograms sharing a
+/// support and returns the barycenter histogram
+///
+/// The barycenter minimizes sum_k w_k UOT_reg(h_k, b), where UOT_reg is the unbalanced
+/// entropic OT loss with a KL relaxation of the marginals weighted by reg_m. The histograms
+/// do not need to share the same total mass. It is obtained with generalized Sinkhorn
+/// scaling updates on the kernel K = exp(-M/reg).
+///
+/// Scaling algorithms for unbalanced transport problems
+/// by Lénaïc Chizat, Gabriel Peyré, Bernhard Schmitzer, François-Xavier Vialard
+///
+/// ```rust
+/// use rust_optimal_transport as ot;
+/// use ot::prelude::*;
+/// use ndarray::prelude::*;
+/// use ndarray_stats::QuantileExt;
+///
+/// let n = 100;
+///
+/// // Two 1D gaussian histograms with different masses, stacked as columns
+/// let h1 = ot::utils::get_1D_gauss_histogram(n, 20., 5.).unwrap();
+/// let h2 = ot::utils::get_1D_gauss_histogram(n, 60., 8.).unwrap() * 3.;
+/// let histograms = ndarray::stack![Axis(1), h1, h2];
+///
+/// // Cost between the bins of the support
+/// let x = Array::range(0., n as f64, 1.).into_shape((n, 1)).unwrap();
+/// let mut cost = dist(&x, &x, SqEuclidean);
+/// cost = &cost / *cost.max().unwrap();
+///
+/// // Uniform weights on the histograms
+/// let weights = Array1::<f64>::zeros(0);
+///
+/// let barycenter = match SinkhornUnbalancedBarycenter::new(&histograms, &weights, &cost, 1E-3, 1.)
+///     .log_domain(true)
+///     .solve()
+/// {
+///     Ok(result) => result,
+///     Err(error) => panic!("{:?}", error),
+/// };
+///
+/// ```
+///
+/// histograms: (dim, n_hists) array, each column is a histogram on the shared support
+/// weights: Weights of the histograms in the barycenter (uniform if empty)
+/// cost: (dim, dim) loss matrix between the bins of the support
+///
+pub struct SinkhornUnbalancedBarycenter<'a, A> {
+    histograms: ArrayView2<'a, A>,
+    weights: ArrayView1<'a, A>,
+    cost: ArrayView2<'a, A>,
+    reg: A,
+    reg_m: A,
+    iterations: i32,
+    threshold: A,
+    log_domain: bool,
+    strict: bool,
+    log: SolverLog,
+}
+
+impl<'a, A> SinkhornUnbalancedBarycenter<'a, A>
+where
+    A: Float + ScalarOperand,
+{
+    pub fn new<S1, S2, S3>(
+        histograms: &'a ArrayBase<S1, Ix2>,
+        weights: &'a ArrayBase<S2, Ix1>,
+        cost: &'a ArrayBase<S3, Ix2>,
+        reg: A,
+        reg_m: A,
+    ) -> Self
+    where
+        S1: Data<Elem = A>,
+        S2: Data<Elem = A>,
+        S3: Data<Elem = A>,
+    {
+        Self {
+            histograms: histograms.view(),
+            weights: weights.view(),
+            cost: cost.view(),
+            reg,
+            reg_m,
+            iterations: 1000,
+            threshold: A::from(1E-9).unwrap(),
+            log_domain: false,
+            strict: false,
+            log: SolverLog::default(),
+        }
+    }
+
+    pub fn iterations<'b>(&'b mut self, iterations: i32) -> &'b mut Self {
+        self.iterations = iterations;
+        self
+    }
+
+    /// Stop threshold on the L1 change of the barycenter
+    pub fn threshold<'b>(&'b mut self, threshold: A) -> &'b mut Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Returns a ConvergenceError if the stop threshold is not reached within the max number
+    /// of iterations
+    pub fn strict<'b>(&'b mut self, strict: bool) -> &'b mut Self {
+        self.strict = strict;
+        self
+    }
+
+    pub fn reg<'b>(&'b mut self, reg: A) -> &'b mut Self {
+        self.reg = reg;
+        self
+    }
+
+    /// Relaxation of the histogram and barycenter marginals
+    pub fn reg_m<'b>(&'b mut self, reg_m: A) -> &'b mut Self {
+        self.reg_m = reg_m;
+        self
+    }
+
+    /// Performs the scaling updates on log-domain potentials, stable for small
+    /// regularization terms
+    pub fn log_domain<'b>(&'b mut self, log_domain: bool) -> &'b mut Self {
+        self.log_domain = log_domain;
+        self
+    }
+}
+
+impl<'a, A> OTSolver for SinkhornUnbalancedBarycenter<'a, A>
+where
+    A: Float + ScalarOperand,
+{
+    type Output = Array1<A>;
+
+    /// Ensures the cost matrix is defined on the support of the histograms and that there is
+    /// one weight per histogram
+    fn check_shape(&self) -> Result<(), OTError> {
+        let mshape = self.cost.shape();
+        let m0 = mshape[0];
+        let m1 = mshape[1];
+        let dim = self.histograms.nrows();
+
+        if dim != m0 || dim != m1 {
+            return Err(OTError::WeightDimensionError {
+                dim_a: dim,
+                dim_b: dim,
+                dim_m_0: m0,
+                dim_m_1: m1,
+            });
+        }
+
+        if !self.weights.is_empty() && self.weights.len() != self.histograms.ncols() {
+            return Err(OTError::ArgError(
+                "Number of weights does not match the number of histograms".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn solve(&mut self) -> Result<Array1<A>, OTError> {
+        self.check_shape()?;
+
+        check_regularization(self.reg, (self.reg_m, self.reg_m))?;
+
+        if self.reg == A::zero() {
+            return Err(OTError::ArgError("Regularization term <= 0".to_string()));
+        }
+
+        if self.iterations <= 0 {
+            return Err(OTError::ArgError(
+                "Iterations not a valid value. Must be > 0".to_string(),
+            ));
+        }
+
+        if self.histograms.ncols() == 0 {
+            return Err(OTError::ArgError("No histograms".to_string()));
+        }
+
+        // Normalized barycenter weights, on a copy
+        let n_hists = self.histograms.ncols();
+        let mut weights = if self.weights.is_empty() {
+            Array1::<A>::ones(n_hists)
+        } else {
+            self.weights.to_owned()
+        };
+        let total = weights.sum();
+        weights.mapv_inplace(|w| w / total);
+
+        let (barycenter, log) = if self.log_domain {
+            barycenter_unbalanced_log(
+                self.histograms,
+                &weights,
+                self.cost,
+                self.reg,
+                self.reg_m,
+                self.iterations,
+                self.threshold,
+            )
+        } else {
+            barycenter_unbalanced(
+                self.histograms,
+                &weights,
+                self.cost,
+                self.reg,
+                self.reg_m,
+                self.iterations,
+                self.threshold,
+            )
+        };
+
+        self.log = log;
+
+        if self.strict {
+            self.log.check_convergence(self.threshold)?;
+        }
+
+        Ok(barycenter)
+    }
+
+    fn log(&self) -> &SolverLog {
+        &self.log
+    }
+}
+
+/// Computes the entropic regularized unbalanced Wasserstein barycenter with generalized
+/// Sinkhorn updates and returns the barycenter
+///
+/// histograms: Histograms, (dim, n_hists)
+/// weights: Normalized weights of the histograms
+/// M: Loss matrix, (dim, dim)
+/// reg: Entropy regularization term > 0
+/// reg_m: Marginal relaxation term > 0
+/// iterations: Max number of iterations
+/// threshold: Stop threshold on the L1 change of the barycenter
+#[allow(non_snake_case)]
+fn barycenter_unbalanced<A>(
+    histograms: ArrayView2<A>,
+    weights: &Array1<A>,
+    M: ArrayView2<A>,
+    reg: A,
+    reg_m: A,
+    iterations: i32,
+    threshold: A,
+) -> (Array1<A>, SolverLog)
+where
+    A: Float + ScalarOperand,
+{
+    let mut log = SolverLog::default();
+    let fi = reg_m / (reg_m + reg);
+
+    // K = exp(-M/reg)
+    let k = M.mapv(|ele| (-ele / reg).exp());
+
+    // One pair of scalings per histogram, stored as columns
+    let mut u = Array2::<A>::ones(histograms.dim());
+    let mut v = Array2::<A>::ones(histograms.dim());
+    let mut barycenter = Array1::<A>::ones(histograms.nrows());
+
+    for count in 0..iterations {
+        let barycenter_prev = barycenter.clone();
+
+        // u = (A / K v)^fi
+        let kv = k.dot(&v);
+        azip!((u in &mut u, &a in &histograms, &kv in &kv) *u = (a / kv).powf(fi));
+
+        // The barycenter is the weighted power mean of K^T u with exponent 1 - fi
+        let ktu = k.t().dot(&u);
+        barycenter = ktu
+            .mapv(|x| x.powf(A::one() - fi))
+            .dot(weights)
+            .mapv_into(|x| x.powf(A::one() / (A::one() - fi)));
+
+        // v = (barycenter / K^T u)^fi
+        for (mut v, ktu) in v.axis_iter_mut(Axis(1)).zip(ktu.axis_iter(Axis(1))) {
+            azip!((v in &mut v, &ktu in &ktu, &p in &barycenter) *v = (p / ktu).powf(fi));
+        }
+
+        log.iterations = count + 1;
+
+        if count % 10 == 0 {
+            let err = (&barycenter - &barycenter_prev).mapv(A::abs).sum();
+
+            log.push_change(err, err);
+
+            if err < threshold {
+                log.converged = true;
+                break;
+            }
+        }
+    }
+
+    (barycenter, log)
+}
+
+/// Computes the entropic regularized unbalanced Wasserstein barycenter with generalized
+/// Sinkhorn updates in the log-domain and returns the barycenter
+///
+/// histograms: Histograms, (dim, n_hists)
+/// weights: Normalized weights of the histograms
+/// M: Loss matrix, (dim, dim)
+/// reg: Entropy regularization term > 0
+/// reg_m: Marginal relaxation term > 0
+/// iterations: Max number of iterations
+/// threshold: Stop threshold on the L1 change of the barycenter
+#[allow(non_snake_case)]
+fn barycenter_unbalanced_log<A>(
+    histograms: ArrayView2<A>,
+    weights: &Array1<A>,
+    M: ArrayView2<A>,
+    reg: A,
+    reg_m: A,
+    iterations: i32,
+    threshold: A,
+) -> (Array1<A>, SolverLog)
+where
+    A: Float + ScalarOperand,
+{
+    let mut log = SolverLog::default();
+    let fi = reg_m / (reg_m + reg);
+    let dim = histograms.nrows();
+
+    let log_a = histograms.mapv(A::ln);
+    let log_k = M.mapv(|ele| -ele / reg);
+    let log_weights = weights.mapv(A::ln);
+
+    // Scaled dual potential g_k/reg, stored as columns
+    let mut g = Array2::<A>::zeros(histograms.dim());
+    let mut log_barycenter = Array1::<A>::zeros(dim);
+
+    for count in 0..iterations {
+        let log_barycenter_prev = log_barycenter.clone();
+
+        // Scaled dual potential f_k/reg = fi * (log(A) - log(K exp(g)))
+        let mut f = Array2::<A>::zeros(histograms.dim());
+        for ((i, k), ele) in f.indexed_iter_mut() {
+            let lkv = logsumexp(log_k.row(i).iter().zip(g.column(k)).map(|(&m, &g)| m + g));
+            *ele = fi * (log_a[[i, k]] - lkv);
+        }
+
+        // log(K^T exp(f))
+        let mut log_ktu = Array2::<A>::zeros(histograms.dim());
+        for ((j, k), ele) in log_ktu.indexed_iter_mut() {
+            *ele = logsumexp(
+                log_k
+                    .column(j)
+                    .iter()
+                    .zip(f.column(k))
+                    .map(|(&m, &f)| m + f),
+            );
+        }
+
+        // log of the weighted power mean of K^T exp(f) with exponent 1 - fi
+        let one_fi = A::one() - fi;
+        log_barycenter = Array1::from_iter(log_ktu.axis_iter(Axis(0)).map(|row| {
+            logsumexp(
+                row.iter()
+                    .zip(log_weights.iter())
+                    .map(|(&l, &w)| w + one_fi * l),
+            ) / one_fi
+        }));
+
+        // g = fi * (log(barycenter) - log(K^T exp(f)))
+        for (mut g, log_ktu) in g.axis_iter_mut(Axis(1)).zip(log_ktu.axis_iter(Axis(1))) {
+            azip!((g in &mut g, &l in &log_ktu, &p in &log_barycenter) *g = fi * (p - l));
+        }
+
+        log.iterations = count + 1;
+
+        if count % 10 == 0 {
+            let err = (&log_barycenter.mapv(A::exp) - &log_barycenter_prev.mapv(A::exp))
+                .mapv(A::abs)
+                .sum();
+
+            log.push_change(err, err);
+
+            if err < threshold {
+                log.converged = true;
+                break;
+            }
+        }
+    }
+
+    (log_barycenter.mapv_into(A::exp), log)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use ndarray::prelude::*;
+
+    use crate::utils::get_1D_gauss_histogram;
+    use crate::OTSolver;
+
+    #[test]
+    fn test_sinkhorn_unbalanced_barycenter() {
+        let n = 50;
+        let h1 = get_1D_gauss_histogram(n, 10., 3.).unwrap();
+        let h2 = get_1D_gauss_histogram(n, 30., 3.).unwrap() * 3.;
+        let histograms = ndarray::stack![Axis(1), h1, h2];
+
+        let x = Array::range(0., n as f64, 1.).into_shape((n, 1)).unwrap();
+        let cost = crate::metrics::dist(&x, &x, crate::metrics::MetricType::SqEuclidean);
+        let cost = &cost / (n * n) as f64;
+
+        let weights = array![0.5, 0.5];
+
+        let mut solver =
+            super::SinkhornUnbalancedBarycenter::new(&histograms, &weights, &cost, 1E-2, 1.);
+        let barycenter = match solver.solve() {
+            Ok(result) => result,
+            Err(error) => panic!("{:?}", error),
+        };
+
+        assert!(solver.log().converged);
+
+        // The mass of the barycenter lies between the masses of the histograms
+        let mass = barycenter.sum();
+        assert!(mass > 1. && mass < 3.);
+
+        // Log-domain updates reach the same barycenter
+        let barycenter_log =
+            match super::SinkhornUnbalancedBarycenter::new(&histograms, &weights, &cost, 1E-2, 1.)
+                .log_domain(true)
+                .solve()
+            {
+                Ok(result) => result,
+                Err(error) => panic!("{:?}", error),
+            };
+
+        assert!(barycenter_log.abs_diff_eq(&barycenter, 1E-6));
+
+        // Histograms of equal masses and a stiff relaxation give back the balanced barycenter
+        let histograms = ndarray::stack![
+            Axis(1),
+            get_1D_gauss_histogram(n, 10., 3.).unwrap(),
+            get_1D_gauss_histogram(n, 30., 3.).unwrap()
+        ];
+        let barycenter =
+            match super::SinkhornUnbalancedBarycenter::new(&histograms, &weights, &cost, 1E-2, 1E3)
+                .iterations(5000)
+                .solve()
+            {
+                Ok(result) => result,
+                Err(error) => panic!("{:?}", error),
+            };
+
+        assert!((barycenter.sum() - 1.).abs() < 1E-2);
+        let mean = barycenter.dot(&x.column(0)) / barycenter.sum();
+        assert!((mean - 20.).abs() < 0.5);
+    }
+
+    #[test]
+    fn test_sinkhorn_unbalanced_barycenter_log_small_reg() {
+        let n = 50;
+        let h1 = get_1D_gauss_histogram(n, 10., 3.).unwrap();
+        let h2 = get_1D_gauss_histogram(n, 30., 3.).unwrap() * 2.;
+        let histograms = ndarray::stack![Axis(1), h1, h2];
+
+        let x = Array::range(0., n as f64, 1.).into_shape((n, 1)).unwrap();
+        let cost = crate::metrics::dist(&x, &x, crate::metrics::MetricType::SqEuclidean);
+        let cost = &cost / (n * n) as f64;
+
+        let weights = Array1::<f64>::zeros(0);
+
+        let barycenter =
+            match super::SinkhornUnbalancedBarycenter::new(&histograms, &weights, &cost, 1E-4, 1.)
+                .log_domain(true)
+                .solve()
+            {
+                Ok(result) => result,
+                Err(error) => panic!("{:?}", error),
+            };
+
+        assert!(!crate::ndarray_logical::is_nan(&barycenter));
+        assert!(barycenter.iter().all(|b| b.is_finite()));
+    }
+}
@@ -0,0 +1,381 @@
+use ndarray::prelude::*;
+use ndarray::{Data, ScalarOperand, Zip};
+use num_traits::Float;
+
+use super::{check_regularization, unbalanced_loss, MarginalDivergence, UnbalancedResult};
+use crate::error::OTError;
+use crate::optim::lbfgs::{lbfgs, lbfgsb};
+use crate::{OTSolver, SolverLog};
+
+/// Solves the unbalanced Optimal Transport problem
+/// min <G, M> + reg * KL(G | a b^T) + reg_m_source * D(G 1 | a) + reg_m_target * D(G^T 1 | b)
+/// over the non-negative plans with L-BFGS-B and returns the OT matrix and the unbalanced loss
+///
+/// The marginal penalties D are KL, squared L2 or total variation divergences. The entropic
+/// regularization term is optional, reg = 0 by default.
+///
+/// The total variation is not differentiable where a marginal of the plan matches the sample
+/// weights, the solver then stops once the line search cannot decrease the loss anymore and
+/// the log may not report convergence.
+///
+/// Unbalanced Optimal Transport through Non-negative Penalized Linear Regression
+/// by Laetitia Chapel, Rémi Flamary, Haoran Wu, Cédric Févotte, Gilles Gasso
+///
+/// ```rust
+/// use rust_optimal_transport as ot;
+/// use ot::prelude::*;
+/// use ndarray::prelude::*;
+///
+/// // The last target sample carries an abnormally large mass
+/// let source = array![[0., 0.], [1., 0.], [2., 0.]];
+/// let target = array![[0., 0.5], [1., 0.5], [2., 0.5]];
+///
+/// let source_weights = array![1., 1., 1.];
+/// let target_weights = array![1., 1., 10.];
+///
+/// let cost = dist(&source, &target, SqEuclidean);
+///
+/// let result = match LBFGSBUnbalanced::new(
+///     &source_weights,
+///     &target_weights,
+///     &cost,
+///     1.,
+/// )
+/// .divergence(MarginalDivergence::TV)
+/// .solve()
+/// {
+///     Ok(result) => result,
+///     Err(error) => panic!("{:?}", error),
+/// };
+///
+/// let ot_matrix = result.plan;
+/// let loss = result.loss;
+///
+/// ```
+///
+/// source_weights and target_weights represent histograms of the Source and Target distributions,
+/// respectively.
+///
+pub struct LBFGSBUnbalanced<'a, A> {
+    source_weights: ArrayView1<'a, A>,
+    target_weights: ArrayView1<'a, A>,
+    cost: ArrayView2<'a, A>,
+    reg: A,
+    reg_m_source: A,
+    reg_m_target: A,
+    divergence: MarginalDivergence,
+    iterations: i32,
+    threshold: A,
+    strict: bool,
+    log: SolverLog,
+}
+
+impl<'a, A> LBFGSBUnbalanced<'a, A>
+where
+    A: Float + ScalarOperand,
+{
+    pub fn new<S1, S2, S3>(
+        source_weights: &'a ArrayBase<S1, Ix1>,
+        target_weights: &'a ArrayBase<S2, Ix1>,
+        cost: &'a ArrayBase<S3, Ix2>,
+        reg_m: A,
+    ) -> Self
+    where
+        S1: Data<Elem = A>,
+        S2: Data<Elem = A>,
+        S3: Data<Elem = A>,
+    {
+        Self {
+            source_weights: source_weights.view(),
+            target_weights: target_weights.view(),
+            cost: cost.view(),
+            reg: A::zero(),
+            reg_m_source: reg_m,
+            reg_m_target: reg_m,
+            divergence: MarginalDivergence::KL,
+            iterations: 1000,
+            threshold: A::from(1E-9).unwrap(),
+            strict: false,
+            log: SolverLog::default(),
+        }
+    }
+
+    /// KL by default
+    pub fn divergence<'b>(&'b mut self, divergence: MarginalDivergence) -> &'b mut Self {
+        self.divergence = divergence;
+        self
+    }
+
+    pub fn iterations<'b>(&'b mut self, iterations: i32) -> &'b mut Self {
+        self.iterations = iterations;
+        self
+    }
+
+    /// Stop threshold on the largest entry of the projected gradient of the loss, with respect
+    /// to log(G) under entropic regularization
+    pub fn threshold<'b>(&'b mut self, threshold: A) -> &'b mut Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Returns a ConvergenceError if the stop threshold is not reached within the max number
+    /// of iterations
+    pub fn strict<'b>(&'b mut self, strict: bool) -> &'b mut Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Entropic regularization term >= 0
+    pub fn reg<'b>(&'b mut self, reg: A) -> &'b mut Self {
+        self.reg = reg;
+        self
+    }
+
+    /// Sets the same marginal relaxation on the source and target marginals
+    pub fn reg_m<'b>(&'b mut self, reg_m: A) -> &'b mut Self {
+        self.reg_m_source = reg_m;
+        self.reg_m_target = reg_m;
+        self
+    }
+
+    pub fn reg_m_source<'b>(&'b mut self, reg_m_source: A) -> &'b mut Self {
+        self.reg_m_source = reg_m_source;
+        self
+    }
+
+    pub fn reg_m_target<'b>(&'b mut self, reg_m_target: A) -> &'b mut Self {
+        self.reg_m_target = reg_m_target;
+        self
+    }
+}
+
+impl<'a, A> OTSolver for LBFGSBUnbalanced<'a, A>
+where
+    A: Float + ScalarOperand,
+{
+    type Output = UnbalancedResult<A>;
+
+    /// Ensures dimensions of the source and target measures are consistent with the
+    /// cost matrix dimensions
+    fn check_shape(&self) -> Result<(), OTError> {
+        let mshape = self.cost.shape();
+        let m0 = mshape[0];
+        let m1 = mshape[1];
+        let dim_a = self.source_weights.len();
+        let dim_b = self.target_weights.len();
+
+        if dim_a != m0 || dim_b != m1 {
+            return Err(OTError::WeightDimensionError {
+                dim_a,
+                dim_b,
+                dim_m_0: m0,
+                dim_m_1: m1,
+            });
+        }
+
+        Ok(())
+    }
+
+    #[allow(non_snake_case)]
+    fn solve(&mut self) -> Result<UnbalancedResult<A>, OTError> {
+        self.check_shape()?;
+
+        let reg_m = (self.reg_m_source, self.reg_m_target);
+        check_regularization(self.reg, reg_m)?;
+
+        if self.iterations <= 0 {
+            return Err(OTError::ArgError(
+                "Iterations not a valid value. Must be > 0".to_string(),
+            ));
+        }
+
+        let (a, b, M) = (self.source_weights, self.target_weights, self.cost);
+        let (reg, divergence) = (self.reg, self.divergence);
+        let shape = M.dim();
+
+        let ab = &a.insert_axis(Axis(1)) * &b.insert_axis(Axis(0));
+
+        // Loss and gradient with respect to the plan
+        let loss_grad = |plan: &Array2<A>| {
+            let value = unbalanced_loss(
+                plan,
+                a,
+                b,
+                M,
+                reg,
+                MarginalDivergence::KL,
+                reg_m,
+                divergence,
+            );
+
+            let mut gradient = &M
+                + &(divergence.gradient(&plan.sum_axis(Axis(1)), &a) * reg_m.0)
+                    .insert_axis(Axis(1))
+                + &(divergence.gradient(&plan.sum_axis(Axis(0)), &b) * reg_m.1)
+                    .insert_axis(Axis(0));
+
+            if reg > A::zero() {
+                gradient = gradient + MarginalDivergence::KL.gradient(plan, &ab) * reg;
+            }
+
+            (value, gradient)
+        };
+
+        let size = shape.0 * shape.1;
+        let x0 = Array1::from_iter(ab.iter().cloned());
+
+        // The entropic term keeps the plan positive, optimizing over log(G) avoids the ill
+        // conditioning of the loss on its vanishing entries. The plan vanishes where a b^T does,
+        // zero sample weights, and only the other entries are optimized
+        let (result, plan) = if reg > A::zero() {
+            let support = ab.mapv(|x| x > A::zero());
+            let to_plan = |x: &Array1<A>| {
+                Zip::from(&x.to_shape(shape).unwrap())
+                    .and(&support)
+                    .map_collect(|&x, &s| if s { x.exp() } else { A::zero() })
+            };
+
+            let objective = |x: &Array1<A>| {
+                let plan = to_plan(x);
+                let (value, gradient) = loss_grad(&plan);
+                let gradient = Zip::from(&gradient)
+                    .and(&plan)
+                    .and(&support)
+                    .map_collect(|&g, &p, &s| if s { g * p } else { A::zero() });
+                (value, Array1::from_iter(gradient))
+            };
+
+            let result = lbfgs(
+                objective,
+                x0.mapv(|x| x.max(A::min_positive_value()).ln()),
+                self.iterations,
+                self.threshold,
+                10,
+            );
+            let plan = to_plan(&result.x);
+
+            (result, plan)
+        } else {
+            let objective = |x: &Array1<A>| {
+                let plan = x.to_shape(shape).unwrap().to_owned();
+                let (value, gradient) = loss_grad(&plan);
+                (value, Array1::from_iter(gradient))
+            };
+
+            let result = lbfgsb(
+                objective,
+                x0,
+                &Array1::zeros(size),
+                &Array1::from_elem(size, A::infinity()),
+                self.iterations,
+                self.threshold,
+                10,
+            );
+            let plan = result.x.to_shape(shape).unwrap().to_owned();
+
+            (result, plan)
+        };
+
+        // Deviation from the target marginal, L-BFGS does not report the change of its iterates
+        let viol = (&plan.sum_axis(Axis(0)) - &b).mapv(A::abs).sum();
+
+        self.log = SolverLog {
+            iterations: result.iterations,
+            marginal_violation: vec![viol.to_f64().unwrap()],
+            error: result.grad_norm.to_f64().unwrap(),
+            converged: result.converged,
+            ..SolverLog::default()
+        };
+
+        if self.strict {
+            self.log.check_convergence(self.threshold)?;
+        }
+
+        Ok(UnbalancedResult {
+            plan,
+            loss: result.value,
+        })
+    }
+
+    fn log(&self) -> &SolverLog {
+        &self.log
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use ndarray::prelude::*;
+
+    use crate::unbalanced::mm::MMUnbalanced;
+    use crate::unbalanced::MarginalDivergence;
+    use crate::OTSolver;
+
+    #[test]
+    fn test_lbfgsb_unbalanced() {
+        let a: Array1<f64> = array![0.2, 0.3, 0.5];
+        let b = array![1.0, 0.5, 1.5];
+        let m = array![[0., 1., 4.], [1., 0., 1.], [4., 1., 0.]];
+
+        // Same solution as the MM solver, with and without entropic regularization
+        for (divergence, reg) in [(MarginalDivergence::KL, 1E-1), (MarginalDivergence::L2, 0.)] {
+            let mut solver = super::LBFGSBUnbalanced::new(&a, &b, &m, 1.);
+            let result = match solver.divergence(divergence).reg(reg).solve() {
+                Ok(result) => result,
+                Err(error) => panic!("{:?}", error),
+            };
+
+            assert!(solver.log().converged);
+
+            let truth = match MMUnbalanced::new(&a, &b, &m, 1.)
+                .divergence(divergence)
+                .reg(reg)
+                .solve()
+            {
+                Ok(result) => result,
+                Err(error) => panic!("{:?}", error),
+            };
+
+            assert!(result.plan.abs_diff_eq(&truth.plan, 1E-5));
+            assert!((result.loss - truth.loss).abs() < 1E-8);
+        }
+
+        // The total variation leaves out the excess mass of the last target sample, creating
+        // source mass costs more than leaving target mass behind
+        let b = array![0.2, 0.3, 5.];
+        let result = match super::LBFGSBUnbalanced::new(&a, &b, &m, 1.)
+            .divergence(MarginalDivergence::TV)
+            .reg_m_source(2.)
+            .solve()
+        {
+            Ok(result) => result,
+            Err(error) => panic!("{:?}", error),
+        };
+
+        assert!(result.plan.sum_axis(Axis(1)).abs_diff_eq(&a, 1E-4));
+        assert!(result
+            .plan
+            .sum_axis(Axis(0))
+            .abs_diff_eq(&array![0.2, 0.3, 0.5], 1E-4));
+
+        // The entropic plan vanishes on the samples with zero weight
+        let a = array![0.5, 0., 0.5];
+        let b = array![1.0, 0.5, 1.5];
+        let mut solver = super::LBFGSBUnbalanced::new(&a, &b, &m, 1.);
+        let result = match solver.reg(1E-1).solve() {
+            Ok(result) => result,
+            Err(error) => panic!("{:?}", error),
+        };
+
+        assert!(solver.log().converged);
+        assert!(result.loss.is_finite());
+        assert_eq!(result.plan.row(1).sum(), 0.);
+
+        let truth = match MMUnbalanced::new(&a, &b, &m, 1.).reg(1E-1).solve() {
+            Ok(result) => result,
+            Err(error) => panic!("{:?}", error),
+        };
+
+        assert!(result.plan.abs_diff_eq(&truth.plan, 1E-5));
+    }
+}
@@ -0,0 +1,379 @@
+use ndarray::prelude::*;
+use ndarray::{Data, ScalarOperand};
+use num_traits::Float;
+
+use super::{check_regularization, unbalanced_loss, MarginalDivergence, UnbalancedResult};
+use crate::error::OTError;
+use crate::{OTSolver, SolverLog};
+
+/// Solves the unbalanced Optimal Transport problem
+/// min <G, M> + reg * D(G | a b^T) + reg_m_source * D(G 1 | a) + reg_m_target * D(G^T 1 | b)
+/// with majorization-minimization and returns the OT matrix and the unbalanced loss
+///
+/// D is the KL divergence or the half squared L2 distance, shared by the marginal penalties
+/// and the regularization term. With the KL divergence the regularization is entropic. Each
+/// iteration minimizes a separable majorizer of the loss in closed form, which results in
+/// multiplicative updates of the plan. The regularization term is optional, reg = 0 by
+/// default.
+///
+/// Unbalanced Optimal Transport through Non-negative Penalized Linear Regression
+/// by Laetitia Chapel, Rémi Flamary, Haoran Wu, Cédric Févotte, Gilles Gasso
+///
+/// ```rust
+/// use rust_optimal_transport as ot;
+/// use ot::prelude::*;
+/// use ndarray::prelude::*;
+/// use ndarray_stats::QuantileExt;
+///
+/// // Generate data
+/// let n = 50;
+///
+/// let cov = array![[1., 0.], [0., 1.]];
+/// let source = ot::utils::sample_2D_gauss(n, &array![0., 0.], &cov).unwrap();
+/// let target = ot::utils::sample_2D_gauss(n, &array![4., 4.], &cov).unwrap();
+///
+/// // Unbalanced source and target masses
+/// let source_weights = Array1::<f64>::from_elem(n, 1. / (n as f64));
+/// let target_weights = Array1::<f64>::from_elem(n, 2. / (n as f64));
+///
+/// // Normalize cost matrix for numerical stability
+/// let mut cost = dist(&source, &target, SqEuclidean);
+/// cost = &cost / *cost.max().unwrap();
+///
+/// let result = match MMUnbalanced::new(
+///     &source_weights,
+///     &target_weights,
+///     &cost,
+///     1.,
+/// )
+/// .divergence(MarginalDivergence::L2)
+/// .solve()
+/// {
+///     Ok(result) => result,
+///     Err(error) => panic!("{:?}", error),
+/// };
+///
+/// let ot_matrix = result.plan;
+/// let loss = result.loss;
+///
+/// ```
+///
+/// source_weights and target_weights represent histograms of the Source and Target distributions,
+/// respectively.
+///
+/// The total variation is not differentiable and has no majorizer of this form, use
+/// LBFGSBUnbalanced for TV marginal penalties.
+///
+pub struct MMUnbalanced<'a, A> {
+    source_weights: ArrayView1<'a, A>,
+    target_weights: ArrayView1<'a, A>,
+    cost: ArrayView2<'a, A>,
+    reg: A,
+    reg_m_source: A,
+    reg_m_target: A,
+    divergence: MarginalDivergence,
+    iterations: i32,
+    threshold: A,
+    strict: bool,
+    log: SolverLog,
+}
+
+impl<'a, A> MMUnbalanced<'a, A>
+where
+    A: Float + ScalarOperand,
+{
+    pub fn new<S1, S2, S3>(
+        source_weights: &'a ArrayBase<S1, Ix1>,
+        target_weights: &'a ArrayBase<S2, Ix1>,
+        cost: &'a ArrayBase<S3, Ix2>,
+        reg_m: A,
+    ) -> Self
+    where
+        S1: Data<Elem = A>,
+        S2: Data<Elem = A>,
+        S3: Data<Elem = A>,
+    {
+        Self {
+            source_weights: source_weights.view(),
+            target_weights: target_weights.view(),
+            cost: cost.view(),
+            reg: A::zero(),
+            reg_m_source: reg_m,
+            reg_m_target: reg_m,
+            divergence: MarginalDivergence::KL,
+            iterations: 1000,
+            threshold: A::from(1E-9).unwrap(),
+            strict: false,
+            log: SolverLog::default(),
+        }
+    }
+
+    /// KL by default, TV is not supported
+    pub fn divergence<'b>(&'b mut self, divergence: MarginalDivergence) -> &'b mut Self {
+        self.divergence = divergence;
+        self
+    }
+
+    pub fn iterations<'b>(&'b mut self, iterations: i32) -> &'b mut Self {
+        self.iterations = iterations;
+        self
+    }
+
+    /// Stop threshold on the L1 change of the plan
+    pub fn threshold<'b>(&'b mut self, threshold: A) -> &'b mut Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Returns a ConvergenceError if the stop threshold is not reached within the max number
+    /// of iterations
+    pub fn strict<'b>(&'b mut self, strict: bool) -> &'b mut Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Regularization term >= 0
+    pub fn reg<'b>(&'b mut self, reg: A) -> &'b mut Self {
+        self.reg = reg;
+        self
+    }
+
+    /// Sets the same marginal relaxation on the source and target marginals
+    pub fn reg_m<'b>(&'b mut self, reg_m: A) -> &'b mut Self {
+        self.reg_m_source = reg_m;
+        self.reg_m_target = reg_m;
+        self
+    }
+
+    pub fn reg_m_source<'b>(&'b mut self, reg_m_source: A) -> &'b mut Self {
+        self.reg_m_source = reg_m_source;
+        self
+    }
+
+    pub fn reg_m_target<'b>(&'b mut self, reg_m_target: A) -> &'b mut Self {
+        self.reg_m_target = reg_m_target;
+        self
+    }
+}
+
+impl<'a, A> OTSolver for MMUnbalanced<'a, A>
+where
+    A: Float + ScalarOperand,
+{
+    type Output = UnbalancedResult<A>;
+
+    /// Ensures dimensions of the source and target measures are consistent with the
+    /// cost matrix dimensions
+    fn check_shape(&self) -> Result<(), OTError> {
+        let mshape = self.cost.shape();
+        let m0 = mshape[0];
+        let m1 = mshape[1];
+        let dim_a = self.source_weights.len();
+        let dim_b = self.target_weights.len();
+
+        if dim_a != m0 || dim_b != m1 {
+            return Err(OTError::WeightDimensionError {
+                dim_a,
+                dim_b,
+                dim_m_0: m0,
+                dim_m_1: m1,
+            });
+        }
+
+        Ok(())
+    }
+
+    fn solve(&mut self) -> Result<UnbalancedResult<A>, OTError> {
+        self.check_shape()?;
+
+        let reg_m = (self.reg_m_source, self.reg_m_target);
+        check_regularization(self.reg, reg_m)?;
+
+        if self.divergence == MarginalDivergence::TV {
+            return Err(OTError::ArgError(
+                "Total variation is not supported by the MM solver".to_string(),
+            ));
+        }
+
+        if self.iterations <= 0 {
+            return Err(OTError::ArgError(
+                "Iterations not a valid value. Must be > 0".to_string(),
+            ));
+        }
+
+        let (plan, log) = mm_unbalanced(
+            self.source_weights,
+            self.target_weights,
+            self.cost,
+            self.reg,
+            reg_m,
+            self.divergence,
+            self.iterations,
+            self.threshold,
+        );
+
+        self.log = log;
+
+        if self.strict {
+            self.log.check_convergence(self.threshold)?;
+        }
+
+        let loss = unbalanced_loss(
+            &plan,
+            self.source_weights,
+            self.target_weights,
+            self.cost,
+            self.reg,
+            self.divergence,
+            reg_m,
+            self.divergence,
+        );
+
+        Ok(UnbalancedResult { plan, loss })
+    }
+
+    fn log(&self) -> &SolverLog {
+        &self.log
+    }
+}
+
+/// Multiplicative majorization-minimization updates of the plan, starting from a b^T
+///
+/// a: Source sample weights
+/// b: Target sample weights
+/// M: Loss matrix
+/// reg: Regularization term >= 0
+/// reg_m: Source and target marginal relaxation terms > 0
+/// divergence: KL or L2
+/// iterations: Max number of iterations
+/// threshold: Stop threshold on the L1 change of the plan
+#[allow(non_snake_case, clippy::too_many_arguments)]
+fn mm_unbalanced<A>(
+    a: ArrayView1<A>,
+    b: ArrayView1<A>,
+    M: ArrayView2<A>,
+    reg: A,
+    reg_m: (A, A),
+    divergence: MarginalDivergence,
+    iterations: i32,
+    threshold: A,
+) -> (Array2<A>, SolverLog)
+where
+    A: Float + ScalarOperand,
+{
+    let mut log = SolverLog::default();
+    let eps = A::from(1E-16).unwrap();
+    let (reg_m1, reg_m2) = reg_m;
+
+    let a_col = a.insert_axis(Axis(1));
+    let b_row = b.insert_axis(Axis(0));
+    let ab = &a_col * &b_row;
+
+    let mut plan = ab.clone();
+
+    // Constant factor of the updates
+    let (k, r1, r2) = match divergence {
+        MarginalDivergence::KL => {
+            let sum_r = reg + reg_m1 + reg_m2;
+            let (r1, r2, r) = (reg_m1 / sum_r, reg_m2 / sum_r, reg / sum_r);
+            let k = &a_col.mapv(|x| x.powf(r1 + r))
+                * &b_row.mapv(|x| x.powf(r2 + r))
+                * M.mapv(|x| (-x / sum_r).exp());
+            (k, r1, r2)
+        }
+        _ => {
+            let k =
+                (&a_col * reg_m1 + &b_row * reg_m2 + &ab * reg - M).mapv_into(|x| x.max(A::zero()));
+            (k, A::zero(), A::zero())
+        }
+    };
+
+    for count in 0..iterations {
+        let plan_prev = plan.clone();
+
+        let row_sums = plan.sum_axis(Axis(1)).insert_axis(Axis(1));
+        let col_sums = plan.sum_axis(Axis(0)).insert_axis(Axis(0));
+
+        plan = match divergence {
+            MarginalDivergence::KL => {
+                let denominator =
+                    &row_sums.mapv(|x| x.powf(r1)) * &col_sums.mapv(|x| x.powf(r2)) + eps;
+                &k * &plan.mapv(|x| x.powf(r1 + r2)) / &denominator
+            }
+            _ => {
+                let denominator = &row_sums * reg_m1 + &col_sums * reg_m2 + &plan * reg + eps;
+                &k * &plan / &denominator
+            }
+        };
+
+        log.iterations = count + 1;
+
+        if count % 10 == 0 {
+            let err = (&plan - &plan_prev).mapv(A::abs).sum();
+
+            // Deviation from the target marginal
+            let viol = (&plan.sum_axis(Axis(0)) - &b).mapv(A::abs).sum();
+
+            log.push(err, viol, err);
+
+            if err < threshold {
+                log.converged = true;
+                break;
+            }
+        }
+    }
+
+    (plan, log)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use ndarray::prelude::*;
+
+    use crate::unbalanced::MarginalDivergence;
+    use crate::OTSolver;
+
+    #[test]
+    fn test_mm_unbalanced() {
+        let a = array![0.2, 0.3, 0.5];
+        let b = array![1.0, 0.5, 1.5];
+        let m = array![[0., 1., 4.], [1., 0., 1.], [4., 1., 0.]];
+
+        for divergence in [MarginalDivergence::KL, MarginalDivergence::L2] {
+            let mut solver = super::MMUnbalanced::new(&a, &b, &m, 1.);
+            let result = match solver.divergence(divergence).reg(1E-1).solve() {
+                Ok(result) => result,
+                Err(error) => panic!("{:?}", error),
+            };
+
+            assert!(solver.log().converged);
+
+            // Optimality conditions, the gradient M + reg D'(G | a b^T) + D'(G 1 | a)
+            // + D'(G^T 1 | b) vanishes on the support of the plan and is non-negative elsewhere
+            let plan: Array2<f64> = result.plan;
+            let row_sums = plan.sum_axis(Axis(1));
+            let col_sums = plan.sum_axis(Axis(0));
+            let ab = &a.view().insert_axis(Axis(1)) * &b.view().insert_axis(Axis(0));
+
+            let gradient = &m
+                + &(divergence.gradient(&plan, &ab) * 1E-1)
+                + &divergence.gradient(&row_sums, &a).insert_axis(Axis(1))
+                + &divergence.gradient(&col_sums, &b).insert_axis(Axis(0));
+
+            for (&g, &grad) in plan.iter().zip(&gradient) {
+                if g > 1E-8 {
+                    assert!(grad.abs() < 1E-4);
+                } else {
+                    assert!(grad > -1E-4);
+                }
+            }
+        }
+
+        // No majorizer of the total variation
+        assert!(super::MMUnbalanced::new(&a, &b, &m, 1.)
+            .divergence(MarginalDivergence::TV)
+            .solve()
+            .is_err());
+    }
+}
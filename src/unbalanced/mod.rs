@@ -1,9 +1,141 @@
+pub mod barycenter;
+pub mod lbfgsb;
+pub mod mm;
+
 // use crate::ndarray_logical;
 use ndarray::prelude::*;
-use ndarray_linalg::norm;
+use ndarray::{Data, ScalarOperand, Zip};
+use num_traits::Float;
 
 use crate::error::OTError;
-use crate::OTSolver;
+use crate::regularized::loss::{entropic_loss, kl_divergence};
+use crate::regularized::sinkhorn::{logsumexp, marginal_violation};
+use crate::{OTSolver, SolverLog};
+
+/// Divergence penalizing the deviation of the marginals of the plan from the sample weights
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MarginalDivergence {
+    /// Generalized Kullback-Leibler divergence sum(x log(x/y) - x + y)
+    KL,
+    /// Half squared euclidean distance ||x - y||^2 / 2
+    L2,
+    /// Total variation ||x - y||_1, more robust to large mass errors
+    TV,
+}
+
+impl MarginalDivergence {
+    /// Divergence between x and y
+    pub(crate) fn value<A, S1, S2, D>(&self, x: &ArrayBase<S1, D>, y: &ArrayBase<S2, D>) -> A
+    where
+        A: Float,
+        S1: Data<Elem = A>,
+        S2: Data<Elem = A>,
+        D: Dimension,
+    {
+        let half = A::from(0.5).unwrap();
+
+        match self {
+            MarginalDivergence::KL => kl_divergence(x, y),
+            MarginalDivergence::L2 => Zip::from(x)
+                .and(y)
+                .fold(A::zero(), |acc, &x, &y| acc + half * (x - y) * (x - y)),
+            MarginalDivergence::TV => Zip::from(x)
+                .and(y)
+                .fold(A::zero(), |acc, &x, &y| acc + (x - y).abs()),
+        }
+    }
+
+    /// Gradient of the divergence with respect to x, a subgradient for the total variation
+    pub(crate) fn gradient<A, S1, S2, D>(
+        &self,
+        x: &ArrayBase<S1, D>,
+        y: &ArrayBase<S2, D>,
+    ) -> Array<A, D>
+    where
+        A: Float,
+        S1: Data<Elem = A>,
+        S2: Data<Elem = A>,
+        D: Dimension,
+    {
+        match self {
+            // log(0) is kept finite, the gradient still pushes zero entries upwards
+            MarginalDivergence::KL => Zip::from(x)
+                .and(y)
+                .map_collect(|&x, &y| (x.max(A::min_positive_value()) / y).ln()),
+            MarginalDivergence::L2 => Zip::from(x).and(y).map_collect(|&x, &y| x - y),
+            MarginalDivergence::TV => Zip::from(x).and(y).map_collect(|&x, &y| {
+                if x > y {
+                    A::one()
+                } else if x < y {
+                    -A::one()
+                } else {
+                    A::zero()
+                }
+            }),
+        }
+    }
+}
+
+/// Plan and loss of an unbalanced Optimal Transport problem
+#[derive(Debug, Clone)]
+pub struct UnbalancedResult<A> {
+    /// OT matrix
+    pub plan: Array2<A>,
+    /// Unbalanced loss of the plan, with its regularization and marginal penalty terms
+    pub loss: A,
+}
+
+/// Returns the unbalanced loss of a plan
+/// <G, M> + reg * D_reg(G | a b^T) + reg_m_a * D(G 1 | a) + reg_m_b * D(G^T 1 | b)
+///
+/// reg_divergence: Divergence D_reg of the regularization term
+/// divergence: Divergence D of the marginal penalties
+#[allow(non_snake_case, clippy::too_many_arguments)]
+pub(crate) fn unbalanced_loss<A>(
+    plan: &Array2<A>,
+    a: ArrayView1<A>,
+    b: ArrayView1<A>,
+    M: ArrayView2<A>,
+    reg: A,
+    reg_divergence: MarginalDivergence,
+    reg_m: (A, A),
+    divergence: MarginalDivergence,
+) -> A
+where
+    A: Float + ScalarOperand,
+{
+    let mut loss = (&M * plan).sum();
+
+    if reg > A::zero() {
+        let ab = &a.insert_axis(Axis(1)) * &b.insert_axis(Axis(0));
+        loss = loss + reg * reg_divergence.value(plan, &ab);
+    }
+
+    loss + reg_m.0 * divergence.value(&plan.sum_axis(Axis(1)), &a)
+        + reg_m.1 * divergence.value(&plan.sum_axis(Axis(0)), &b)
+}
+
+/// Ensures the regularization and marginal relaxation terms of the MM and L-BFGS-B unbalanced
+/// solvers are valid
+pub(crate) fn check_regularization<A: Float>(reg: A, reg_m: (A, A)) -> Result<(), OTError> {
+    if reg < A::zero() {
+        return Err(OTError::ArgError("Regularization term < 0".to_string()));
+    }
+
+    if reg_m.0 <= A::zero() || reg_m.1 <= A::zero() {
+        return Err(OTError::ArgError(
+            "Marginal regularization term <= 0".to_string(),
+        ));
+    }
+
+    if reg_m.0.is_infinite() || reg_m.1.is_infinite() {
+        return Err(OTError::ArgError(
+            "Marginal regularization term must be finite".to_string(),
+        ));
+    }
+
+    Ok(())
+}
 
 /// Solves the entropic regularization optimal transport problem using the Sinkhorn-Knopp algorithm
 /// and returns the OT matrix
@@ -60,33 +192,52 @@ use crate::OTSolver;
 /// source_weights and target_weights represent histograms of the Source and Target distributions,
 /// respectively.
 ///
+/// The marginal relaxation reg_m applies to both marginals by default, reg_m_source and
+/// reg_m_target set a distinct relaxation on each side. An infinite relaxation keeps the
+/// corresponding marginal constraint hard, which yields the semi-relaxed OT problem when set
+/// on a single side.
+///
 
-pub struct SinkhornKnoppUnbalanced<'a> {
-    source_weights: &'a Array1<f64>,
-    target_weights: &'a Array1<f64>,
-    cost: &'a Array2<f64>,
-    reg: f64,
-    reg_m: f64,
+pub struct SinkhornKnoppUnbalanced<'a, A> {
+    source_weights: ArrayView1<'a, A>,
+    target_weights: ArrayView1<'a, A>,
+    cost: ArrayView2<'a, A>,
+    reg: A,
+    reg_m_source: A,
+    reg_m_target: A,
     iterations: i32,
-    threshold: f64,
+    threshold: A,
+    strict: bool,
+    log: SolverLog,
 }
 
-impl<'a> SinkhornKnoppUnbalanced<'a> {
-    pub fn new(
-        source_weights: &'a Array1<f64>,
-        target_weights: &'a Array1<f64>,
-        cost: &'a Array2<f64>,
-        reg: f64,
-        reg_m: f64,
-    ) -> Self {
+impl<'a, A> SinkhornKnoppUnbalanced<'a, A>
+where
+    A: Float + ScalarOperand,
+{
+    pub fn new<S1, S2, S3>(
+        source_weights: &'a ArrayBase<S1, Ix1>,
+        target_weights: &'a ArrayBase<S2, Ix1>,
+        cost: &'a ArrayBase<S3, Ix2>,
+        reg: A,
+        reg_m: A,
+    ) -> Self
+    where
+        S1: Data<Elem = A>,
+        S2: Data<Elem = A>,
+        S3: Data<Elem = A>,
+    {
         Self {
-            source_weights,
-            target_weights,
-            cost,
+            source_weights: source_weights.view(),
+            target_weights: target_weights.view(),
+            cost: cost.view(),
             reg,
-            reg_m,
+            reg_m_source: reg_m,
+            reg_m_target: reg_m,
             iterations: 1000,
-            threshold: 1E-9,
+            threshold: A::from(1E-9).unwrap(),
+            strict: false,
+            log: SolverLog::default(),
         }
     }
 
@@ -95,23 +246,67 @@ impl<'a> SinkhornKnoppUnbalanced<'a> {
         self
     }
 
-    pub fn threshold<'b>(&'b mut self, threshold: f64) -> &'b mut Self {
+    pub fn threshold<'b>(&'b mut self, threshold: A) -> &'b mut Self {
         self.threshold = threshold;
         self
     }
 
-    pub fn reg<'b>(&'b mut self, reg: f64) -> &'b mut Self {
+    /// Returns a ConvergenceError if the stop threshold is not reached within the max number
+    /// of iterations
+    pub fn strict<'b>(&'b mut self, strict: bool) -> &'b mut Self {
+        self.strict = strict;
+        self
+    }
+
+    pub fn reg<'b>(&'b mut self, reg: A) -> &'b mut Self {
         self.reg = reg;
         self
     }
 
-    pub fn reg_m<'b>(&'b mut self, reg_m: f64) -> &'b mut Self {
-        self.reg_m = reg_m;
+    /// Sets the same marginal relaxation on the source and target marginals
+    pub fn reg_m<'b>(&'b mut self, reg_m: A) -> &'b mut Self {
+        self.reg_m_source = reg_m;
+        self.reg_m_target = reg_m;
+        self
+    }
+
+    /// Relaxation of the source marginal, infinity keeps it hard
+    pub fn reg_m_source<'b>(&'b mut self, reg_m_source: A) -> &'b mut Self {
+        self.reg_m_source = reg_m_source;
+        self
+    }
+
+    /// Relaxation of the target marginal, infinity keeps it hard
+    pub fn reg_m_target<'b>(&'b mut self, reg_m_target: A) -> &'b mut Self {
+        self.reg_m_target = reg_m_target;
         self
     }
+
+    /// Solves the unbalanced entropic regularization optimal transport problem and returns the
+    /// loss <G, M> + reg * KL(G | a b^T) + reg_m_source * KL(G 1 | a)
+    /// + reg_m_target * KL(G^T 1 | b)
+    ///
+    /// Hard marginals, with an infinite relaxation, do not contribute to the loss.
+    pub fn loss(&mut self) -> Result<A, OTError> {
+        let plan = self.solve()?;
+
+        Ok(sinkhorn_unbalanced_loss(
+            &plan,
+            self.source_weights,
+            self.target_weights,
+            self.cost,
+            self.reg,
+            (self.reg_m_source, self.reg_m_target),
+        ))
+    }
 }
 
-impl<'a> OTSolver for SinkhornKnoppUnbalanced<'a> {
+impl<'a, A> OTSolver for SinkhornKnoppUnbalanced<'a, A>
+where
+    A: Float + ScalarOperand,
+{
+    type Output = Array2<A>;
+
     /// Ensures dimensions of the source and target measures are consistent with the
     /// cost matrix dimensions
     fn check_shape(&self) -> Result<(), OTError> {
@@ -134,14 +329,14 @@ impl<'a> OTSolver for SinkhornKnoppUnbalanced<'a> {
         Ok(())
     }
 
-    fn solve(&mut self) -> Result<Array2<f64>, OTError> {
+    fn solve(&mut self) -> Result<Array2<A>, OTError> {
         self.check_shape()?;
 
-        if self.reg <= 0. {
+        if self.reg <= A::zero() {
             return Err(OTError::ArgError("Regularization term <= 0".to_string()));
         }
 
-        if self.reg_m <= 0. {
+        if self.reg_m_source <= A::zero() || self.reg_m_target <= A::zero() {
             return Err(OTError::ArgError(
                 "Marginal regularization term <= 0".to_string(),
             ));
@@ -153,15 +348,28 @@ impl<'a> OTSolver for SinkhornKnoppUnbalanced<'a> {
             ));
         }
 
-        sinkhorn_knopp_unbalanced(
+        let (plan, log) = sinkhorn_knopp_unbalanced(
             self.source_weights,
             self.target_weights,
             self.cost,
             self.reg,
-            self.reg_m,
+            self.reg_m_source,
+            self.reg_m_target,
             self.iterations,
             self.threshold,
-        )
+        )?;
+
+        self.log = log;
+
+        if self.strict {
+            self.log.check_convergence(self.threshold)?;
+        }
+
+        Ok(plan)
+    }
+
+    fn log(&self) -> &SolverLog {
+        &self.log
     }
 }
 
@@ -171,18 +379,25 @@ impl<'a> OTSolver for SinkhornKnoppUnbalanced<'a> {
 /// b: Target sample weights (defaults to uniform weight if empty)
 /// M: Loss matrix
 /// reg: Entropy regularization term > 0
-/// reg_m: Marginal relaxation term > 0
+/// reg_m_a: Source marginal relaxation term > 0, infinity for a hard constraint
+/// reg_m_b: Target marginal relaxation term > 0, infinity for a hard constraint
 /// num_iter_max: Max number of iterations (default = 1000)
 /// stop_threshold: Stop threshold on error (> 0) (default = 1E-6)
-fn sinkhorn_knopp_unbalanced(
-    a: &Array1<f64>,
-    b: &Array1<f64>,
-    M: &Array2<f64>,
-    reg: f64,
-    reg_m: f64,
+#[allow(clippy::too_many_arguments)]
+fn sinkhorn_knopp_unbalanced<A>(
+    a: ArrayView1<A>,
+    b: ArrayView1<A>,
+    M: ArrayView2<A>,
+    reg: A,
+    reg_m_a: A,
+    reg_m_b: A,
     iterations: i32,
-    threshold: f64,
-) -> Result<Array2<f64>, OTError> {
+    threshold: A,
+) -> Result<(Array2<A>, SolverLog), OTError>
+where
+    A: Float + ScalarOperand,
+{
+    let mut log = SolverLog::default();
     let mut err;
     let mut ktu;
     let mut v_prev;
@@ -190,21 +405,19 @@ fn sinkhorn_knopp_unbalanced(
     let k_transpose;
     let dim_a = a.len();
     let dim_b = b.len();
-    let fi = reg_m / (reg_m + reg);
+    let fi_a = scaling_exponent(reg_m_a, reg);
+    let fi_b = scaling_exponent(reg_m_b, reg);
 
     // we assume that no distances are null except those of the diagonal distances
-    let mut u = Array1::<f64>::from_elem(dim_a, 1. / (dim_a as f64));
-    let mut v = Array1::<f64>::from_elem(dim_b, 1. / (dim_b as f64));
+    let mut u = Array1::<A>::from_elem(dim_a, A::one() / A::from(dim_a).unwrap());
+    let mut v = Array1::<A>::from_elem(dim_b, A::one() / A::from(dim_b).unwrap());
 
     // K = exp(-M/reg)
-    let f = |ele: f64| (-ele / reg).exp();
-    let k = M.clone().mapv_into(f);
-
-    let a_cache = a.clone();
-    let b_cache = b.clone();
+    let f = |ele: A| (-ele / reg).exp();
+    let k = M.mapv(f);
 
     // Kp = (1./a) * K
-    let numerator: Array1<f64> = a_cache.mapv_into(|a| 1. / a);
+    let numerator: Array1<A> = a.mapv(|a| A::one() / a);
     kp = numerator.into_shape((dim_a, 1)).unwrap() * &k;
 
     // K.transpose()
@@ -217,22 +430,384 @@ fn sinkhorn_knopp_unbalanced(
         ktu = k_transpose.dot(&u);
 
         // v = b/ktu
-        azip!((v in &mut v, &b in &b_cache, &ktu in &ktu) *v = (b / ktu).powf(fi));
+        azip!((v in &mut v, &b in &b, &ktu in &ktu) *v = (b / ktu).powf(fi_b));
 
         // Update u
         // u = a/kv = 1 / (dot(kp, v)
-        azip!((u in &mut u, &kpdotv in &kp.dot(&v)) *u = (1. / kpdotv).powf(fi));
+        azip!((u in &mut u, &kpdotv in &kp.dot(&v)) *u = (A::one() / kpdotv).powf(fi_a));
+
+        log.iterations = count + 1;
+
+        if count % 10 == 0 {
+            err = (&v - &v_prev).mapv(A::abs).sum();
+
+            // Deviation from the target marginal, sum(G, axis=0) = v * K^T u
+            let viol = marginal_violation(&v, &k_transpose.dot(&u), b);
+
+            log.push(err, viol, err);
+
+            if err < threshold {
+                log.converged = true;
+                break;
+            }
+        }
+    }
+
+    let plan = u.into_shape((dim_a, 1)).unwrap() * k * v.into_shape((1, dim_b)).unwrap();
+
+    Ok((plan, log))
+}
+
+/// Exponent reg_m / (reg_m + reg) of the unbalanced scaling updates, 1 for hard marginals
+fn scaling_exponent<A: Float>(reg_m: A, reg: A) -> A {
+    if reg_m.is_infinite() {
+        A::one()
+    } else {
+        reg_m / (reg_m + reg)
+    }
+}
+
+/// Returns the unbalanced entropic loss of a plan
+/// <G, M> + reg * KL(G | a b^T) + reg_m.0 * KL(G 1 | a) + reg_m.1 * KL(G^T 1 | b)
+///
+/// Hard marginals, with an infinite relaxation, do not contribute to the loss.
+#[allow(non_snake_case)]
+fn sinkhorn_unbalanced_loss<A>(
+    plan: &Array2<A>,
+    a: ArrayView1<A>,
+    b: ArrayView1<A>,
+    M: ArrayView2<A>,
+    reg: A,
+    reg_m: (A, A),
+) -> A
+where
+    A: Float,
+{
+    // Penalty of a marginal, zero for hard constraints
+    let penalty = |reg_m: A, marginal: Array1<A>, weights: ArrayView1<A>| {
+        if reg_m.is_infinite() {
+            A::zero()
+        } else {
+            reg_m * kl_divergence(&marginal, &weights)
+        }
+    };
+
+    entropic_loss(plan, &a, &b, &M, reg)
+        + penalty(reg_m.0, plan.sum_axis(Axis(1)), a)
+        + penalty(reg_m.1, plan.sum_axis(Axis(0)), b)
+}
+
+/// Solves the unbalanced entropic regularization optimal transport problem using the
+/// Sinkhorn-Knopp algorithm in the log-domain and returns the OT matrix
+///
+/// The scaling updates u = (a / K v)^fi are performed on the dual potentials with log-sum-exp
+/// reductions, which keeps the solver stable for small regularization terms and widely varying
+/// masses where SinkhornKnoppUnbalanced underflows.
+///
+/// ```rust
+/// use rust_optimal_transport as ot;
+/// use ot::prelude::*;
+/// use ndarray::prelude::*;
+/// use ndarray_stats::QuantileExt;
+///
+/// // Generate data
+/// let n = 100;
+///
+/// let cov = array![[1., 0.], [0., 1.]];
+/// let source = ot::utils::sample_2D_gauss(n, &array![0., 0.], &cov).unwrap();
+/// let target = ot::utils::sample_2D_gauss(n, &array![4., 4.], &cov).unwrap();
+///
+/// // Unbalanced source and target masses
+/// let source_weights = Array1::<f64>::from_elem(n, 1. / (n as f64));
+/// let target_weights = Array1::<f64>::from_elem(n, 5. / (n as f64));
+///
+/// // Normalize cost matrix for numerical stability
+/// let mut cost = dist(&source, &target, SqEuclidean);
+/// cost = &cost / *cost.max().unwrap();
+///
+/// let regularization = 1E-4;
+/// let marginal_regularization = 1E-1;
+///
+/// let ot_matrix = match SinkhornLogUnbalanced::new(
+///     &source_weights,
+///     &target_weights,
+///     &cost,
+///     regularization,
+///     marginal_regularization,
+/// ).solve() {
+///     Ok(result) => result,
+///     Err(error) => panic!("{:?}", error),
+/// };
+///
+/// assert!(ot_matrix.iter().all(|g| g.is_finite()));
+///
+/// ```
+///
+/// source_weights and target_weights represent histograms of the Source and Target distributions,
+/// respectively.
+///
+/// As for SinkhornKnoppUnbalanced, reg_m_source and reg_m_target set a distinct relaxation on
+/// each marginal and an infinite relaxation keeps the corresponding marginal hard.
+///
+pub struct SinkhornLogUnbalanced<'a, A> {
+    source_weights: ArrayView1<'a, A>,
+    target_weights: ArrayView1<'a, A>,
+    cost: ArrayView2<'a, A>,
+    reg: A,
+    reg_m_source: A,
+    reg_m_target: A,
+    iterations: i32,
+    threshold: A,
+    strict: bool,
+    log: SolverLog,
+}
+
+impl<'a, A> SinkhornLogUnbalanced<'a, A>
+where
+    A: Float + ScalarOperand,
+{
+    pub fn new<S1, S2, S3>(
+        source_weights: &'a ArrayBase<S1, Ix1>,
+        target_weights: &'a ArrayBase<S2, Ix1>,
+        cost: &'a ArrayBase<S3, Ix2>,
+        reg: A,
+        reg_m: A,
+    ) -> Self
+    where
+        S1: Data<Elem = A>,
+        S2: Data<Elem = A>,
+        S3: Data<Elem = A>,
+    {
+        Self {
+            source_weights: source_weights.view(),
+            target_weights: target_weights.view(),
+            cost: cost.view(),
+            reg,
+            reg_m_source: reg_m,
+            reg_m_target: reg_m,
+            iterations: 1000,
+            threshold: A::from(1E-9).unwrap(),
+            strict: false,
+            log: SolverLog::default(),
+        }
+    }
+
+    pub fn iterations<'b>(&'b mut self, iterations: i32) -> &'b mut Self {
+        self.iterations = iterations;
+        self
+    }
+
+    pub fn threshold<'b>(&'b mut self, threshold: A) -> &'b mut Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Returns a ConvergenceError if the stop threshold is not reached within the max number
+    /// of iterations
+    pub fn strict<'b>(&'b mut self, strict: bool) -> &'b mut Self {
+        self.strict = strict;
+        self
+    }
+
+    pub fn reg<'b>(&'b mut self, reg: A) -> &'b mut Self {
+        self.reg = reg;
+        self
+    }
+
+    /// Sets the same marginal relaxation on the source and target marginals
+    pub fn reg_m<'b>(&'b mut self, reg_m: A) -> &'b mut Self {
+        self.reg_m_source = reg_m;
+        self.reg_m_target = reg_m;
+        self
+    }
+
+    /// Relaxation of the source marginal, infinity keeps it hard
+    pub fn reg_m_source<'b>(&'b mut self, reg_m_source: A) -> &'b mut Self {
+        self.reg_m_source = reg_m_source;
+        self
+    }
+
+    /// Relaxation of the target marginal, infinity keeps it hard
+    pub fn reg_m_target<'b>(&'b mut self, reg_m_target: A) -> &'b mut Self {
+        self.reg_m_target = reg_m_target;
+        self
+    }
+
+    /// Solves the unbalanced entropic regularization optimal transport problem and returns the
+    /// loss <G, M> + reg * KL(G | a b^T) + reg_m_source * KL(G 1 | a)
+    /// + reg_m_target * KL(G^T 1 | b)
+    ///
+    /// Hard marginals, with an infinite relaxation, do not contribute to the loss.
+    pub fn loss(&mut self) -> Result<A, OTError> {
+        let plan = self.solve()?;
+
+        Ok(sinkhorn_unbalanced_loss(
+            &plan,
+            self.source_weights,
+            self.target_weights,
+            self.cost,
+            self.reg,
+            (self.reg_m_source, self.reg_m_target),
+        ))
+    }
+}
+
+impl<'a, A> OTSolver for SinkhornLogUnbalanced<'a, A>
+where
+    A: Float + ScalarOperand,
+{
+    type Output = Array2<A>;
+
+    /// Ensures dimensions of the source and target measures are consistent with the
+    /// cost matrix dimensions
+    fn check_shape(&self) -> Result<(), OTError> {
+        let mshape = self.cost.shape();
+        let m0 = mshape[0];
+        let m1 = mshape[1];
+        let dim_a = self.source_weights.len();
+        let dim_b = self.target_weights.len();
+
+        if dim_a != m0 || dim_b != m1 {
+            return Err(OTError::WeightDimensionError {
+                dim_a,
+                dim_b,
+                dim_m_0: m0,
+                dim_m_1: m1,
+            });
+        }
+
+        Ok(())
+    }
+
+    fn solve(&mut self) -> Result<Array2<A>, OTError> {
+        self.check_shape()?;
+
+        if self.reg <= A::zero() {
+            return Err(OTError::ArgError("Regularization term <= 0".to_string()));
+        }
+
+        if self.reg_m_source <= A::zero() || self.reg_m_target <= A::zero() {
+            return Err(OTError::ArgError(
+                "Marginal regularization term <= 0".to_string(),
+            ));
+        }
+
+        if self.iterations <= 0 {
+            return Err(OTError::ArgError(
+                "Iterations not a valid value. Must be > 0".to_string(),
+            ));
+        }
+
+        let (plan, log) = sinkhorn_log_unbalanced(
+            self.source_weights,
+            self.target_weights,
+            self.cost,
+            self.reg,
+            (self.reg_m_source, self.reg_m_target),
+            self.iterations,
+            self.threshold,
+        );
+
+        self.log = log;
+
+        if self.strict {
+            self.log.check_convergence(self.threshold)?;
+        }
+
+        Ok(plan)
+    }
+
+    fn log(&self) -> &SolverLog {
+        &self.log
+    }
+}
+
+/// Solves the unbalanced entropic regularization optimal transport problem in the log-domain
+/// and returns the OT matrix
+///
+/// a: Source sample weights
+/// b: Target sample weights
+/// M: Loss matrix
+/// reg: Entropy regularization term > 0
+/// reg_m: Source and target marginal relaxation terms > 0, infinity for hard constraints
+/// iterations: Max number of iterations
+/// threshold: Stop threshold on the L1 change of the scaled target potential
+#[allow(non_snake_case)]
+fn sinkhorn_log_unbalanced<A>(
+    a: ArrayView1<A>,
+    b: ArrayView1<A>,
+    M: ArrayView2<A>,
+    reg: A,
+    reg_m: (A, A),
+    iterations: i32,
+    threshold: A,
+) -> (Array2<A>, SolverLog)
+where
+    A: Float + ScalarOperand,
+{
+    let mut log = SolverLog::default();
+    let fi_a = scaling_exponent(reg_m.0, reg);
+    let fi_b = scaling_exponent(reg_m.1, reg);
+
+    let loga = a.mapv(A::ln);
+    let logb = b.mapv(A::ln);
+
+    // Scaled dual potentials: f/reg and g/reg
+    let mut u = Array1::<A>::zeros(a.len());
+    let mut v = Array1::<A>::zeros(b.len());
+
+    // log(K^T u) and log(K v) with K = exp(-M/reg)
+    let log_ktu = |u: &Array1<A>| {
+        Array1::from_iter(
+            M.axis_iter(Axis(1))
+                .map(|col| logsumexp(col.iter().zip(u.iter()).map(|(&m, &u)| -m / reg + u))),
+        )
+    };
+    let log_kv = |v: &Array1<A>| {
+        Array1::from_iter(
+            M.axis_iter(Axis(0))
+                .map(|row| logsumexp(row.iter().zip(v.iter()).map(|(&m, &v)| -m / reg + v))),
+        )
+    };
+
+    for count in 0..iterations {
+        let v_prev = v.clone();
+
+        // v = fi * (log(b) - log(K^T u))
+        v = (&logb - &log_ktu(&u)) * fi_b;
+
+        // u = fi * (log(a) - log(K v))
+        u = (&loga - &log_kv(&v)) * fi_a;
+
+        log.iterations = count + 1;
 
         if count % 10 == 0 {
-            err = norm::Norm::norm_l1(&(&v - &v_prev));
+            let err = (&v - &v_prev).mapv(A::abs).sum();
+
+            // Deviation from the target marginal, sum(G, axis=0) = exp(v + log(K^T u))
+            let viol = Zip::from(&v)
+                .and(&log_ktu(&u))
+                .and(&b)
+                .fold(A::zero(), |acc, &v, &lk, &b| {
+                    acc + ((v + lk).exp() - b).abs()
+                });
+
+            log.push(err, viol, err);
 
             if err < threshold {
+                log.converged = true;
                 break;
             }
         }
     }
 
-    Ok(u.into_shape((dim_a, 1)).unwrap() * k * v.into_shape((1, dim_b)).unwrap())
+    // G = exp(u[:, None] - M/reg + v[None, :])
+    let mut plan = M.mapv(|ele| -ele / reg);
+    for ((i, j), ele) in plan.indexed_iter_mut() {
+        *ele = (*ele + u[i] + v[j]).exp();
+    }
+
+    (plan, log)
 }
 
 #[cfg(test)]
@@ -257,7 +832,7 @@ mod tests {
         let mean_target = 60.0;
         let std_target = 10.0;
 
-        let mut source_mass = match get_1D_gauss_histogram(n, mean_source, std_source) {
+        let source_mass = match get_1D_gauss_histogram(n, mean_source, std_source) {
             Ok(val) => val,
             Err(err) => panic!("{:?}", err),
         };
@@ -271,7 +846,7 @@ mod tests {
         target_mass *= 5.0;
 
         // ot.dist(x.reshape(n,1), x.reshape(n,1))
-        let mut m = array![
+        let m = array![
             [0., 1., 4., 9., 16.],
             [1., 0., 1., 4., 9.],
             [4., 1., 0., 1., 4.],
@@ -280,15 +855,16 @@ mod tests {
         ];
 
         let result = match super::sinkhorn_knopp_unbalanced(
-            &mut source_mass,
-            &mut target_mass,
-            &mut m,
+            source_mass.view(),
+            target_mass.view(),
+            m.view(),
             reg,
             reg_m,
+            reg_m,
             1000,
             1E-9,
         ) {
-            Ok(result) => result,
+            Ok((result, _log)) => result,
             Err(error) => panic!("{:?}", error),
         };
 
@@ -348,7 +924,7 @@ mod tests {
         let mean_target = 60.0;
         let std_target = 10.0;
 
-        let mut source_mass = match get_1D_gauss_histogram(n, mean_source, std_source) {
+        let source_mass = match get_1D_gauss_histogram(n, mean_source, std_source) {
             Ok(val) => val,
             Err(err) => panic!("{:?}", err),
         };
@@ -362,7 +938,7 @@ mod tests {
         target_mass *= 5.0;
 
         // ot.dist(x.reshape(n,1), x.reshape(n,1))
-        let mut m = array![
+        let m = array![
             [0., 1., 4., 9., 16.],
             [1., 0., 1., 4., 9.],
             [4., 1., 0., 1., 4.],
@@ -370,18 +946,13 @@ mod tests {
             [16., 9., 4., 1., 0.]
         ];
 
-        let result = match super::SinkhornKnoppUnbalanced::new(
-            &mut source_mass,
-            &mut target_mass,
-            &mut m,
-            reg,
-            reg_m,
-        )
-        .solve()
-        {
-            Ok(result) => result,
-            Err(error) => panic!("{:?}", error),
-        };
+        let result =
+            match super::SinkhornKnoppUnbalanced::new(&source_mass, &target_mass, &m, reg, reg_m)
+                .solve()
+            {
+                Ok(result) => result,
+                Err(error) => panic!("{:?}", error),
+            };
 
         let truth = array![
             [
@@ -423,4 +994,106 @@ mod tests {
 
         assert!(result.relative_eq(&truth, 1E-6, 1E-2));
     }
+
+    #[test]
+    fn test_sinkhorn_semi_relaxed() {
+        let a = array![0.2, 0.3, 0.5];
+        let b = array![1.0, 0.5, 1.5];
+        let m = array![[0., 1., 4.], [1., 0., 1.], [4., 1., 0.]];
+
+        // Hard source marginal, relaxed target marginal
+        let mut solver = super::SinkhornKnoppUnbalanced::new(&a, &b, &m, 0.1, 1.0);
+        let plan = match solver.reg_m_source(f64::INFINITY).iterations(10000).solve() {
+            Ok(result) => result,
+            Err(error) => panic!("{:?}", error),
+        };
+
+        assert!(solver.log().converged);
+        assert!(plan.sum_axis(Axis(1)).abs_diff_eq(&a, 1E-6));
+
+        let loss = match solver.loss() {
+            Ok(result) => result,
+            Err(error) => panic!("{:?}", error),
+        };
+
+        assert!(loss.is_finite());
+
+        // Hard target marginal only
+        let plan = match super::SinkhornKnoppUnbalanced::new(&a, &b, &m, 0.1, 1.0)
+            .reg_m_target(f64::INFINITY)
+            .solve()
+        {
+            Ok(result) => result,
+            Err(error) => panic!("{:?}", error),
+        };
+
+        assert!(plan.sum_axis(Axis(0)).abs_diff_eq(&b, 1E-6));
+    }
+
+    #[test]
+    fn test_sinkhorn_log_unbalanced() {
+        let a: Array1<f64> = array![0.2, 0.3, 0.5];
+        let b: Array1<f64> = array![1.0, 0.5, 1.5];
+        let m = array![[0., 1., 4.], [1., 0., 1.], [4., 1., 0.]] / 4.;
+
+        // Same plan and loss as the Sinkhorn-Knopp iterations
+        let mut solver = super::SinkhornLogUnbalanced::new(&a, &b, &m, 0.1, 1.0);
+        let plan = match solver.solve() {
+            Ok(result) => result,
+            Err(error) => panic!("{:?}", error),
+        };
+
+        assert!(solver.log().converged);
+
+        let mut truth_solver = super::SinkhornKnoppUnbalanced::new(&a, &b, &m, 0.1, 1.0);
+        let truth = match truth_solver.solve() {
+            Ok(result) => result,
+            Err(error) => panic!("{:?}", error),
+        };
+
+        assert!(plan.abs_diff_eq(&truth, 1E-8));
+        assert!((solver.loss().unwrap() - truth_solver.loss().unwrap()).abs() < 1E-8);
+
+        // Small regularization and hard source marginal, exp(-M/reg) underflows
+        let plan = match super::SinkhornLogUnbalanced::new(&a, &b, &m, 1E-4, 1.0)
+            .reg_m_source(f64::INFINITY)
+            .solve()
+        {
+            Ok(result) => result,
+            Err(error) => panic!("{:?}", error),
+        };
+
+        assert!(plan.iter().all(|g| g.is_finite()));
+        assert!(plan.sum_axis(Axis(1)).abs_diff_eq(&a, 1E-6));
+    }
+
+    #[test]
+    fn test_sinkhorn_unbalanced_loss() {
+        let a = array![0.5, 0.5];
+        let b = array![1.0, 1.0];
+        let reg = 1.0;
+        let reg_m = 1.0;
+        let m = array![[0.0, 1.0], [1.0, 0.0]];
+
+        let mut solver = super::SinkhornKnoppUnbalanced::new(&a, &b, &m, reg, reg_m);
+
+        let plan = match solver.solve() {
+            Ok(result) => result,
+            Err(error) => panic!("{:?}", error),
+        };
+
+        let loss = match solver.loss() {
+            Ok(result) => result,
+            Err(error) => panic!("{:?}", error),
+        };
+
+        // By symmetry u = (2 / c)^(1/3) / 2 and v = (2 / c)^(1/3) with c = 1 + exp(-1), the
+        // plan is [[x, y], [y, x]] with x = u * v and y = x * exp(-1)
+        let x = (2. / (1. + (-1f64).exp())).powf(2. / 3.) / 2.;
+        let truth_plan = array![[x, x / 1f64.exp()], [x / 1f64.exp(), x]];
+        assert!(plan.abs_diff_eq(&truth_plan, 1E-8));
+
+        // <G, M> + reg * KL(G | a b^T) + reg_m * (KL(G 1 | a) + KL(G^T 1 | b))
+        assert!((loss - 0.935044228364998).abs() < 1E-8);
+    }
 }
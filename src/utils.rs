@@ -2,6 +2,7 @@ use ndarray::prelude::*;
 use ndarray_linalg::cholesky::*;
 use ndarray_rand::rand::{thread_rng, Rng};
 use ndarray_rand::rand_distr::StandardNormal;
+use num_traits::Float;
 
 use anyhow::anyhow;
 use thiserror::Error;
@@ -19,20 +20,18 @@ pub enum DistributionError {
 /// n: number of bins in histogram
 /// mean: mean value of distribution
 /// std: standard distribution of distribution
-pub fn get_1D_gauss_histogram(
+pub fn get_1D_gauss_histogram<A: Float>(
     n: usize,
-    mean: f64,
-    std: f64,
-) -> Result<Array1<f64>, DistributionError> {
-    let x = Array1::<f64>::range(0.0, n as f64, 1.0);
-    let var = std.powf(2.0);
-    let denom = 2.0 * var;
-    let diff = &x - mean;
-    let numerator = -&diff * &diff;
-    let mut result: Array1<f64> = numerator.iter().map(|val| (val / denom).exp()).collect();
+    mean: A,
+    std: A,
+) -> Result<Array1<A>, DistributionError> {
+    let x = Array1::<A>::range(A::zero(), A::from(n).unwrap(), A::one());
+    let var = std.powi(2);
+    let denom = A::from(2.0).unwrap() * var;
+    let mut result: Array1<A> = x.mapv(|x| (-(x - mean) * (x - mean) / denom).exp());
     let summed_val = result.sum();
 
-    result /= summed_val;
+    result.mapv_inplace(|x| x / summed_val);
 
     // TODO: add error handling
 
@@ -43,11 +42,11 @@ pub fn get_1D_gauss_histogram(
 /// n: number of samples to take
 /// mean: mean values (x,y) of distribution
 /// cov: covariance matrix of the distribution
-pub fn sample_2D_gauss(
+pub fn sample_2D_gauss<A: Float>(
     n: usize,
-    mean: &Array1<f64>,
-    cov: &Array2<f64>,
-) -> Result<Array2<f64>, DistributionError> {
+    mean: &Array1<A>,
+    cov: &Array2<A>,
+) -> Result<Array2<A>, DistributionError> {
     let cov_shape = cov.shape();
 
     if mean.is_empty() || cov.is_empty() {
@@ -71,7 +70,8 @@ pub fn sample_2D_gauss(
 
     // add small perturbation to covariance matrix for numerical stability
     let epsilon = 0.0001;
-    let cov_perturbed = cov + Array2::<f64>::eye(cov_shape[0]) * epsilon;
+    let cov_perturbed =
+        cov.mapv(|x| x.to_f64().unwrap()) + Array2::<f64>::eye(cov_shape[0]) * epsilon;
 
     // Compute cholesky decomposition
     let lower = match cov_perturbed.cholesky(UPLO::Lower) {
@@ -79,7 +79,9 @@ pub fn sample_2D_gauss(
         Err(_) => return Err(DistributionError::Other(anyhow!("oops!"))),
     };
 
-    Ok(mean + samples.dot(&lower))
+    let mean = mean.mapv(|x| x.to_f64().unwrap());
+
+    Ok((mean + samples.dot(&lower)).mapv(|x| A::from(x).unwrap()))
 }
 
 #[cfg(test)]